@@ -0,0 +1,114 @@
+//! Benchmarks syncing a large addon, the way `gdenv sync` does.
+//!
+//! Run with `cargo bench -p gdenv-lib --bench file_sync`. The generated addon has
+//! `GDENV_BENCH_FILES` files (2000 by default) of `GDENV_BENCH_FILE_KB` KiB each (256 by
+//! default), spread over 20 directories.
+
+use gdenv_lib::file_sync::{HashCache, apply_sync_plan, plan_sync};
+use std::fs::{self, File};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+use walkdir::WalkDir;
+
+fn main() -> anyhow::Result<()> {
+    let files = env_or("GDENV_BENCH_FILES", 2000);
+    let file_kb = env_or("GDENV_BENCH_FILE_KB", 256);
+    let tmp_dir = tempfile::Builder::new().prefix("gdenv-bench").tempdir()?;
+    let source = tmp_dir.path().join("source");
+    let dest = tmp_dir.path().join("dest");
+    let cache_path = tmp_dir.path().join("file_hashes.json");
+
+    println!(
+        "Generating {} files of {} KiB ({} MiB)",
+        files,
+        file_kb,
+        files * file_kb / 1024
+    );
+    // Files are an hour old, so their hashes can be cached
+    let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+    for i in 0..files {
+        let dir = source.join(format!("dir{}", i % 20));
+        fs::create_dir_all(&dir)?;
+        let contents: Vec<u8> = (0..file_kb * 1024).map(|j| (i * 31 + j) as u8).collect();
+        write(&dir.join(format!("file{i}.bin")), &contents, an_hour_ago)?;
+    }
+
+    let sync = |name: &str| -> anyhow::Result<()> {
+        let start = Instant::now();
+        let hashes = HashCache::load(&cache_path);
+        let plan = plan_sync(&source, &dest, None, None, false, &hashes)?;
+        let planned = start.elapsed();
+        apply_sync_plan(&source, &dest, &plan)?;
+        hashes.save()?;
+        println!(
+            "{:<44} {:>9.1?} ({:.1?} planning, {} changes)",
+            name,
+            start.elapsed(),
+            planned,
+            plan.changes().len()
+        );
+        Ok(())
+    };
+
+    sync("Initial sync")?;
+    sync("Unchanged, same modification times")?;
+
+    // E.g. a destination synced by an older gdenv, which didn't keep modification times
+    for entry in WalkDir::new(&dest).into_iter().filter_map(|e| e.ok()) {
+        if entry.file_type().is_file() {
+            File::options()
+                .write(true)
+                .open(entry.path())?
+                .set_modified(an_hour_ago - Duration::from_secs(60))?;
+        }
+    }
+    let _ = fs::remove_file(&cache_path);
+    sync("Unchanged, other modification times")?;
+    sync("Unchanged, other modification times, cached")?;
+
+    for i in (0..files).step_by(100) {
+        let path = source.join(format!("dir{}/file{i}.bin", i % 20));
+        let contents: Vec<u8> = (0..file_kb * 1024).map(|j| (i + j) as u8).collect();
+        write(&path, &contents, SystemTime::now())?;
+    }
+    sync("1% of the files changed")?;
+
+    // How gdenv used to compare the directories
+    let start = Instant::now();
+    let _ = hash_all_files(&source)? ^ hash_all_files(&dest)?;
+    println!(
+        "{:<44} {:>9.1?}",
+        "Reading and hashing both sides (before)",
+        start.elapsed()
+    );
+    Ok(())
+}
+
+fn env_or(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn write(path: &Path, contents: &[u8], modified: SystemTime) -> anyhow::Result<()> {
+    fs::write(path, contents)?;
+    File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(modified)?;
+    Ok(())
+}
+
+fn hash_all_files(dir: &Path) -> anyhow::Result<u64> {
+    let mut combined = 0;
+    for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+        if entry.file_type().is_file() {
+            let mut hasher = DefaultHasher::new();
+            fs::read(entry.path())?.hash(&mut hasher);
+            combined ^= hasher.finish();
+        }
+    }
+    Ok(combined)
+}
@@ -0,0 +1,501 @@
+//! Addons can declare the addons they depend on with `[addon.<name>]` tables in a
+//! `gdenv-addon.toml` (or `gdenv.toml`) file of their own, so projects don't have to
+//! list every transitive dependency by hand.
+
+use crate::addons::{resolve_addon, sorted_addons};
+use crate::config::Config;
+use crate::git::GitClient;
+use crate::project_specification::{
+    AddonSource, AddonSpec, GitAddonSource, ProjectSpecification, validate_addon_spec,
+};
+use anyhow::{Context, Result, bail};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+/// Files an addon's dependencies are read from, in order of preference. They're looked for in
+/// the addon's source directory first, then at the root of its repository or archive.
+pub const ADDON_DEPENDENCY_FILES: [&str; 2] = ["gdenv-addon.toml", "gdenv.toml"];
+
+/// The part of an addon's dependency file gdenv reads. Other tables, like the `[godot]`
+/// table of an addon's own development project, are ignored.
+#[derive(Deserialize)]
+struct AddonDependencies {
+    #[serde(default)]
+    addon: HashMap<String, AddonSpec>,
+}
+
+/// The addons of a project, including the ones other addons depend on.
+#[derive(Debug, Clone)]
+pub struct AddonTree {
+    /// The project specification, with the dependencies added to its addons.
+    pub project_spec: ProjectSpecification,
+    /// Addons declared in the project's configuration file, in name order.
+    pub roots: Vec<String>,
+    /// The dependencies each addon declares, in name order.
+    pub dependencies: HashMap<String, Vec<String>>,
+    /// The addon each dependency was first declared by. Addons declared in the project's
+    /// configuration file aren't included.
+    pub required_by: HashMap<String, String>,
+}
+
+/// An addon in [`AddonTree::walk`] order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddonTreeEntry {
+    pub addon_name: String,
+    /// 0 for addons declared in the project's configuration file.
+    pub depth: usize,
+    /// Whether this is the last dependency of its parent.
+    pub is_last: bool,
+    /// Whether the addon appeared earlier in the tree, in which case its dependencies
+    /// aren't repeated.
+    pub is_repeated: bool,
+}
+
+impl AddonTree {
+    /// Visits the tree depth-first, e.g. to print it.
+    pub fn walk(&self) -> Vec<AddonTreeEntry> {
+        let mut entries = Vec::new();
+        let mut visited = Vec::new();
+        self.walk_level(&self.roots, 0, &mut visited, &mut entries);
+        entries
+    }
+
+    fn walk_level(
+        &self,
+        addon_names: &[String],
+        depth: usize,
+        visited: &mut Vec<String>,
+        entries: &mut Vec<AddonTreeEntry>,
+    ) {
+        for (i, addon_name) in addon_names.iter().enumerate() {
+            let is_repeated = visited.contains(addon_name);
+            entries.push(AddonTreeEntry {
+                addon_name: addon_name.clone(),
+                depth,
+                is_last: i + 1 == addon_names.len(),
+                is_repeated,
+            });
+            if is_repeated {
+                continue;
+            }
+            visited.push(addon_name.clone());
+            if let Some(dependencies) = self.dependencies.get(addon_name) {
+                self.walk_level(dependencies, depth + 1, visited, entries);
+            }
+        }
+    }
+}
+
+/// Reads the dependencies of the project's addons, and of their dependencies, fetching up to
+/// `jobs` addons at a time.
+///
+/// An addon declared in the project's configuration file takes precedence over the
+/// dependencies of the same name that addons declare. Otherwise, two addons that require the
+/// same dependency with different sources, or the same git repository at different revisions,
+/// are a conflict, which is resolved by declaring the dependency in the project's configuration.
+pub async fn resolve_addon_tree<G: GitClient>(
+    config: &Config,
+    project_spec: &ProjectSpecification,
+    git_client: &G,
+    jobs: usize,
+) -> Result<AddonTree> {
+    let roots: Vec<String> = sorted_addons(project_spec)
+        .into_iter()
+        .map(|(addon_name, _)| addon_name.clone())
+        .collect();
+    let mut expanded_spec = project_spec.clone();
+    let mut dependencies: HashMap<String, Vec<String>> = HashMap::new();
+    let mut required_by: HashMap<String, String> = HashMap::new();
+
+    let mut level = roots.clone();
+    while !level.is_empty() {
+        let addons: Vec<(String, AddonSpec)> = level
+            .iter()
+            .map(|addon_name| (addon_name.clone(), expanded_spec.addons[addon_name].clone()))
+            .collect();
+        // Addons from the same repository are read one after the other, since reading them
+        // checks the repository out
+        let mut groups: Vec<Vec<&(String, AddonSpec)>> = Vec::new();
+        for addon in &addons {
+            let same_repo = |other: &&(String, AddonSpec)| {
+                matches!(
+                    (&addon.1.source, &other.1.source),
+                    (AddonSource::Git(a), AddonSource::Git(b)) if a.git == b.git
+                )
+            };
+            match groups.iter_mut().find(|group| group.iter().any(same_repo)) {
+                Some(group) => group.push(addon),
+                None => groups.push(vec![addon]),
+            }
+        }
+        let expanded_spec_ref = &expanded_spec;
+        let mut results: HashMap<&String, Result<Vec<(String, AddonSpec)>>> =
+            futures_util::stream::iter(groups)
+                .map(async |group| {
+                    let mut results = Vec::new();
+                    for (addon_name, addon_spec) in group {
+                        let result = read_addon_dependencies(
+                            config,
+                            expanded_spec_ref,
+                            git_client,
+                            addon_name,
+                            addon_spec,
+                        )
+                        .await
+                        .context(format!(
+                            "Failed to read the dependencies of addon \"{addon_name}\""
+                        ));
+                        results.push((addon_name, result));
+                    }
+                    results
+                })
+                .buffered(jobs.max(1))
+                .flat_map(futures_util::stream::iter)
+                .collect()
+                .await;
+
+        let mut next_level = Vec::new();
+        for (addon_name, _) in &addons {
+            let result = results.remove(addon_name).expect("every addon was read");
+            for (dependency_name, dependency_spec) in result? {
+                dependencies
+                    .entry(addon_name.clone())
+                    .or_default()
+                    .push(dependency_name.clone());
+                if let Some(existing) = expanded_spec.addons.get(&dependency_name) {
+                    if let Some(first_required_by) = required_by.get(&dependency_name)
+                        && *existing != dependency_spec
+                    {
+                        bail!(
+                            "Addons \"{}\" and \"{}\" require different sources for addon \"{}\". \
+                             Declare \"{}\" in the project's configuration file to choose one.",
+                            first_required_by,
+                            addon_name,
+                            dependency_name,
+                            dependency_name
+                        );
+                    }
+                    continue;
+                }
+                check_repository_conflicts(
+                    &expanded_spec,
+                    &required_by,
+                    &dependency_name,
+                    &dependency_spec,
+                    addon_name,
+                )?;
+                expanded_spec
+                    .addons
+                    .insert(dependency_name.clone(), dependency_spec);
+                required_by.insert(dependency_name.clone(), addon_name.clone());
+                next_level.push(dependency_name);
+            }
+        }
+        level = next_level;
+    }
+
+    Ok(AddonTree {
+        project_spec: expanded_spec,
+        roots,
+        dependencies,
+        required_by,
+    })
+}
+
+/// Fails if another addon uses the git repository of the new dependency at another revision.
+fn check_repository_conflicts(
+    project_spec: &ProjectSpecification,
+    required_by: &HashMap<String, String>,
+    dependency_name: &str,
+    dependency_spec: &AddonSpec,
+    dependent_name: &str,
+) -> Result<()> {
+    let AddonSource::Git(dependency_git) = &dependency_spec.source else {
+        return Ok(());
+    };
+    for (addon_name, addon_spec) in sorted_addons(project_spec) {
+        let AddonSource::Git(git) = &addon_spec.source else {
+            continue;
+        };
+        if git.git != dependency_git.git
+            || (git.rev == dependency_git.rev && git.version == dependency_git.version)
+        {
+            continue;
+        }
+        let origin = match required_by.get(addon_name) {
+            Some(dependent) => format!("required by \"{dependent}\""),
+            None => "declared in the project's configuration file".to_string(),
+        };
+        bail!(
+            "Conflicting revisions of {}: addon \"{}\" (required by \"{}\") uses {}, \
+             but addon \"{}\" ({}) uses {}. \
+             Declare \"{}\" in the project's configuration file to choose a revision.",
+            git.git,
+            dependency_name,
+            dependent_name,
+            describe_rev(dependency_git),
+            addon_name,
+            origin,
+            describe_rev(git),
+            dependency_name
+        );
+    }
+    Ok(())
+}
+
+fn describe_rev(git: &GitAddonSource) -> String {
+    match (&git.rev, &git.version) {
+        (Some(rev), _) => format!("rev \"{rev}\""),
+        (None, Some(version)) => format!("version \"{version}\""),
+        (None, None) => "the default branch".to_string(),
+    }
+}
+
+/// Reads the dependencies the addon declares, in name order. Paths of local dependencies
+/// are made relative to the dependency file rather than the project.
+async fn read_addon_dependencies<G: GitClient>(
+    config: &Config,
+    project_spec: &ProjectSpecification,
+    git_client: &G,
+    addon_name: &str,
+    addon_spec: &AddonSpec,
+) -> Result<Vec<(String, AddonSpec)>> {
+    let Some(resolved) =
+        resolve_addon(config, project_spec, git_client, addon_name, addon_spec).await?
+    else {
+        return Ok(Vec::new());
+    };
+
+    let dependency_file = [&resolved.source_dir, &resolved.source_root]
+        .into_iter()
+        .flat_map(|dir| ADDON_DEPENDENCY_FILES.map(|file_name| dir.join(file_name)))
+        .find(|path| path.is_file());
+    let Some(dependency_file) = dependency_file else {
+        return Ok(Vec::new());
+    };
+    let contents = fs::read_to_string(&dependency_file)?;
+    let file: AddonDependencies = toml::from_str(&contents)
+        .context(format!("Failed to parse {}", dependency_file.display()))?;
+    tracing::debug!(
+        "Addon {} declares {} dependencies in {:?}",
+        addon_name,
+        file.addon.len(),
+        dependency_file
+    );
+    for (name, spec) in &file.addon {
+        if let Err(e) = validate_addon_spec(name, spec) {
+            bail!("Invalid dependency in {}: {e}", dependency_file.display());
+        }
+    }
+
+    let dependency_dir = dependency_file.parent().unwrap_or(&resolved.source_dir);
+    let mut addons: Vec<(String, AddonSpec)> = file
+        .addon
+        .into_iter()
+        .map(|(name, mut spec)| {
+            if let AddonSource::Local(local) = &mut spec.source {
+                local.path = dependency_dir.join(&local.path);
+            }
+            (name, spec)
+        })
+        .collect();
+    addons.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(addons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project_specification::load_godot_project_spec;
+    use crate::test_helpers::mock_git_client::MockGitClient;
+    use std::path::Path;
+
+    fn write_dependencies(dir: &Path, dependencies: &str) -> Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join("plugin.cfg"), "[plugin]")?;
+        fs::write(dir.join("gdenv-addon.toml"), dependencies)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_addon_tree() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = MockGitClient::new(config.clone());
+        let sources_dir = tmp_dir.path().join("sources");
+
+        // dialogue -> state-machine -> utils, and dialogue -> utils
+        write_dependencies(
+            &sources_dir.join("dialogue"),
+            r#"
+[addon.state-machine]
+path = "../state-machine"
+
+[addon.utils]
+git = "https://example.com/utils.git"
+rev = "v1"
+subdir = "addons/test-addon1"
+            "#,
+        )?;
+        write_dependencies(
+            &sources_dir.join("state-machine"),
+            r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.utils]
+git = "https://example.com/utils.git"
+rev = "v1"
+subdir = "addons/test-addon1"
+            "#,
+        )?;
+        let project_dir = tmp_dir.path().join("project");
+        fs::create_dir_all(&project_dir)?;
+        let spec = r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.dialogue]
+path = "../sources/dialogue"
+        "#;
+        fs::write(project_dir.join("gdenv.toml"), spec)?;
+        let project_spec =
+            load_godot_project_spec(&project_dir, |_| bail!("Test lambda not implemented."))?;
+
+        let tree = resolve_addon_tree(&config, &project_spec, &git_client, 4).await?;
+        let mut addon_names: Vec<&String> = tree.project_spec.addons.keys().collect();
+        addon_names.sort();
+        assert_eq!(addon_names, vec!["dialogue", "state-machine", "utils"]);
+        assert_eq!(tree.required_by["state-machine"], "dialogue");
+        assert!(matches!(
+            &tree.project_spec.addons["state-machine"].source,
+            AddonSource::Local(local) if local.path == project_dir.join("../sources/dialogue/../state-machine")
+        ));
+        let walk = tree.walk();
+        let walk: Vec<(usize, &str, bool)> = walk
+            .iter()
+            .map(|e| (e.depth, e.addon_name.as_str(), e.is_repeated))
+            .collect();
+        assert_eq!(
+            walk,
+            vec![
+                (0, "dialogue", false),
+                (1, "state-machine", false),
+                (2, "utils", false),
+                (1, "utils", true),
+            ]
+        );
+
+        // Requiring the same repository at another revision is a conflict...
+        write_dependencies(
+            &sources_dir.join("state-machine"),
+            r#"
+[addon.utils-v2]
+git = "https://example.com/utils.git"
+rev = "v2"
+            "#,
+        )?;
+        let error = resolve_addon_tree(&config, &project_spec, &git_client, 4)
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("Conflicting revisions"),
+            "{error}"
+        );
+
+        // ...and so is requiring the same addon from another source...
+        write_dependencies(
+            &sources_dir.join("state-machine"),
+            r#"
+[addon.utils]
+git = "https://example.com/utils.git"
+rev = "v2"
+subdir = "addons/test-addon1"
+            "#,
+        )?;
+        let error = resolve_addon_tree(&config, &project_spec, &git_client, 4)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("different sources"), "{error}");
+
+        // ...unless the project declares the addon itself
+        fs::write(
+            project_dir.join("gdenv.toml"),
+            format!(
+                "{spec}\n[addon.utils]\ngit = \"https://example.com/utils.git\"\nrev = \"v3\"\n"
+            ),
+        )?;
+        let project_spec =
+            load_godot_project_spec(&project_dir, |_| bail!("Test lambda not implemented."))?;
+        let tree = resolve_addon_tree(&config, &project_spec, &git_client, 4).await?;
+        assert!(matches!(
+            &tree.project_spec.addons["utils"].source,
+            AddonSource::Git(git) if git.rev.as_deref() == Some("v3")
+        ));
+        assert!(!tree.required_by.contains_key("utils"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_addon_dependency() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = MockGitClient::new(config.clone());
+        let dialogue_dir = tmp_dir.path().join("sources/dialogue");
+        let project_dir = tmp_dir.path().join("project");
+        fs::create_dir_all(&project_dir)?;
+        fs::write(
+            project_dir.join("gdenv.toml"),
+            r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.dialogue]
+path = "../sources/dialogue"
+            "#,
+        )?;
+        let project_spec =
+            load_godot_project_spec(&project_dir, |_| bail!("Test lambda not implemented."))?;
+
+        write_dependencies(
+            &dialogue_dir,
+            r#"
+[addon.utils]
+git = "https://example.com/utils.git"
+rev = "v1"
+version = "^1.0"
+            "#,
+        )?;
+        let error = resolve_addon_tree(&config, &project_spec, &git_client, 4)
+            .await
+            .unwrap_err();
+        let error = format!("{error:#}");
+        assert!(error.contains("both `rev` and `version`"), "{error}");
+        assert!(error.contains("gdenv-addon.toml"), "{error}");
+
+        write_dependencies(
+            &dialogue_dir,
+            r#"
+[addon.state-machine]
+path = "../state-machine"
+link = true
+exclude = ["tests/"]
+            "#,
+        )?;
+        let error = resolve_addon_tree(&config, &project_spec, &git_client, 4)
+            .await
+            .unwrap_err();
+        let error = format!("{error:#}");
+        assert!(error.contains("`link = true`"), "{error}");
+        assert!(error.contains("gdenv-addon.toml"), "{error}");
+        Ok(())
+    }
+}
@@ -0,0 +1,315 @@
+//! Compares the tags that git addons are pinned to with the tags of their repositories, to
+//! find out about new releases, and resolves `version` requirements to tags.
+
+use crate::addons::sorted_addons;
+use crate::git::{GitClient, GitTag};
+use crate::project_specification::{AddonSource, ProjectSpecification};
+use anyhow::{Context, Result, bail};
+use futures_util::StreamExt;
+use semver::{Version, VersionReq};
+
+/// The versions of a git addon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddonVersions {
+    pub addon_name: String,
+    /// The `rev` the addon is pinned to, or its `version` requirement, if any.
+    pub current: Option<String>,
+    /// Whether `current` is a `version` requirement, which syncing follows by itself,
+    /// rather than a pinned `rev`.
+    pub is_requirement: bool,
+    /// The newest tag that is semver compatible with `current`, if `current` is a version tag,
+    /// or that matches it, if it's a version requirement.
+    pub latest_compatible: Option<String>,
+    /// The newest tag. Pre-releases are only considered if `current` is one.
+    pub latest: Option<String>,
+}
+
+impl AddonVersions {
+    /// The tag to update the addon to: the latest compatible one, or the latest one if
+    /// `major` is set. `None` if the addon isn't pinned to a version tag, or already is
+    /// on the newest version.
+    pub fn update_target(&self, major: bool) -> Option<&str> {
+        if self.is_requirement {
+            return None;
+        }
+        let current = parse_tag_version(self.current.as_deref()?)?;
+        let target = if major {
+            self.latest.as_deref()?
+        } else {
+            self.latest_compatible.as_deref()?
+        };
+        (parse_tag_version(target)? > current).then_some(target)
+    }
+}
+
+/// Lists the tags of every git addon's repository, up to `jobs` at a time, and picks the
+/// newest ones. Addons from other sources are left out.
+pub async fn check_addon_versions<G: GitClient>(
+    project_spec: &ProjectSpecification,
+    git_client: &G,
+    jobs: usize,
+) -> Result<Vec<AddonVersions>> {
+    let git_addons: Vec<_> = sorted_addons(project_spec)
+        .into_iter()
+        .filter_map(|(addon_name, addon_spec)| match &addon_spec.source {
+            AddonSource::Git(git) => Some((addon_name, git)),
+            _ => None,
+        })
+        .collect();
+    futures_util::stream::iter(git_addons)
+        .map(async |(addon_name, git)| {
+            let tags = git_client
+                .list_remote_tags(&git.git)
+                .await
+                .context(format!("Failed to list the tags of addon \"{addon_name}\""))?;
+            let tags: Vec<&str> = tags.iter().map(|tag| tag.name.as_str()).collect();
+            let current = git.rev.as_deref().or(git.version.as_deref());
+            Ok(AddonVersions {
+                is_requirement: git.rev.is_none() && git.version.is_some(),
+                ..newest_versions(addon_name, current, &tags)
+            })
+        })
+        .buffered(jobs.max(1))
+        .collect::<Vec<Result<AddonVersions>>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+fn newest_versions(addon_name: &str, current: Option<&str>, tags: &[&str]) -> AddonVersions {
+    let current_version = current.and_then(parse_tag_version);
+    let allow_pre = current_version
+        .as_ref()
+        .is_some_and(|version| !version.pre.is_empty());
+    let mut versions: Vec<(Version, &str)> = tags
+        .iter()
+        .filter_map(|tag| Some((parse_tag_version(tag)?, *tag)))
+        .filter(|(version, _)| allow_pre || version.pre.is_empty())
+        .collect();
+    versions.sort();
+
+    // A `version` requirement is compatible with the versions it matches
+    let req = match current_version {
+        Some(version) => VersionReq::parse(&format!("^{version}")).ok(),
+        None => current.and_then(|current| VersionReq::parse(current).ok()),
+    };
+    let compatible = req.and_then(|req| {
+        versions
+            .iter()
+            .rev()
+            .find(|(version, _)| req.matches(version))
+    });
+    AddonVersions {
+        addon_name: addon_name.to_string(),
+        current: current.map(str::to_string),
+        is_requirement: false,
+        latest_compatible: compatible.map(|(_, tag)| tag.to_string()),
+        latest: versions.last().map(|(_, tag)| tag.to_string()),
+    }
+}
+
+/// The newest tag of `repo_url` whose version matches `version_req`, e.g. "^0.10".
+pub async fn resolve_version_tag<G: GitClient>(
+    git_client: &G,
+    repo_url: &str,
+    version_req: &str,
+) -> Result<GitTag> {
+    let req = VersionReq::parse(version_req)
+        .context(format!("Invalid version requirement \"{version_req}\""))?;
+    let tags = git_client.list_remote_tags(repo_url).await?;
+    let mut versions: Vec<(Version, GitTag)> = tags
+        .into_iter()
+        .filter_map(|tag| Some((parse_tag_version(&tag.name)?, tag)))
+        .collect();
+    versions.sort_by(|a, b| b.0.cmp(&a.0));
+
+    if let Some((_, tag)) = versions.iter().find(|(version, _)| req.matches(version)) {
+        return Ok(tag.clone());
+    }
+    if versions.is_empty() {
+        bail!("No tag of {repo_url} matches version \"{version_req}\": it has no version tags.");
+    }
+    let newest: Vec<&str> = versions
+        .iter()
+        .take(10)
+        .map(|(_, tag)| tag.name.as_str())
+        .collect();
+    bail!(
+        "No tag of {} matches version \"{}\". Newest versions: {}{}",
+        repo_url,
+        version_req,
+        newest.join(", "),
+        if versions.len() > newest.len() {
+            ", ..."
+        } else {
+            ""
+        }
+    );
+}
+
+/// Parses tags like `v1.2.3`, `1.2.3-beta.1` or `v1.2` as semantic versions.
+pub fn parse_tag_version(tag: &str) -> Option<Version> {
+    let version = tag.strip_prefix(['v', 'V']).unwrap_or(tag);
+    let core_len = version.find(['-', '+']).unwrap_or(version.len());
+    let (core, rest) = version.split_at(core_len);
+    let padding = match core.matches('.').count() {
+        0 => ".0.0",
+        1 => ".0",
+        _ => "",
+    };
+    Version::parse(&format!("{core}{padding}{rest}")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::project_specification::load_godot_project_spec;
+    use crate::test_helpers::mock_git_client::MockGitClient;
+    use anyhow::bail;
+    use std::fs;
+
+    #[test]
+    fn test_parse_tag_version() {
+        assert_eq!(parse_tag_version("v1.2.3"), Some(Version::new(1, 2, 3)));
+        assert_eq!(parse_tag_version("1.2"), Some(Version::new(1, 2, 0)));
+        assert_eq!(parse_tag_version("V2"), Some(Version::new(2, 0, 0)));
+        assert_eq!(
+            parse_tag_version("v1.0-beta.1"),
+            Version::parse("1.0.0-beta.1").ok()
+        );
+        assert_eq!(parse_tag_version("main"), None);
+        assert_eq!(parse_tag_version("release-1.0"), None);
+    }
+
+    #[tokio::test]
+    async fn test_check_addon_versions() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = MockGitClient::new(config).with_tags(&[
+            "v0.9.0",
+            "v0.10.0",
+            "v0.10.2",
+            "v0.11.0",
+            "v1.0.0-rc.1",
+            "v1.0.0",
+            "latest",
+        ]);
+        fs::write(
+            tmp_dir.path().join("gdenv.toml"),
+            r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.pinned]
+git = "https://example.com/pinned.git"
+rev = "v0.10.0"
+
+[addon.newest]
+git = "https://example.com/newest.git"
+rev = "1.0"
+
+[addon.branch]
+git = "https://example.com/branch.git"
+rev = "main"
+
+[addon.ranged]
+git = "https://example.com/ranged.git"
+version = ">=0.9, <0.11"
+
+[addon.required]
+git = "https://example.com/required.git"
+version = "0.10"
+
+[addon.local]
+path = "../local"
+            "#,
+        )?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+
+        let versions = check_addon_versions(&project_spec, &git_client, 2).await?;
+        assert_eq!(
+            versions,
+            vec![
+                AddonVersions {
+                    addon_name: "branch".to_string(),
+                    current: Some("main".to_string()),
+                    is_requirement: false,
+                    latest_compatible: None,
+                    latest: Some("v1.0.0".to_string()),
+                },
+                AddonVersions {
+                    addon_name: "newest".to_string(),
+                    current: Some("1.0".to_string()),
+                    is_requirement: false,
+                    latest_compatible: Some("v1.0.0".to_string()),
+                    latest: Some("v1.0.0".to_string()),
+                },
+                AddonVersions {
+                    addon_name: "pinned".to_string(),
+                    current: Some("v0.10.0".to_string()),
+                    is_requirement: false,
+                    latest_compatible: Some("v0.10.2".to_string()),
+                    latest: Some("v1.0.0".to_string()),
+                },
+                AddonVersions {
+                    addon_name: "ranged".to_string(),
+                    current: Some(">=0.9, <0.11".to_string()),
+                    is_requirement: true,
+                    latest_compatible: Some("v0.10.2".to_string()),
+                    latest: Some("v1.0.0".to_string()),
+                },
+                AddonVersions {
+                    addon_name: "required".to_string(),
+                    current: Some("0.10".to_string()),
+                    is_requirement: true,
+                    latest_compatible: Some("v0.10.2".to_string()),
+                    latest: Some("v1.0.0".to_string()),
+                },
+            ]
+        );
+        assert_eq!(versions[0].update_target(true), None);
+        assert_eq!(versions[3].update_target(true), None);
+        assert_eq!(versions[1].update_target(true), None);
+        assert_eq!(versions[2].update_target(false), Some("v0.10.2"));
+        assert_eq!(versions[2].update_target(true), Some("v1.0.0"));
+        // Syncing already follows a `version` requirement, which can't be pinned to a `rev`
+        assert_eq!(versions[4].update_target(false), None);
+        assert_eq!(versions[4].update_target(true), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_version_tag() -> Result<()> {
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = MockGitClient::new(config).with_tags(&[
+            "0.9.0", "v0.10.0", "v0.10.3", "v0.11.0", "v1.0.0", "nightly",
+        ]);
+        let repo_url = "https://example.com/addon.git";
+
+        let tag = resolve_version_tag(&git_client, repo_url, "^0.10").await?;
+        assert_eq!(tag.name, "v0.10.3");
+        assert_eq!(tag.commit, format!("{:040x}", 2));
+        assert_eq!(
+            resolve_version_tag(&git_client, repo_url, "<0.10")
+                .await?
+                .name,
+            "0.9.0"
+        );
+        let error = resolve_version_tag(&git_client, repo_url, "^2")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No tag of https://example.com/addon.git matches version \"^2\". Newest versions: v1.0.0, v0.11.0, v0.10.3, v0.10.0, 0.9.0"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+//! Watches the sources of local addons and the project's configuration file,
+//! so that `gdenv sync --watch` can re-sync addons as soon as they change.
+
+use crate::addons::copied_local_addon_sources;
+use crate::project_specification::ProjectSpecification;
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How long to wait for more changes before reporting them, since saving a file or checking
+/// out a branch usually produces a burst of events.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// The project's configuration file changed, so every addon may have changed.
+    SpecChanged,
+    /// Files of these local addons changed.
+    AddonsChanged(Vec<String>),
+}
+
+pub struct AddonWatcher {
+    watcher: RecommendedWatcher,
+    events: mpsc::UnboundedReceiver<notify::Result<Event>>,
+    spec_file: Option<PathBuf>,
+    /// Watched source directories by addon name, as configured and canonicalized, since
+    /// some platforms report events with canonical paths.
+    sources: Vec<(String, PathBuf, PathBuf)>,
+    watched: Vec<PathBuf>,
+}
+
+impl AddonWatcher {
+    pub fn new(project_spec: &ProjectSpecification) -> Result<Self> {
+        let (sender, events) = mpsc::unbounded_channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })
+        .context("Failed to start watching files")?;
+        let mut addon_watcher = Self {
+            watcher,
+            events,
+            spec_file: None,
+            sources: Vec::new(),
+            watched: Vec::new(),
+        };
+        addon_watcher.watch(project_spec)?;
+        Ok(addon_watcher)
+    }
+
+    /// Watches the local addons and configuration file of `project_spec`, replacing the
+    /// previously watched ones. Call this again after the configuration file changed.
+    pub fn watch(&mut self, project_spec: &ProjectSpecification) -> Result<()> {
+        for path in self.watched.drain(..) {
+            let _ = self.watcher.unwatch(&path);
+        }
+        self.sources.clear();
+
+        for (addon_name, source_dir) in copied_local_addon_sources(project_spec) {
+            if !source_dir.is_dir() {
+                tracing::warn!(
+                    "Addon {} path {:?} does not exist, not watching it",
+                    addon_name,
+                    source_dir
+                );
+                continue;
+            }
+            self.watcher
+                .watch(&source_dir, RecursiveMode::Recursive)
+                .context(format!("Failed to watch {}", source_dir.display()))?;
+            let canonical_dir = source_dir.canonicalize()?;
+            self.watched.push(source_dir.clone());
+            self.sources.push((addon_name, source_dir, canonical_dir));
+        }
+
+        // Editors often save by replacing the file, so watch its directory instead
+        self.spec_file = project_spec.spec_file_path.clone();
+        if let Some(spec_dir) = self.spec_file.as_deref().and_then(Path::parent) {
+            self.watcher
+                .watch(spec_dir, RecursiveMode::NonRecursive)
+                .context(format!("Failed to watch {}", spec_dir.display()))?;
+            self.watched.push(spec_dir.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Waits for the watched files to change.
+    pub async fn next_event(&mut self) -> Result<WatchEvent> {
+        let mut spec_changed = false;
+        let mut changed_addons: Vec<String> = Vec::new();
+        loop {
+            let event = if spec_changed || !changed_addons.is_empty() {
+                match tokio::time::timeout(DEBOUNCE, self.events.recv()).await {
+                    Ok(event) => event,
+                    Err(_) => break,
+                }
+            } else {
+                self.events.recv().await
+            };
+            let event = match event.context("Stopped watching files")? {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::warn!("Error while watching files: {}", e);
+                    continue;
+                }
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+            for path in &event.paths {
+                if self.is_spec_file(path) {
+                    spec_changed = true;
+                }
+                for (addon_name, source_dir, canonical_dir) in &self.sources {
+                    if (path.starts_with(source_dir) || path.starts_with(canonical_dir))
+                        && !changed_addons.contains(addon_name)
+                    {
+                        changed_addons.push(addon_name.clone());
+                    }
+                }
+            }
+        }
+
+        if spec_changed {
+            Ok(WatchEvent::SpecChanged)
+        } else {
+            changed_addons.sort();
+            Ok(WatchEvent::AddonsChanged(changed_addons))
+        }
+    }
+
+    fn is_spec_file(&self, path: &Path) -> bool {
+        let Some(spec_file) = &self.spec_file else {
+            return false;
+        };
+        path == spec_file
+            || (path.file_name() == spec_file.file_name()
+                && path.parent().and_then(|dir| dir.canonicalize().ok())
+                    == spec_file.parent().and_then(|dir| dir.canonicalize().ok()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project_specification::load_godot_project_spec;
+    use anyhow::bail;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_watch_local_addons() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let addon1_dir = tmp_dir.path().join("addon-sources/addon1");
+        let addon2_dir = tmp_dir.path().join("addon-sources/addon2");
+        fs::create_dir_all(&addon1_dir)?;
+        fs::create_dir_all(&addon2_dir)?;
+        let spec = r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.addon1]
+path = "addon-sources/addon1"
+
+[addon.addon2]
+path = "addon-sources/addon2"
+link = true
+        "#;
+        fs::write(tmp_dir.path().join("gdenv.toml"), spec)?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+        let mut watcher = AddonWatcher::new(&project_spec)?;
+        let timeout = Duration::from_secs(10);
+
+        fs::write(addon1_dir.join("plugin.gd"), "extends EditorPlugin")?;
+        assert_eq!(
+            tokio::time::timeout(timeout, watcher.next_event()).await??,
+            WatchEvent::AddonsChanged(vec!["addon1".to_string()])
+        );
+
+        // Linked addons aren't watched, but the configuration file is
+        fs::write(addon2_dir.join("plugin.gd"), "extends EditorPlugin")?;
+        fs::write(
+            tmp_dir.path().join("gdenv.toml"),
+            spec.replace("link = true", ""),
+        )?;
+        assert_eq!(
+            tokio::time::timeout(timeout, watcher.next_event()).await??,
+            WatchEvent::SpecChanged
+        );
+
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+        watcher.watch(&project_spec)?;
+        fs::write(addon2_dir.join("plugin.gd"), "extends EditorPlugin\n")?;
+        assert_eq!(
+            tokio::time::timeout(timeout, watcher.next_event()).await??,
+            WatchEvent::AddonsChanged(vec!["addon2".to_string()])
+        );
+        Ok(())
+    }
+}
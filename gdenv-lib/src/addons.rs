@@ -1,132 +1,777 @@
-use crate::file_sync::sync_recursive;
-use crate::git::GitClient;
-use crate::project_specification::{
-    AddonSource, AddonSpec, GitAddonSource, LocalAddonSource, ProjectSpecification,
+use crate::addon_updates::resolve_version_tag;
+use crate::archive::ArchiveClient;
+use crate::asset_library::AssetLibraryClient;
+use crate::config::Config;
+use crate::editor_plugins::find_plugins;
+use crate::file_lock::FileLock;
+use crate::file_sync::{
+    HashCache, SyncPlan, apply_sync_plan, create_dir_link, is_link, plan_sync, remove_link,
+    text_diff,
 };
-use anyhow::Result;
+use crate::git::{GitClient, GitTag, sparse_checkout_dirs};
+use crate::project_specification::{AddonSource, AddonSpec, ProjectSpecification};
+use crate::sync_manifest::{AddonManifest, SYNC_MANIFEST_FILE, SyncManifest};
+use crate::sync_snapshot::SyncSnapshot;
+use anyhow::{Result, bail};
+use futures_util::StreamExt;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-#[allow(dead_code)]
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Overwrite files that were changed by hand since the last sync.
+    pub force: bool,
+    /// Sync only the addons with these names, instead of every addon of the project.
+    pub addons: Option<Vec<String>>,
+    /// Maximum number of addons to fetch at the same time. Treated as 1 if 0.
+    pub jobs: usize,
+    /// Stop at the first addon that fails, instead of carrying on with the others.
+    pub fail_fast: bool,
+    /// Where to save the files that the sync overwrites or removes, to share one snapshot
+    /// between syncing addons and removing orphaned ones. Each sync gets a snapshot of its
+    /// own otherwise.
+    pub snapshot: Option<Arc<SyncSnapshot>>,
+}
+
+impl SyncOptions {
+    fn snapshot(&self, config: &Config, project_root_dir: &Path) -> Result<Arc<SyncSnapshot>> {
+        match &self.snapshot {
+            Some(snapshot) => Ok(snapshot.clone()),
+            None => Ok(Arc::new(SyncSnapshot::new(config, project_root_dir)?)),
+        }
+    }
+}
+
+/// How fetching, planning or syncing one addon went.
+#[derive(Debug)]
+pub struct AddonSyncOutcome {
+    pub addon_name: String,
+    pub elapsed: Duration,
+    /// The changes made (or to be made), or the reason the addon failed.
+    pub result: Result<AddonSyncPlan>,
+}
+
+/// The changes a sync will make to one addon's destination directory.
+#[derive(Debug, Clone)]
+pub struct AddonSyncPlan {
+    pub addon_name: String,
+    pub source_dir: PathBuf,
+    pub dest_dir: PathBuf,
+    /// Whether `dest_dir` is a link to `source_dir` instead of a copy of its files.
+    pub link: bool,
+    /// Whether `dest_dir` has to be (re)created as a link to `source_dir`.
+    pub relink: bool,
+    /// For linked addons, the differences between `source_dir` and the directory the link
+    /// replaces, if any.
+    pub plan: SyncPlan,
+    /// Text diffs of the modified files, if requested.
+    pub diffs: Vec<(PathBuf, String)>,
+    /// Files the sync would overwrite or remove that were changed by hand since the last sync,
+    /// relative to `dest_dir`.
+    pub local_changes: Vec<PathBuf>,
+    /// Patch that re-applies the local changes on top of the synced files.
+    pub local_changes_patch: String,
+    /// For git addons with a `version` requirement, the tag it resolved to.
+    pub resolved_tag: Option<GitTag>,
+}
+
+impl AddonSyncPlan {
+    pub fn is_up_to_date(&self) -> bool {
+        self.plan.is_empty() && !self.relink
+    }
+}
+
+/// An addon whose source files are available on disk.
+pub(crate) struct ResolvedAddon {
+    project_root_dir: PathBuf,
+    pub(crate) source_dir: PathBuf,
+    /// The checkout, download or directory `source_dir` is part of.
+    pub(crate) source_root: PathBuf,
+    dest_dir: PathBuf,
+    /// `dest_dir` relative to the project root directory.
+    destination: PathBuf,
+    resolved_tag: Option<GitTag>,
+    /// For git addons, the repository the source directory is checked out from.
+    repo_url: Option<String>,
+    /// Held while the source directory is read, e.g. a git checkout that other addons share.
+    source_lock: Option<FileLock>,
+}
+
+/// The plans computed by [`plan_addons`], together with the addon sources they were
+/// computed from.
+pub struct PlannedAddons {
+    pub outcomes: Vec<AddonSyncOutcome>,
+    /// The sources of the planned addons, by addon name, so [`apply_addon_plans`] copies
+    /// exactly the files that were planned.
+    resolved: BTreeMap<String, ResolvedAddon>,
+}
+
+/// Downloads the addon sources and computes what syncing them would change,
+/// without modifying the project. See [`sync_addons`] for how addons are processed.
+pub async fn plan_addons<G: GitClient>(
+    config: &Config,
+    project_spec: &ProjectSpecification,
+    git_client: &G,
+    with_diffs: bool,
+    options: &SyncOptions,
+) -> Result<PlannedAddons> {
+    let manifest = SyncManifest::load(&project_spec.project_root_dir)?;
+    let hashes = HashCache::load(&config.file_hashes_file);
+    let resolved_addons = Mutex::new(BTreeMap::new());
+    let outcomes = run_per_addon(project_spec, options, async |addon_name, addon_spec| {
+        let Some(mut resolved) =
+            resolve_addon(config, project_spec, git_client, addon_name, addon_spec).await?
+        else {
+            return Ok(None);
+        };
+        let plan = plan_addon(
+            addon_name, addon_spec, &resolved, &manifest, &hashes, with_diffs,
+        )?;
+        // Other addons from the same repository are planned next, and the plans may not be
+        // applied at all, so the repository is locked again to apply them
+        resolved.source_lock = None;
+        resolved_addons
+            .lock()
+            .expect("Resolved addons lock poisoned")
+            .insert(addon_name.clone(), resolved);
+        Ok(Some(plan))
+    })
+    .await;
+    save_hash_cache(&hashes);
+    Ok(PlannedAddons {
+        outcomes,
+        resolved: resolved_addons
+            .into_inner()
+            .expect("Resolved addons lock poisoned"),
+    })
+}
+
+/// Applies the plans of [`plan_addons`] that succeeded, from the same addon sources and
+/// without fetching anything again, and records the synced files in the project's
+/// [`SyncManifest`]. Returns the changes that were made to each addon, in name order.
+/// A failing addon doesn't stop the others, unless `options.fail_fast` is set.
+pub async fn apply_addon_plans<G: GitClient>(
+    config: &Config,
+    project_spec: &ProjectSpecification,
+    git_client: &G,
+    planned: &PlannedAddons,
+    options: &SyncOptions,
+) -> Result<Vec<AddonSyncOutcome>> {
+    let mut manifest = SyncManifest::load(&project_spec.project_root_dir)?;
+    let hashes = HashCache::load(&config.file_hashes_file);
+    let snapshot = options.snapshot(config, &project_spec.project_root_dir)?;
+    let mut outcomes = Vec::new();
+    for outcome in &planned.outcomes {
+        let (Ok(plan), Some(resolved)) =
+            (&outcome.result, planned.resolved.get(&outcome.addon_name))
+        else {
+            continue;
+        };
+        if let Some(addons) = &options.addons
+            && !addons.contains(&outcome.addon_name)
+        {
+            continue;
+        }
+        let start = Instant::now();
+        let result = async {
+            let _source_lock = match &resolved.repo_url {
+                Some(repo_url) => Some(git_client.lock_repo(repo_url).await?),
+                None => None,
+            };
+            if !resolved.source_dir.exists() {
+                bail!(
+                    "{} was removed since the sync was planned",
+                    resolved.source_dir.display()
+                );
+            }
+            apply_addon_plan(plan, resolved, &mut manifest, &hashes, &snapshot, options)
+        }
+        .await
+        .map(|()| plan.clone());
+        let failed = result.is_err();
+        outcomes.push(AddonSyncOutcome {
+            addon_name: outcome.addon_name.clone(),
+            elapsed: start.elapsed(),
+            result,
+        });
+        if failed && options.fail_fast {
+            break;
+        }
+    }
+    save_hash_cache(&hashes);
+    Ok(outcomes)
+}
+
+/// Syncs the addons of the project and records the synced files in the project's
+/// [`SyncManifest`]. Returns the changes that were made to each addon, in name order.
+///
+/// Addons are fetched concurrently, up to `options.jobs` at a time. Addons that share a git
+/// repository, or whose destinations are nested in each other, are synced one after the other
+/// in name order, so the result never depends on which download finishes first.
+/// A failing addon doesn't stop the others, unless `options.fail_fast` is set.
 pub async fn sync_addons<G: GitClient>(
+    config: &Config,
     project_spec: ProjectSpecification,
     git_client: &G,
-) -> Result<()> {
-    for (addon_name, addon_spec) in project_spec.addons {
-        let project_dir = project_spec
-            .project_root_dir
-            .join(&project_spec.godot_project_dir);
-
-        let dest_base = if let Some(destination) = &addon_spec.destination {
-            project_dir.join(destination)
-        } else {
-            project_dir.join("addons").join(&addon_name)
+    options: &SyncOptions,
+) -> Result<Vec<AddonSyncOutcome>> {
+    let manifest = Mutex::new(SyncManifest::load(&project_spec.project_root_dir)?);
+    let hashes = HashCache::load(&config.file_hashes_file);
+    let snapshot = options.snapshot(config, &project_spec.project_root_dir)?;
+    let project_spec = &project_spec;
+    let outcomes = run_per_addon(project_spec, options, async |addon_name, addon_spec| {
+        let Some(resolved) =
+            resolve_addon(config, project_spec, git_client, addon_name, addon_spec).await?
+        else {
+            return Ok(None);
         };
+        let mut manifest = manifest.lock().expect("Sync manifest lock poisoned");
+        sync_addon(
+            addon_name,
+            addon_spec,
+            &resolved,
+            &mut manifest,
+            &hashes,
+            &snapshot,
+            options,
+        )
+        .map(Some)
+    })
+    .await;
+    save_hash_cache(&hashes);
+    Ok(outcomes)
+}
 
-        tracing::info!("Syncing addon \"{}\" to {:?}", addon_name, dest_base);
-
-        match &addon_spec.source {
-            AddonSource::Git(git) => {
-                sync_git_addon(
-                    git_client,
-                    &project_spec
-                        .project_root_dir
-                        .join(&project_spec.godot_project_dir),
-                    &addon_name,
-                    &addon_spec,
-                    git,
-                )
-                .await?
+/// The hash cache only makes later syncs faster, so failing to save it isn't an error.
+fn save_hash_cache(hashes: &HashCache) {
+    if let Err(e) = hashes.save() {
+        tracing::warn!("Failed to save the hash cache: {:#}", e);
+    }
+}
+
+/// Runs `task` for each addon to sync, grouped by [`addon_groups`], and times it.
+/// A task returns `None` for an addon that is skipped.
+async fn run_per_addon<'a>(
+    project_spec: &'a ProjectSpecification,
+    options: &SyncOptions,
+    task: impl AsyncFn(&'a String, &'a AddonSpec) -> Result<Option<AddonSyncPlan>>,
+) -> Vec<AddonSyncOutcome> {
+    let task = &task;
+    let mut groups = futures_util::stream::iter(addon_groups(project_spec, options))
+        .map(|group| async move {
+            let mut outcomes = Vec::new();
+            for (addon_name, addon_spec) in group {
+                let start = Instant::now();
+                let result = task(addon_name, addon_spec).await;
+                let failed = result.is_err();
+                if let Some(result) = result.transpose() {
+                    outcomes.push(AddonSyncOutcome {
+                        addon_name: addon_name.clone(),
+                        elapsed: start.elapsed(),
+                        result,
+                    });
+                }
+                if failed && options.fail_fast {
+                    break;
+                }
             }
-            AddonSource::Local(local) => sync_local_addon(
-                &project_spec
-                    .project_root_dir
-                    .join(&project_spec.godot_project_dir),
-                &addon_name,
-                &addon_spec,
-                local,
-            )?,
+            outcomes
+        })
+        .buffer_unordered(options.jobs.max(1));
+
+    let mut outcomes = Vec::new();
+    while let Some(group_outcomes) = groups.next().await {
+        let failed = group_outcomes.iter().any(|outcome| outcome.result.is_err());
+        outcomes.extend(group_outcomes);
+        if failed && options.fail_fast {
+            break;
         }
     }
-    Ok(())
+    outcomes.sort_by(|a, b| a.addon_name.cmp(&b.addon_name));
+    outcomes
 }
 
-async fn sync_git_addon<G: GitClient>(
-    git_client: &G,
-    project_dir: &Path,
+/// Splits the addons selected by `options` into groups that can be synced concurrently.
+/// Addons from the same git repository share its checkout, and addons with nested destinations
+/// would overwrite each other's files, so those end up in the same group.
+/// Groups, and the addons in them, are in name order.
+fn addon_groups<'a>(
+    project_spec: &'a ProjectSpecification,
+    options: &SyncOptions,
+) -> Vec<Vec<(&'a String, &'a AddonSpec)>> {
+    let mut groups: Vec<Vec<(&String, &AddonSpec, PathBuf)>> = Vec::new();
+    for (addon_name, addon_spec) in sorted_addons(project_spec) {
+        if let Some(addons) = &options.addons
+            && !addons.contains(addon_name)
+        {
+            continue;
+        }
+        let destination = addon_destination(project_spec, addon_name, addon_spec);
+        let conflicts = |(_, other_spec, other_destination): &(&String, &AddonSpec, PathBuf)| {
+            let same_repo = matches!(
+                (&addon_spec.source, &other_spec.source),
+                (AddonSource::Git(a), AddonSource::Git(b)) if a.git == b.git
+            );
+            same_repo
+                || destination.starts_with(other_destination)
+                || other_destination.starts_with(&destination)
+        };
+        let (conflicting, mut others): (Vec<_>, Vec<_>) = groups
+            .into_iter()
+            .partition(|group| group.iter().any(conflicts));
+        let mut group: Vec<_> = conflicting.into_iter().flatten().collect();
+        group.push((addon_name, addon_spec, destination));
+        group.sort_by(|a, b| a.0.cmp(b.0));
+        others.push(group);
+        others.sort_by(|a, b| a[0].0.cmp(b[0].0));
+        groups = others;
+    }
+    groups
+        .into_iter()
+        .map(|group| {
+            group
+                .into_iter()
+                .map(|(addon_name, addon_spec, _)| (addon_name, addon_spec))
+                .collect()
+        })
+        .collect()
+}
+
+/// Plans and syncs a resolved addon, and records it in `manifest`.
+fn sync_addon(
     addon_name: &str,
     addon_spec: &AddonSpec,
-    addon_source: &GitAddonSource,
+    resolved: &ResolvedAddon,
+    manifest: &mut SyncManifest,
+    hashes: &HashCache,
+    snapshot: &SyncSnapshot,
+    options: &SyncOptions,
+) -> Result<AddonSyncPlan> {
+    let plan = plan_addon(addon_name, addon_spec, resolved, manifest, hashes, false)?;
+    apply_addon_plan(&plan, resolved, manifest, hashes, snapshot, options)?;
+    Ok(plan)
+}
+
+/// Applies the plan of a resolved addon and records it in `manifest`.
+fn apply_addon_plan(
+    plan: &AddonSyncPlan,
+    resolved: &ResolvedAddon,
+    manifest: &mut SyncManifest,
+    hashes: &HashCache,
+    snapshot: &SyncSnapshot,
+    options: &SyncOptions,
 ) -> Result<()> {
-    let source_base = git_client
-        .checkout(&addon_source.git, addon_source.rev.as_deref().unwrap_or(""))
-        .await?
-        .join(addon_source.subdir.as_deref().unwrap_or(Path::new("")));
+    let addon_name = &plan.addon_name;
+    tracing::info!(
+        "Syncing addon \"{}\" to {:?}",
+        addon_name,
+        resolved.dest_dir
+    );
+    if !plan.local_changes.is_empty() && !options.force {
+        bail!(
+            "Addon \"{}\" has files that were changed since the last sync and would be overwritten:\n  {}\nUse --force to overwrite them.",
+            addon_name,
+            plan.local_changes
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n  ")
+        );
+    }
 
-    let dest_base = if let Some(destination) = &addon_spec.destination {
-        project_dir.join(destination)
+    if !plan.is_up_to_date() {
+        snapshot.save_path(&resolved.project_root_dir.join(SYNC_MANIFEST_FILE))?;
+    }
+    let addon_manifest = if plan.link {
+        if plan.relink {
+            snapshot.save_path(&resolved.dest_dir)?;
+            link_addon(resolved)?;
+        }
+        AddonManifest::linked(addon_name)
     } else {
-        project_dir.join("addons").join(addon_name)
+        snapshot.save_plan(&resolved.dest_dir, &plan.plan)?;
+        fs::create_dir_all(&resolved.dest_dir)?;
+        apply_sync_plan(&resolved.source_dir, &resolved.dest_dir, &plan.plan)?;
+        AddonManifest::record(addon_name, &resolved.dest_dir, hashes)?
     };
+    manifest
+        .destinations
+        .insert(resolved.destination.clone(), addon_manifest);
+    manifest.save(&resolved.project_root_dir)
+}
 
-    tracing::debug!(
-        "Syncing addon {} from {:?} to {:?}",
-        addon_name,
-        source_base,
-        dest_base
-    );
+/// Computes what syncing a resolved addon would change.
+fn plan_addon(
+    addon_name: &str,
+    addon_spec: &AddonSpec,
+    resolved: &ResolvedAddon,
+    manifest: &SyncManifest,
+    hashes: &HashCache,
+    with_diffs: bool,
+) -> Result<AddonSyncPlan> {
+    let link = matches!(&addon_spec.source, AddonSource::Local(local) if local.link == Some(true));
+    let (plan, relink) = if !link {
+        let plan = plan_sync(
+            &resolved.source_dir,
+            &resolved.dest_dir,
+            addon_spec.include.as_deref(),
+            addon_spec.exclude.as_deref(),
+            addon_spec.preserve_godot_files.unwrap_or(true),
+            hashes,
+        )?;
+        (plan, false)
+    } else if is_link(&resolved.dest_dir) {
+        let up_to_date = fs::canonicalize(&resolved.dest_dir).ok()
+            == fs::canonicalize(&resolved.source_dir).ok();
+        (SyncPlan::default(), !up_to_date)
+    } else {
+        // Switching from a copy to a link: anything in the copy that differs from the
+        // source is lost
+        let plan = plan_sync(
+            &resolved.source_dir,
+            &resolved.dest_dir,
+            None,
+            None,
+            false,
+            hashes,
+        )?;
+        (plan, true)
+    };
 
-    if !source_base.exists() {
-        tracing::warn!(
-            "Addon {} path {:?} does not exist, skipping",
-            addon_name,
-            source_base
+    let diffs = if with_diffs {
+        plan.text_diffs(&resolved.source_dir, &resolved.dest_dir)?
+    } else {
+        Vec::new()
+    };
+    let local_changes = match manifest.addon(&resolved.destination) {
+        Some(addon_manifest) => addon_manifest.local_changes(&resolved.dest_dir, &plan, hashes)?,
+        None => Vec::new(),
+    };
+    let local_changes_patch = local_changes_patch(resolved, &plan, &local_changes)?;
+    Ok(AddonSyncPlan {
+        addon_name: addon_name.to_string(),
+        source_dir: resolved.source_dir.clone(),
+        dest_dir: resolved.dest_dir.clone(),
+        link,
+        relink,
+        plan,
+        diffs,
+        local_changes,
+        local_changes_patch,
+        resolved_tag: resolved.resolved_tag.clone(),
+    })
+}
+
+/// Replaces the addon's destination, whether it's a copy or a link to another directory,
+/// with a link to its source directory.
+fn link_addon(resolved: &ResolvedAddon) -> Result<()> {
+    let dest_dir = &resolved.dest_dir;
+    if is_link(dest_dir) {
+        remove_link(dest_dir)?;
+    } else if dest_dir.exists() {
+        fs::remove_dir_all(dest_dir)?;
+    }
+    if let Some(parent) = dest_dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    create_dir_link(&std::path::absolute(&resolved.source_dir)?, dest_dir)
+}
+
+/// Diffs from the addon's new files to the locally changed ones, with paths relative to the
+/// project root directory, so `git apply` can re-apply the changes after syncing.
+fn local_changes_patch(
+    resolved: &ResolvedAddon,
+    plan: &SyncPlan,
+    local_changes: &[PathBuf],
+) -> Result<String> {
+    let mut patch = String::new();
+    for rel_path in local_changes {
+        let local = fs::read(resolved.dest_dir.join(rel_path))?;
+        let synced = if plan.modified.iter().any(|c| &c.rel_path == rel_path) {
+            Some(fs::read(resolved.source_dir.join(rel_path))?)
+        } else {
+            None
+        };
+        let project_path = resolved.destination.join(rel_path);
+        match text_diff(synced.as_deref(), Some(&local), &project_path) {
+            Some(diff) => patch.push_str(&diff),
+            None => patch.push_str(&format!(
+                "Binary file {} was changed and can't be included in the patch\n",
+                project_path.display()
+            )),
+        }
+    }
+    Ok(patch)
+}
+
+/// A destination that an addon was synced to, but that no addon of the project uses anymore,
+/// e.g. because the addon was removed or renamed, or its destination changed.
+#[derive(Debug, Clone)]
+pub struct OrphanedAddon {
+    /// Name of the addon when it was last synced.
+    pub addon_name: String,
+    /// `dest_dir` relative to the project root directory.
+    pub destination: PathBuf,
+    pub dest_dir: PathBuf,
+    /// Whether `dest_dir` is a link, which is removed without touching the files it points to.
+    pub link: bool,
+    /// Removes the files the addon owned.
+    pub plan: SyncPlan,
+    /// Files in the destination that the addon didn't own, which are kept.
+    pub kept: Vec<PathBuf>,
+    /// Owned files that were changed by hand since the last sync.
+    pub local_changes: Vec<PathBuf>,
+}
+
+/// Finds the destinations recorded in the project's [`SyncManifest`] that no addon uses anymore.
+pub fn find_orphaned_addons(project_spec: &ProjectSpecification) -> Result<Vec<OrphanedAddon>> {
+    let manifest = SyncManifest::load(&project_spec.project_root_dir)?;
+    let destinations: Vec<PathBuf> = project_spec
+        .addons
+        .iter()
+        .map(|(addon_name, addon_spec)| addon_destination(project_spec, addon_name, addon_spec))
+        .collect();
+
+    let mut orphans = Vec::new();
+    for (destination, addon_manifest) in &manifest.destinations {
+        if destinations.contains(destination) {
+            continue;
+        }
+        let dest_dir = project_spec.project_root_dir.join(destination);
+        let link = is_link(&dest_dir);
+        let (plan, kept, local_changes) = if link {
+            (SyncPlan::default(), Vec::new(), Vec::new())
+        } else {
+            let (plan, kept) = addon_manifest.removal_plan(&dest_dir)?;
+            let local_changes =
+                addon_manifest.local_changes(&dest_dir, &plan, &HashCache::default())?;
+            (plan, kept, local_changes)
+        };
+        orphans.push(OrphanedAddon {
+            addon_name: addon_manifest.addon_name.clone(),
+            destination: destination.clone(),
+            dest_dir,
+            link,
+            plan,
+            kept,
+            local_changes,
+        });
+    }
+    Ok(orphans)
+}
+
+/// Removes the files of orphaned addons (see [`find_orphaned_addons`]) and forgets them,
+/// limited to the addons named in `options.addons` if set. Returns the addons that were removed.
+pub fn remove_orphaned_addons(
+    config: &Config,
+    project_spec: &ProjectSpecification,
+    options: &SyncOptions,
+) -> Result<Vec<OrphanedAddon>> {
+    let mut orphans = find_orphaned_addons(project_spec)?;
+    if let Some(addons) = &options.addons {
+        orphans.retain(|orphan| addons.contains(&orphan.addon_name));
+    }
+    if !options.force
+        && let Some(orphan) = orphans.iter().find(|o| !o.local_changes.is_empty())
+    {
+        bail!(
+            "Orphaned addon \"{}\" has files that were changed since the last sync and would be removed:\n  {}\nUse --force to remove them.",
+            orphan.addon_name,
+            orphan
+                .local_changes
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n  ")
         );
-        return Ok(());
     }
 
-    fs::create_dir_all(&dest_base)?;
-    sync_recursive(
-        &source_base,
-        &dest_base,
-        addon_spec.include.as_deref(),
-        addon_spec.exclude.as_deref(),
-    )?;
-    Ok(())
+    let mut manifest = SyncManifest::load(&project_spec.project_root_dir)?;
+    let snapshot = options.snapshot(config, &project_spec.project_root_dir)?;
+    for orphan in &orphans {
+        tracing::info!(
+            "Removing orphaned addon \"{}\" from {:?}",
+            orphan.addon_name,
+            orphan.dest_dir
+        );
+        snapshot.save_path(&project_spec.project_root_dir.join(SYNC_MANIFEST_FILE))?;
+        if orphan.link {
+            snapshot.save_path(&orphan.dest_dir)?;
+            remove_link(&orphan.dest_dir)?;
+        } else {
+            snapshot.save_plan(&orphan.dest_dir, &orphan.plan)?;
+            apply_sync_plan(&orphan.dest_dir, &orphan.dest_dir, &orphan.plan)?;
+        }
+        if orphan.kept.is_empty() && orphan.dest_dir.exists() {
+            fs::remove_dir(&orphan.dest_dir)?;
+        }
+        manifest.destinations.remove(&orphan.destination);
+        manifest.save(&project_spec.project_root_dir)?;
+    }
+    Ok(orphans)
 }
 
-fn sync_local_addon(
-    project_dir: &Path,
+/// The addon's destination directory, relative to the project root directory.
+pub(crate) fn addon_destination(
+    project_spec: &ProjectSpecification,
     addon_name: &str,
     addon_spec: &AddonSpec,
-    addon_source: &LocalAddonSource,
-) -> Result<()> {
-    let source_base = project_dir.join(&addon_source.path);
-    let dest_base = project_dir.join("addons").join(addon_name);
+) -> PathBuf {
+    project_spec
+        .godot_project_dir
+        .join(
+            addon_spec
+                .destination
+                .clone()
+                .unwrap_or_else(|| Path::new("addons").join(addon_name)),
+        )
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// Source directories of the local addons that are copied to their destination, by addon name.
+/// Linked addons aren't included, since changes to them show up in the project on their own.
+pub fn copied_local_addon_sources(project_spec: &ProjectSpecification) -> Vec<(String, PathBuf)> {
+    let project_dir = project_spec
+        .project_root_dir
+        .join(&project_spec.godot_project_dir);
+    sorted_addons(project_spec)
+        .into_iter()
+        .filter_map(|(addon_name, addon_spec)| match &addon_spec.source {
+            AddonSource::Local(local) if local.link != Some(true) => {
+                Some((addon_name.clone(), project_dir.join(&local.path)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The directories of an addon's source that contain a `plugin.cfg` file, relative to the root
+/// of its repository, download or local path (ignoring its `subdir`), which is where Godot
+/// expects an addon's files. Fetches the source if needed.
+pub async fn find_addon_plugin_dirs<G: GitClient>(
+    config: &Config,
+    project_spec: &ProjectSpecification,
+    git_client: &G,
+    addon_name: &str,
+    addon_spec: &AddonSpec,
+) -> Result<Vec<PathBuf>> {
+    let Some(resolved) =
+        resolve_addon(config, project_spec, git_client, addon_name, addon_spec).await?
+    else {
+        bail!("The source of addon \"{}\" doesn't exist", addon_name);
+    };
+    Ok(find_plugins(&resolved.source_root)
+        .into_iter()
+        .map(|plugin| plugin.parent().unwrap_or(Path::new("")).to_path_buf())
+        .collect())
+}
+
+/// Addons in name order, so plans and logs are stable between runs.
+pub(crate) fn sorted_addons(project_spec: &ProjectSpecification) -> Vec<(&String, &AddonSpec)> {
+    let mut addons: Vec<_> = project_spec.addons.iter().collect();
+    addons.sort_by(|a, b| a.0.cmp(b.0));
+    addons
+}
+
+/// Makes the addon's source files available on disk (checking out, downloading or
+/// extracting them as needed). Returns `None` if the source directory doesn't exist.
+pub(crate) async fn resolve_addon<G: GitClient>(
+    config: &Config,
+    project_spec: &ProjectSpecification,
+    git_client: &G,
+    addon_name: &str,
+    addon_spec: &AddonSpec,
+) -> Result<Option<ResolvedAddon>> {
+    let project_dir = project_spec
+        .project_root_dir
+        .join(&project_spec.godot_project_dir);
+    let destination = addon_destination(project_spec, addon_name, addon_spec);
+    let dest_dir = project_spec.project_root_dir.join(&destination);
+
+    let mut source_lock = None;
+    let mut resolved_tag = None;
+    let (source_root, subdir) = match &addon_spec.source {
+        AddonSource::Git(git) => {
+            let git_ref = match &git.version {
+                Some(version) => {
+                    let tag = resolve_version_tag(git_client, &git.git, version).await?;
+                    tracing::debug!(
+                        "Addon {} version {} resolved to tag {} ({})",
+                        addon_name,
+                        version,
+                        tag.name,
+                        tag.commit
+                    );
+                    resolved_tag.insert(tag).name.clone()
+                }
+                None => git.rev.clone().unwrap_or_default(),
+            };
+            source_lock = Some(git_client.lock_repo(&git.git).await?);
+            let sparse_dirs =
+                sparse_checkout_dirs(git.subdir.as_deref(), addon_spec.include.as_deref());
+            let checkout = git_client
+                .checkout(&git.git, &git_ref, &sparse_dirs)
+                .await?;
+            (checkout, git.subdir.as_deref())
+        }
+        AddonSource::AssetLibrary(asset) => {
+            let asset_library_client = AssetLibraryClient::new(config.clone());
+            let download = asset_library_client
+                .resolve(asset.asset_id, asset.version.as_deref())
+                .await?;
+            tracing::debug!(
+                "Addon {} is \"{}\" {} from the Asset Library",
+                addon_name,
+                download.title,
+                download.version_string
+            );
+            let download_dir = asset_library_client.download(&download).await?;
+            (download_dir, asset.subdir.as_deref())
+        }
+        AddonSource::Archive(archive) => {
+            let extracted_dir = ArchiveClient::new(config.clone())
+                .fetch(&archive.url, archive.sha256.as_deref())
+                .await?;
+            (extracted_dir, archive.strip_prefix.as_deref())
+        }
+        AddonSource::Local(local) => (project_dir.join(&local.path), None),
+    };
+    let source_dir = source_root.join(subdir.unwrap_or(Path::new("")));
+
     tracing::debug!(
         "Syncing addon {} from {:?} to {:?}",
         addon_name,
-        source_base,
-        dest_base
+        source_dir,
+        dest_dir
     );
 
-    if !source_base.exists() {
+    if !source_dir.exists() {
         tracing::warn!(
             "Addon {} path {:?} does not exist, skipping",
             addon_name,
-            source_base
+            source_dir
         );
-        return Ok(());
+        return Ok(None);
     }
 
-    fs::create_dir_all(&dest_base)?;
-    sync_recursive(
-        &source_base,
-        &dest_base,
-        addon_spec.include.as_deref(),
-        addon_spec.exclude.as_deref(),
-    )?;
-    Ok(())
+    Ok(Some(ResolvedAddon {
+        project_root_dir: project_spec.project_root_dir.clone(),
+        source_dir,
+        source_root,
+        dest_dir,
+        destination,
+        resolved_tag,
+        repo_url: match &addon_spec.source {
+            AddonSource::Git(git) => Some(git.git.clone()),
+            _ => None,
+        },
+        source_lock,
+    }))
 }
 
 #[cfg(test)]
@@ -134,10 +779,18 @@ mod tests {
     use super::*;
     use crate::config::Config;
     use crate::project_specification::load_godot_project_spec;
+    use crate::sync_snapshot::latest_snapshot;
+    use crate::test_helpers::archives::zip_directory;
     use crate::test_helpers::mock_git_client::MockGitClient;
-    use anyhow::bail;
+    use crate::test_helpers::mock_http_server::MockHttpServer;
+    use anyhow::{Context, bail};
     use std::fs;
 
+    /// The plans of `outcomes`, or the first failure.
+    fn into_plans(outcomes: Vec<AddonSyncOutcome>) -> Result<Vec<AddonSyncPlan>> {
+        outcomes.into_iter().map(|outcome| outcome.result).collect()
+    }
+
     #[tokio::test]
     async fn test_sync_local_path_addons() -> Result<()> {
         let _ = tracing_subscriber::fmt()
@@ -151,7 +804,7 @@ mod tests {
             .tempdir()?;
         let version_file = tmp_dir.path().join("gdenv.toml");
         let config = Config::setup(Some(&tmp_data_dir.path()))?;
-        let git_client = MockGitClient::new(config);
+        let git_client = MockGitClient::new(config.clone());
 
         let test_data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data");
         let test_addon2_path = test_data_dir.join("test-addon2-repo/addons/test-addon2");
@@ -176,7 +829,9 @@ path = {}
         fs::write(&version_file, &str_spec_v1)?;
         let project_spec =
             load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
-        sync_addons(project_spec, &git_client).await?;
+        into_plans(
+            sync_addons(&config, project_spec, &git_client, &SyncOptions::default()).await?,
+        )?;
 
         assert!(
             tmp_dir
@@ -211,7 +866,9 @@ path = {}
         fs::write(&version_file, &str_spec_v2)?;
         let project_spec =
             load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
-        sync_addons(project_spec, &git_client).await?;
+        into_plans(
+            sync_addons(&config, project_spec, &git_client, &SyncOptions::default()).await?,
+        )?;
 
         assert!(
             tmp_dir
@@ -243,7 +900,7 @@ path = {}
             .tempdir()?;
         let version_file = tmp_dir.path().join("gdenv.toml");
         let config = Config::setup(Some(&tmp_data_dir.path()))?;
-        let git_client = MockGitClient::new(config);
+        let git_client = MockGitClient::new(config.clone());
 
         // Synchronize addon
         let str_spec_v1 = r#"
@@ -259,7 +916,9 @@ destination = "addons/test-addon1/subfolder"
         fs::write(&version_file, &str_spec_v1)?;
         let project_spec =
             load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
-        sync_addons(project_spec, &git_client).await?;
+        into_plans(
+            sync_addons(&config, project_spec, &git_client, &SyncOptions::default()).await?,
+        )?;
 
         assert!(
             tmp_dir
@@ -271,6 +930,607 @@ destination = "addons/test-addon1/subfolder"
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_sync_git_addon_version() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client =
+            MockGitClient::new(config.clone()).with_tags(&["v0.9.0", "v0.10.1", "v1.0.0"]);
+        let spec = r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.test-addon1]
+git = "https://github.com/GitHubUser/github_repo.git"
+subdir = "addons/test-addon1"
+version = "^0.10"
+        "#;
+        fs::write(tmp_dir.path().join("gdenv.toml"), spec)?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+
+        let plans = into_plans(
+            sync_addons(&config, project_spec, &git_client, &SyncOptions::default()).await?,
+        )?;
+        let resolved_tag = plans[0].resolved_tag.as_ref().unwrap();
+        assert_eq!(resolved_tag.name, "v0.10.1");
+        assert!(
+            tmp_dir
+                .path()
+                .join("addons/test-addon1/plugin.cfg")
+                .exists()
+        );
+
+        fs::write(
+            tmp_dir.path().join("gdenv.toml"),
+            spec.replace("^0.10", "^2"),
+        )?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+        let error = into_plans(
+            sync_addons(&config, project_spec, &git_client, &SyncOptions::default()).await?,
+        )
+        .unwrap_err();
+        assert!(
+            format!("{error:#}").contains(
+                "No tag of https://github.com/GitHubUser/github_repo.git matches version \"^2\""
+            ),
+            "{error:#}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_addon_plugin_dirs() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = MockGitClient::new(config.clone());
+        fs::write(
+            tmp_dir.path().join("gdenv.toml"),
+            "[godot]\nversion = \"4.6.0-stable\"\n",
+        )?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+        let addon_spec: AddonSpec =
+            toml::from_str(r#"git = "https://github.com/GitHubUser/github_repo.git""#)?;
+
+        let plugin_dirs =
+            find_addon_plugin_dirs(&config, &project_spec, &git_client, "new", &addon_spec).await?;
+        assert_eq!(plugin_dirs, vec![PathBuf::from("addons/test-addon1")]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_asset_library_addon() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let test_data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data");
+
+        let server = MockHttpServer::start().await?;
+        server.add(
+            "/asset/1234",
+            format!(
+                r#"{{"title": "Test Addon", "version_string": "1.0", "download_url": "{}"}}"#,
+                server.url("/test-addon1.zip")
+            ),
+        );
+        server.add(
+            "/test-addon1.zip",
+            zip_directory(&test_data_dir.join("test-addon1-repo"), "test-addon1-1.0")?,
+        );
+        let config = Config {
+            asset_library_url: server.url(""),
+            ..Config::setup(Some(tmp_data_dir.path()))?
+        };
+        let git_client = MockGitClient::new(config.clone());
+
+        let str_spec = r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.test-addon1]
+asset_id = 1234
+subdir = "addons/test-addon1"
+exclude = ["old_file.txt"]
+        "#;
+        fs::write(tmp_dir.path().join("gdenv.toml"), str_spec)?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+        into_plans(
+            sync_addons(&config, project_spec, &git_client, &SyncOptions::default()).await?,
+        )?;
+
+        let addon_dir = tmp_dir.path().join("addons/test-addon1");
+        assert!(addon_dir.join("plugin.cfg").exists());
+        assert!(addon_dir.join("changed_file.txt").exists());
+        assert!(!addon_dir.join("old_file.txt").exists());
+        assert!(!tmp_dir.path().join("file-not-part-of-addon.txt").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_archive_addon() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let test_data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data");
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = MockGitClient::new(config.clone());
+
+        let archive_path = tmp_dir.path().join("test-addon1-1.0.zip");
+        fs::write(
+            &archive_path,
+            zip_directory(&test_data_dir.join("test-addon1-repo"), "test-addon1-1.0")?,
+        )?;
+        let str_spec = format!(
+            r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.test-addon1]
+url = "{}"
+sha256 = "{}"
+strip_prefix = "test-addon1-1.0/addons/test-addon1"
+        "#,
+            url::Url::from_file_path(&archive_path)
+                .map_err(|()| anyhow::anyhow!("Invalid path"))?,
+            crate::file_sync::sha256_file(&archive_path)?
+        );
+        fs::write(tmp_dir.path().join("gdenv.toml"), str_spec)?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+        into_plans(
+            sync_addons(&config, project_spec, &git_client, &SyncOptions::default()).await?,
+        )?;
+
+        let addon_dir = tmp_dir.path().join("addons/test-addon1");
+        assert!(addon_dir.join("plugin.cfg").exists());
+        assert!(addon_dir.join("old_file.txt").exists());
+        assert!(!addon_dir.join("test-addon1-1.0").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_apply_addon_plans() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = MockGitClient::new(config.clone());
+        let source_dir = tmp_dir.path().join("source");
+        fs::create_dir_all(&source_dir)?;
+        fs::write(source_dir.join("planned.txt"), "Planned\n")?;
+        fs::write(
+            tmp_dir.path().join("gdenv.toml"),
+            r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.test-addon1]
+path = "source"
+
+# Planned one after the other, with the repository locked by each in turn
+[addon.git-a]
+git = "https://example.com/test-addon1-repo"
+rev = "main"
+subdir = "addons/test-addon1"
+
+[addon.git-b]
+git = "https://example.com/test-addon1-repo"
+rev = "main"
+subdir = "addons/test-addon1"
+        "#,
+        )?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+
+        let planned = plan_addons(
+            &config,
+            &project_spec,
+            &git_client,
+            false,
+            &SyncOptions::default(),
+        )
+        .await?;
+        // Files that appear after planning aren't part of the confirmed plan
+        fs::write(source_dir.join("unplanned.txt"), "Unplanned\n")?;
+        let plans = into_plans(
+            apply_addon_plans(
+                &config,
+                &project_spec,
+                &git_client,
+                &planned,
+                &SyncOptions::default(),
+            )
+            .await?,
+        )?;
+        assert_eq!(plans.len(), 3);
+        assert_eq!(plans[2].addon_name, "test-addon1");
+        assert_eq!(plans[2].plan.added.len(), 1);
+        assert!(tmp_dir.path().join("addons/git-a/plugin.cfg").exists());
+        assert!(tmp_dir.path().join("addons/git-b/plugin.cfg").exists());
+
+        let addon_dir = tmp_dir.path().join("addons/test-addon1");
+        assert!(addon_dir.join("planned.txt").exists());
+        assert!(!addon_dir.join("unplanned.txt").exists());
+        let manifest = SyncManifest::load(tmp_dir.path())?;
+        assert!(manifest.addon(Path::new("addons/test-addon1")).is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_protects_local_changes() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = MockGitClient::new(config.clone());
+        let test_data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data");
+        let spec = |repo: &str| {
+            format!(
+                r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.test-addon1]
+path = {}
+        "#,
+                toml::Value::String(
+                    test_data_dir
+                        .join(repo)
+                        .join("addons/test-addon1")
+                        .to_string_lossy()
+                        .to_string()
+                )
+            )
+        };
+
+        fs::write(tmp_dir.path().join("gdenv.toml"), spec("test-addon1-repo"))?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+        into_plans(
+            sync_addons(&config, project_spec, &git_client, &SyncOptions::default()).await?,
+        )?;
+        assert!(tmp_dir.path().join(".gdenv/sync-manifest.json").exists());
+
+        let addon_dir = tmp_dir.path().join("addons/test-addon1");
+        fs::write(addon_dir.join("changed_file.txt"), "A local patch\n")?;
+        fs::write(
+            tmp_dir.path().join("gdenv.toml"),
+            spec("test-addon1v2-repo"),
+        )?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+
+        let plans = into_plans(
+            plan_addons(
+                &config,
+                &project_spec,
+                &git_client,
+                false,
+                &SyncOptions::default(),
+            )
+            .await?
+            .outcomes,
+        )?;
+        assert_eq!(
+            plans[0].local_changes,
+            vec![PathBuf::from("changed_file.txt")]
+        );
+        assert!(
+            plans[0]
+                .local_changes_patch
+                .contains("+++ b/addons/test-addon1/changed_file.txt\n")
+        );
+        assert!(plans[0].local_changes_patch.contains("+A local patch\n"));
+
+        let error = into_plans(
+            sync_addons(
+                &config,
+                project_spec.clone(),
+                &git_client,
+                &SyncOptions::default(),
+            )
+            .await?,
+        )
+        .unwrap_err();
+        assert!(
+            format!("{error:#}").contains("changed_file.txt"),
+            "{error:#}"
+        );
+        assert_eq!(
+            fs::read_to_string(addon_dir.join("changed_file.txt"))?,
+            "A local patch\n"
+        );
+
+        into_plans(
+            sync_addons(
+                &config,
+                project_spec,
+                &git_client,
+                &SyncOptions {
+                    force: true,
+                    ..Default::default()
+                },
+            )
+            .await?,
+        )?;
+        assert!(addon_dir.join("new_file.txt").exists());
+        assert_ne!(
+            fs::read_to_string(addon_dir.join("changed_file.txt"))?,
+            "A local patch\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_continues_after_failures() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = MockGitClient::new(config.clone());
+        let test_data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data");
+        let missing_archive = url::Url::from_file_path(tmp_dir.path().join("missing.zip"))
+            .map_err(|()| anyhow::anyhow!("Invalid path"))?;
+        fs::write(
+            tmp_dir.path().join("gdenv.toml"),
+            format!(
+                r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.a-broken]
+url = "{missing_archive}"
+
+[addon.b-local]
+path = {}
+
+[addon.c-git]
+git = "https://example.com/repo.git"
+subdir = "addons/test-addon1"
+
+[addon.d-same-repo]
+git = "https://example.com/repo.git"
+destination = "addons/other"
+
+[addon.e-nested]
+path = {}
+destination = "addons/b-local/nested"
+        "#,
+                toml::Value::String(
+                    test_data_dir
+                        .join("test-addon1-repo/addons/test-addon1")
+                        .to_string_lossy()
+                        .to_string()
+                ),
+                toml::Value::String(
+                    test_data_dir
+                        .join("test-addon2-repo/addons/test-addon2")
+                        .to_string_lossy()
+                        .to_string()
+                ),
+            ),
+        )?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+
+        let groups: Vec<Vec<&str>> = addon_groups(&project_spec, &SyncOptions::default())
+            .into_iter()
+            .map(|group| group.into_iter().map(|(name, _)| name.as_str()).collect())
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                vec!["a-broken"],
+                vec!["b-local", "e-nested"],
+                vec!["c-git", "d-same-repo"],
+            ]
+        );
+
+        // With --fail-fast, nothing after the failing addon is synced
+        let options = SyncOptions {
+            jobs: 1,
+            fail_fast: true,
+            ..Default::default()
+        };
+        let outcomes = sync_addons(&config, project_spec.clone(), &git_client, &options).await?;
+        assert_eq!(outcomes.len(), 1);
+        assert!(outcomes[0].result.is_err());
+        assert!(!tmp_dir.path().join("addons/b-local").exists());
+
+        // Otherwise, the other addons are synced
+        let options = SyncOptions {
+            jobs: 4,
+            ..Default::default()
+        };
+        let outcomes = sync_addons(&config, project_spec, &git_client, &options).await?;
+        let names: Vec<&str> = outcomes.iter().map(|o| o.addon_name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["a-broken", "b-local", "c-git", "d-same-repo", "e-nested"]
+        );
+        assert_eq!(outcomes[0].addon_name, "a-broken");
+        assert!(outcomes[0].result.is_err());
+        assert!(outcomes[1..].iter().all(|o| o.result.is_ok()));
+        assert!(tmp_dir.path().join("addons/b-local/plugin.cfg").exists());
+        assert!(
+            tmp_dir
+                .path()
+                .join("addons/b-local/nested/plugin.cfg")
+                .exists()
+        );
+        assert!(tmp_dir.path().join("addons/c-git/plugin.cfg").exists());
+        assert!(tmp_dir.path().join("addons/other/addons").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_linked_local_addon() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = MockGitClient::new(config.clone());
+        let test_data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data");
+        let source_dir = test_data_dir.join("test-addon1-repo/addons/test-addon1");
+        let addon_dir = tmp_dir.path().join("addons/test-addon1");
+        let spec = |link: bool| {
+            format!(
+                r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.test-addon1]
+path = {}
+link = {link}
+        "#,
+                toml::Value::String(source_dir.to_string_lossy().to_string())
+            )
+        };
+        let sync = async |link: bool, force: bool| -> Result<Vec<AddonSyncPlan>> {
+            fs::write(tmp_dir.path().join("gdenv.toml"), spec(link))?;
+            let project_spec =
+                load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+            into_plans(
+                sync_addons(
+                    &config,
+                    project_spec,
+                    &git_client,
+                    &SyncOptions {
+                        force,
+                        ..Default::default()
+                    },
+                )
+                .await?,
+            )
+        };
+
+        // Switching from a copy to a link protects the local changes of the copy
+        sync(false, false).await?;
+        fs::write(addon_dir.join("changed_file.txt"), "A local patch\n")?;
+        let error = sync(true, false).await.unwrap_err();
+        assert!(
+            format!("{error:#}").contains("changed_file.txt"),
+            "{error:#}"
+        );
+        assert!(!is_link(&addon_dir));
+
+        let plans = sync(true, true).await?;
+        assert!(plans[0].relink);
+        assert!(is_link(&addon_dir));
+        assert_eq!(
+            fs::canonicalize(&addon_dir)?,
+            fs::canonicalize(&source_dir)?
+        );
+        let manifest = SyncManifest::load(tmp_dir.path())?;
+        assert!(
+            manifest
+                .addon(Path::new("addons/test-addon1"))
+                .is_some_and(|m| m.link && m.files.is_empty())
+        );
+        assert!(sync(true, false).await?[0].is_up_to_date());
+
+        // Switching back to a copy replaces the link, leaving the source untouched
+        sync(false, false).await?;
+        assert!(!is_link(&addon_dir));
+        assert!(addon_dir.join("plugin.cfg").is_file());
+        fs::write(addon_dir.join("notes.txt"), "Only in the copy")?;
+        assert!(!source_dir.join("notes.txt").exists());
+        fs::remove_file(addon_dir.join("notes.txt"))?;
+
+        // Removing a linked addon only removes the link
+        sync(true, false).await?;
+        fs::write(
+            tmp_dir.path().join("gdenv.toml"),
+            "[godot]\nversion = \"4.6.0-stable\"\n",
+        )?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+        let orphans = remove_orphaned_addons(&config, &project_spec, &SyncOptions::default())?;
+        assert!(orphans[0].link);
+        assert!(!addon_dir.exists() && !is_link(&addon_dir));
+        assert!(source_dir.join("plugin.cfg").is_file());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_remove_orphaned_addons() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = MockGitClient::new(config.clone());
+        let addon_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data/test-addon1-repo/addons/test-addon1");
+        let spec = |name: &str| {
+            format!(
+                r#"
+[godot]
+version = "4.6.0-stable"
+
+[addon.{name}]
+path = {}
+        "#,
+                toml::Value::String(addon_path.to_string_lossy().to_string())
+            )
+        };
+
+        fs::write(tmp_dir.path().join("gdenv.toml"), spec("test-addon1"))?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+        into_plans(
+            sync_addons(&config, project_spec, &git_client, &SyncOptions::default()).await?,
+        )?;
+        let old_dir = tmp_dir.path().join("addons/test-addon1");
+        fs::write(old_dir.join("notes.txt"), "Not part of the addon")?;
+
+        // Renaming the addon moves it to a new destination
+        fs::write(tmp_dir.path().join("gdenv.toml"), spec("renamed"))?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+        let orphans = find_orphaned_addons(&project_spec)?;
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].addon_name, "test-addon1");
+        assert_eq!(orphans[0].kept, vec![PathBuf::from("notes.txt")]);
+        assert!(old_dir.join("plugin.cfg").exists());
+
+        let options = SyncOptions {
+            snapshot: Some(Arc::new(SyncSnapshot::new(&config, tmp_dir.path())?)),
+            ..Default::default()
+        };
+        sync_addons(&config, project_spec.clone(), &git_client, &options).await?;
+        remove_orphaned_addons(&config, &project_spec, &options)?;
+        assert!(tmp_dir.path().join("addons/renamed/plugin.cfg").exists());
+        assert!(!old_dir.join("plugin.cfg").exists());
+        assert!(old_dir.join("notes.txt").exists());
+        assert!(find_orphaned_addons(&project_spec)?.is_empty());
+
+        // Undoing the sync moves the addon back
+        latest_snapshot(&config, tmp_dir.path())?
+            .context("No snapshot of the sync")?
+            .restore()?;
+        assert!(!tmp_dir.path().join("addons/renamed").exists());
+        assert!(old_dir.join("plugin.cfg").exists());
+        assert!(old_dir.join("notes.txt").exists());
+        assert_eq!(find_orphaned_addons(&project_spec)?.len(), 1);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_sync_from_subdirectory() -> Result<()> {
         let _ = tracing_subscriber::fmt()
@@ -291,7 +1551,7 @@ destination = "addons/test-addon1/subfolder"
             .prefix("gdenv-test-data-dir")
             .tempdir()?;
         let config = Config::setup(Some(&tmp_data_dir.path()))?;
-        let git_client = MockGitClient::new(config);
+        let git_client = MockGitClient::new(config.clone());
 
         let test_data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data");
         let test_addon_source = test_data_dir.join("test-addon2-repo/addons/test-addon2");
@@ -316,7 +1576,9 @@ path = {}
 
         // Try to sync using sub_dir as working_dir
         // This should fail or place things in the wrong place currently
-        sync_addons(project_spec, &git_client).await?;
+        into_plans(
+            sync_addons(&config, project_spec, &git_client, &SyncOptions::default()).await?,
+        )?;
 
         // It should be in project_dir/addons/test-addon2/plugin.cfg
         // because gdenv.toml is in project_dir
@@ -0,0 +1,239 @@
+//! Downloads addons that are published as `.zip` or `.tar.gz` archives,
+//! e.g. release artifacts with prebuilt GDExtension binaries.
+
+use crate::config::Config;
+use crate::download_client::download_url_to_file;
+use crate::file_lock::FileLock;
+use crate::file_sync::sha256_file;
+use crate::installer::extract_zip;
+use anyhow::{Context, Result, bail};
+use reqwest::Client;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    fn from_url(url: &str) -> Result<Self> {
+        let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+        if path.ends_with(".zip") {
+            Ok(Self::Zip)
+        } else if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            Ok(Self::TarGz)
+        } else {
+            bail!(
+                "Unsupported archive format: {url}. Only .zip and .tar.gz archives are supported."
+            )
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarGz => "tar.gz",
+        }
+    }
+}
+
+pub struct ArchiveClient {
+    config: Config,
+    client: Client,
+}
+
+impl ArchiveClient {
+    pub fn new(config: Config) -> Self {
+        let client = Client::builder()
+            .user_agent("gdenv/0.1.0")
+            .build()
+            .expect("Failed to create HTTP client");
+        Self { config, client }
+    }
+
+    /// Downloads the archive at `url` (`http(s)://` or `file://`) and extracts it into the cache.
+    /// If `sha256` is given, the archive must match it, and an archive that is already
+    /// extracted in the cache is used without downloading it again.
+    /// Returns the directory holding the extracted files.
+    pub async fn fetch(&self, url: &str, sha256: Option<&str>) -> Result<PathBuf> {
+        let format = ArchiveFormat::from_url(url)?;
+        let cache_dir = self.config.cache_dir.join("archives");
+        let expected_hash = sha256.map(str::to_lowercase);
+        if let Some(hash) = &expected_hash
+            && cache_dir.join(hash).exists()
+        {
+            return Ok(cache_dir.join(hash));
+        }
+        fs::create_dir_all(&cache_dir)?;
+
+        let partial_path = cache_dir.join(format!(
+            "download.{}.part-{}",
+            format.extension(),
+            std::process::id()
+        ));
+        let result = self.download(url, &partial_path).await.and_then(|()| {
+            let hash = sha256_file(&partial_path)?;
+            match &expected_hash {
+                Some(expected) if *expected != hash => bail!(
+                    "Checksum mismatch for {url}: expected sha256 {expected}, but the download has sha256 {hash}"
+                ),
+                Some(_) => {}
+                None => tracing::warn!(
+                    "No sha256 given for {}, so the archive can't be verified. Its sha256 is {}",
+                    url,
+                    hash
+                ),
+            }
+            let archive_path = cache_dir.join(format!("{hash}.{}", format.extension()));
+            fs::rename(&partial_path, &archive_path).context("Failed to move download")?;
+            Ok((hash, archive_path))
+        });
+        let (hash, archive_path) = match result {
+            Ok(downloaded) => downloaded,
+            Err(e) => {
+                let _ = fs::remove_file(&partial_path);
+                return Err(e);
+            }
+        };
+
+        let extracted_dir = cache_dir.join(&hash);
+        let _cache_lock = FileLock::acquire(
+            &self.config.locks_dir.join(format!("archive-{hash}.lock")),
+            &format!("extracting {url}"),
+        )
+        .await?;
+        if extracted_dir.exists() {
+            return Ok(extracted_dir);
+        }
+
+        // Extract next to the final location, so a failed extraction never looks cached.
+        let extracting_dir = cache_dir.join(format!("{hash}.extracting-{}", std::process::id()));
+        if extracting_dir.exists() {
+            fs::remove_dir_all(&extracting_dir)?;
+        }
+        let result = extract_archive(format, &archive_path, &extracting_dir).and_then(|()| {
+            fs::rename(&extracting_dir, &extracted_dir).context("Failed to move extracted files")
+        });
+        if let Err(e) = result {
+            let _ = fs::remove_dir_all(&extracting_dir);
+            return Err(e).context(format!("Failed to extract {}", archive_path.display()));
+        }
+
+        Ok(extracted_dir)
+    }
+
+    async fn download(&self, url: &str, path: &Path) -> Result<()> {
+        if url.starts_with("file://") {
+            let source = url::Url::parse(url)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .context(format!("Invalid file URL: {url}"))?;
+            fs::copy(&source, path).context(format!("Failed to read {}", source.display()))?;
+            return Ok(());
+        }
+        tracing::info!("Downloading {}", url);
+        download_url_to_file(&self.client, url, path)
+            .await
+            .context(format!("Failed to download {url}"))
+    }
+}
+
+fn extract_archive(format: ArchiveFormat, archive_path: &Path, destination: &Path) -> Result<()> {
+    match format {
+        ArchiveFormat::Zip => extract_zip(archive_path, destination),
+        ArchiveFormat::TarGz => {
+            let file = BufReader::new(File::open(archive_path)?);
+            let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+            archive.set_preserve_permissions(true);
+            fs::create_dir_all(destination)?;
+            archive.unpack(destination)?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::archives::zip_directory;
+
+    #[tokio::test]
+    async fn test_fetch_file_url() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let test_data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data");
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let client = ArchiveClient::new(config);
+
+        // A .tar.gz archive with a top-level folder
+        let tar_gz_path = tmp_dir.path().join("addon.tar.gz");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            File::create(&tar_gz_path)?,
+            flate2::Compression::default(),
+        ));
+        builder.append_dir_all("test-addon1-1.0", test_data_dir.join("test-addon1-repo"))?;
+        builder.into_inner()?.finish()?;
+        let tar_gz_url = url::Url::from_file_path(&tar_gz_path)
+            .map_err(|()| anyhow::anyhow!("Invalid path"))?
+            .to_string();
+
+        let hash = sha256_file(&tar_gz_path)?;
+        let extracted = client
+            .fetch(&tar_gz_url, Some(&hash.to_uppercase()))
+            .await?;
+        assert_eq!(
+            extracted,
+            tmp_data_dir.path().join("cache/archives").join(&hash)
+        );
+        assert!(
+            extracted
+                .join("test-addon1-1.0/addons/test-addon1/plugin.cfg")
+                .exists()
+        );
+
+        // Once extracted, an archive with a known hash isn't read again
+        fs::remove_file(&tar_gz_path)?;
+        assert_eq!(client.fetch(&tar_gz_url, Some(&hash)).await?, extracted);
+
+        // A .zip archive without a checksum
+        let zip_path = tmp_dir.path().join("addon.zip");
+        fs::write(
+            &zip_path,
+            zip_directory(&test_data_dir.join("test-addon1v2-repo"), "test-addon1-2.0")?,
+        )?;
+        let zip_url = url::Url::from_file_path(&zip_path)
+            .map_err(|()| anyhow::anyhow!("Invalid path"))?
+            .to_string();
+        let extracted = client.fetch(&zip_url, None).await?;
+        assert!(
+            extracted
+                .join("test-addon1-2.0/addons/test-addon1/new_file.txt")
+                .exists()
+        );
+
+        // A checksum mismatch is rejected and nothing is extracted
+        let wrong_hash = "0".repeat(64);
+        let error = client.fetch(&zip_url, Some(&wrong_hash)).await.unwrap_err();
+        assert!(error.to_string().contains("Checksum mismatch"), "{error}");
+        assert!(
+            !tmp_data_dir
+                .path()
+                .join("cache/archives")
+                .join(wrong_hash)
+                .exists()
+        );
+
+        assert!(
+            client
+                .fetch("https://example.com/addon.rar", None)
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+//! Client for the Godot Asset Library REST API, used to download addons
+//! published on the Asset Library.
+
+use crate::config::Config;
+use crate::download_client::download_url_to_file;
+use crate::file_lock::FileLock;
+use crate::installer::extract_zip;
+use anyhow::{Context, Result, bail};
+use reqwest::Client;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A specific version of an asset and where to download it from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetDownload {
+    pub asset_id: u64,
+    pub title: String,
+    pub version_string: String,
+    pub download_url: String,
+}
+
+/// Matches the API JSON response for a single asset
+#[derive(Debug, Deserialize)]
+struct AssetJson {
+    title: String,
+    version_string: String,
+    download_url: String,
+}
+
+/// Matches the API JSON response for a page of asset edits
+#[derive(Debug, Deserialize)]
+struct AssetEditPageJson {
+    result: Vec<AssetEditSummaryJson>,
+    pages: u32,
+}
+
+/// Matches a single entry of the asset edits list. Fields that an edit didn't change are null.
+#[derive(Debug, Deserialize)]
+struct AssetEditSummaryJson {
+    edit_id: String,
+    version_string: Option<String>,
+}
+
+/// Matches the API JSON response for a single asset edit
+#[derive(Debug, Deserialize)]
+struct AssetEditJson {
+    title: Option<String>,
+    download_url: Option<String>,
+}
+
+pub struct AssetLibraryClient {
+    config: Config,
+    client: Client,
+}
+
+impl AssetLibraryClient {
+    pub fn new(config: Config) -> Self {
+        let client = Client::builder()
+            .user_agent("gdenv/0.1.0")
+            .build()
+            .expect("Failed to create HTTP client");
+        Self { config, client }
+    }
+
+    /// Looks up the download of `asset_id`. Without a `version`, the latest version is used.
+    pub async fn resolve(&self, asset_id: u64, version: Option<&str>) -> Result<AssetDownload> {
+        let asset: AssetJson =
+            self.get_json(&format!("asset/{asset_id}"))
+                .await
+                .context(format!(
+                    "Failed to look up asset {asset_id} in the Asset Library"
+                ))?;
+
+        match version {
+            None => Ok(AssetDownload {
+                asset_id,
+                title: asset.title,
+                version_string: asset.version_string,
+                download_url: asset.download_url,
+            }),
+            Some(version) if version == asset.version_string => Ok(AssetDownload {
+                asset_id,
+                title: asset.title,
+                version_string: asset.version_string,
+                download_url: asset.download_url,
+            }),
+            Some(version) => self.resolve_older_version(asset_id, &asset, version).await,
+        }
+    }
+
+    /// Older versions are only available through the accepted edits of the asset.
+    async fn resolve_older_version(
+        &self,
+        asset_id: u64,
+        asset: &AssetJson,
+        version: &str,
+    ) -> Result<AssetDownload> {
+        let mut page = 0;
+        loop {
+            let edits: AssetEditPageJson = self
+                .get_json(&format!(
+                    "asset/edit?asset={asset_id}&status=accepted&page={page}"
+                ))
+                .await
+                .context(format!("Failed to list versions of asset {asset_id}"))?;
+
+            if let Some(edit) = edits
+                .result
+                .iter()
+                .find(|edit| edit.version_string.as_deref() == Some(version))
+            {
+                let edit: AssetEditJson = self
+                    .get_json(&format!("asset/edit/{}", edit.edit_id))
+                    .await
+                    .context(format!(
+                        "Failed to look up version {version} of asset {asset_id}"
+                    ))?;
+                let download_url = edit.download_url.context(format!(
+                    "Version {version} of asset {asset_id} has no download URL"
+                ))?;
+                return Ok(AssetDownload {
+                    asset_id,
+                    title: edit.title.unwrap_or_else(|| asset.title.clone()),
+                    version_string: version.to_string(),
+                    download_url,
+                });
+            }
+
+            page += 1;
+            if page >= edits.pages {
+                bail!(
+                    "Version {} of asset {} (\"{}\") not found in the Asset Library. The latest version is {}.",
+                    version,
+                    asset_id,
+                    asset.title,
+                    asset.version_string
+                );
+            }
+        }
+    }
+
+    /// Downloads and extracts the asset, unless it's already cached.
+    /// Returns the directory holding the asset's files, with the top-level folder of the
+    /// archive stripped.
+    pub async fn download(&self, download: &AssetDownload) -> Result<PathBuf> {
+        let cache_dir = self.config.cache_dir.join("asset_library");
+        let cache_name = format!(
+            "{}-{}",
+            download.asset_id,
+            sanitize_file_name(&download.version_string)
+        );
+        let extracted_dir = cache_dir.join(&cache_name);
+        if extracted_dir.exists() {
+            return Ok(extracted_dir);
+        }
+
+        let _cache_lock = FileLock::acquire(
+            &self
+                .config
+                .locks_dir
+                .join(format!("asset-library-{cache_name}.lock")),
+            &format!("downloading asset {}", download.asset_id),
+        )
+        .await?;
+        // Another gdenv process may have downloaded the asset while we waited for the lock.
+        if extracted_dir.exists() {
+            return Ok(extracted_dir);
+        }
+        fs::create_dir_all(&cache_dir)?;
+
+        let archive_path = cache_dir.join(format!("{cache_name}.zip"));
+        if !archive_path.exists() {
+            tracing::info!(
+                "Downloading \"{}\" {} from the Asset Library",
+                download.title,
+                download.version_string
+            );
+            let partial_path =
+                cache_dir.join(format!("{cache_name}.zip.part-{}", std::process::id()));
+            let result = download_url_to_file(&self.client, &download.download_url, &partial_path)
+                .await
+                .and_then(|()| {
+                    fs::rename(&partial_path, &archive_path).context("Failed to move download")
+                });
+            if let Err(e) = result {
+                let _ = fs::remove_file(&partial_path);
+                return Err(e).context(format!(
+                    "Failed to download asset {} from {}",
+                    download.asset_id, download.download_url
+                ));
+            }
+        }
+
+        // Extract next to the final location, so a failed extraction never looks cached.
+        let extracting_dir =
+            cache_dir.join(format!("{cache_name}.extracting-{}", std::process::id()));
+        if extracting_dir.exists() {
+            fs::remove_dir_all(&extracting_dir)?;
+        }
+        let result = extract_zip(&archive_path, &extracting_dir)
+            .and_then(|()| strip_top_level_folder(&extracting_dir))
+            .and_then(|root| {
+                fs::rename(root, &extracted_dir).context("Failed to move extracted files")
+            });
+        let _ = fs::remove_dir_all(&extracting_dir);
+        result.context(format!(
+            "Failed to extract asset archive {}",
+            archive_path.display()
+        ))?;
+
+        Ok(extracted_dir)
+    }
+
+    async fn get_json<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T> {
+        let url = format!(
+            "{}/{}",
+            self.config.asset_library_url.trim_end_matches('/'),
+            path
+        );
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            bail!(
+                "Asset Library request {} failed: {}",
+                url,
+                response.status()
+            );
+        }
+        Ok(response.json().await?)
+    }
+}
+
+/// Asset Library archives wrap the asset's files in a single folder (e.g. `repo-<commit>/`).
+/// Returns that folder, or `dir` itself if the archive has no single top-level folder.
+fn strip_top_level_folder(dir: &Path) -> Result<PathBuf> {
+    let entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    match entries.as_slice() {
+        [entry] if entry.path().is_dir() => Ok(entry.path()),
+        _ => Ok(dir.to_path_buf()),
+    }
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::archives::zip_directory;
+    use crate::test_helpers::mock_http_server::MockHttpServer;
+
+    #[tokio::test]
+    async fn test_resolve_and_download() -> Result<()> {
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let test_data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data");
+        let server = MockHttpServer::start().await?;
+
+        server.add(
+            "/api/asset/1234",
+            format!(
+                r#"{{"asset_id": "1234", "title": "Test Addon", "version_string": "2.0", "download_url": "{}"}}"#,
+                server.url("/download/v2.zip")
+            ),
+        );
+        server.add(
+            "/api/asset/edit?asset=1234&status=accepted&page=0",
+            r#"{"result": [{"edit_id": "20", "version_string": "2.0"}, {"edit_id": "10", "version_string": "1.0"}], "pages": 1}"#,
+        );
+        server.add(
+            "/api/asset/edit/10",
+            format!(
+                r#"{{"edit_id": "10", "title": null, "download_url": "{}"}}"#,
+                server.url("/download/v1.zip")
+            ),
+        );
+        server.add(
+            "/download/v2.zip",
+            zip_directory(
+                &test_data_dir.join("test-addon1v2-repo"),
+                "test-addon1-main",
+            )?,
+        );
+        server.add(
+            "/download/v1.zip",
+            zip_directory(&test_data_dir.join("test-addon1-repo"), "test-addon1-main")?,
+        );
+
+        let config = Config {
+            asset_library_url: server.url("/api"),
+            ..Config::setup(Some(tmp_data_dir.path()))?
+        };
+        let client = AssetLibraryClient::new(config);
+
+        let latest = client.resolve(1234, None).await?;
+        assert_eq!(latest.version_string, "2.0");
+        let latest_dir = client.download(&latest).await?;
+        assert!(latest_dir.join("addons/test-addon1/new_file.txt").exists());
+
+        let older = client.resolve(1234, Some("1.0")).await?;
+        assert_eq!(older.title, "Test Addon");
+        assert_eq!(older.download_url, server.url("/download/v1.zip"));
+        let older_dir = client.download(&older).await?;
+        assert!(older_dir.join("addons/test-addon1/old_file.txt").exists());
+        assert_ne!(latest_dir, older_dir);
+
+        let error = client.resolve(1234, Some("3.0")).await.unwrap_err();
+        assert!(
+            error.to_string().contains("The latest version is 2.0"),
+            "{error}"
+        );
+        Ok(())
+    }
+}
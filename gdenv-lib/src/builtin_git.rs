@@ -0,0 +1,536 @@
+//! A [GitClient] built on libgit2, for machines where the `git` command isn't installed.
+//! It shares the git cache with [SystemGitClient](crate::git::SystemGitClient), but can't
+//! fetch only the blobs that are checked out, nor download Git LFS files. Commits whose blobs
+//! the `git` command didn't fetch into a mirror are fetched again in full.
+
+use crate::config::Config;
+use crate::file_lock::FileLock;
+use crate::git::{
+    DEFAULT_BRANCH_REF, GitClient, GitTag, get_repo_dir, get_worktree_dir, is_abbreviated_commit,
+    lock_repo_dir, mark_used, parse_tags, slash_path, uses_lfs,
+};
+use anyhow::{Context, Result, anyhow, bail};
+use git2::build::CheckoutBuilder;
+use git2::{
+    AutotagOption, Direction, FetchOptions, ObjectType, Oid, Remote, Repository,
+    RepositoryInitOptions, TreeWalkMode, TreeWalkResult,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The depth that fetches the whole history of a shallow repository.
+const UNSHALLOW_DEPTH: i32 = i32::MAX;
+
+pub struct BuiltinGitClient {
+    config: Config,
+}
+
+impl BuiltinGitClient {
+    pub fn new(config: Config) -> Self {
+        Self { config }
+    }
+}
+
+impl GitClient for BuiltinGitClient {
+    async fn init(&self, path: &Path, branch: Option<&str>) -> Result<()> {
+        let path = path.to_path_buf();
+        let branch = branch.map(str::to_string);
+        tokio::task::spawn_blocking(move || {
+            let mut options = RepositoryInitOptions::new();
+            if let Some(branch) = &branch {
+                options.initial_head(branch);
+            }
+            Repository::init_opts(&path, &options)
+                .context(format!("Failed to initialize a git repository at {path:?}"))?;
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn lock_repo(&self, repo_url: &str) -> Result<FileLock> {
+        lock_repo_dir(&self.config, repo_url).await
+    }
+
+    async fn checkout(
+        &self,
+        repo_url: &str,
+        git_ref: &str,
+        sparse_dirs: &[PathBuf],
+    ) -> Result<PathBuf> {
+        let config = self.config.clone();
+        let repo_url = repo_url.to_string();
+        let git_ref = git_ref.to_string();
+        let sparse_dirs = sparse_dirs.to_vec();
+        tokio::task::spawn_blocking(move || checkout(&config, &repo_url, &git_ref, &sparse_dirs))
+            .await?
+    }
+
+    async fn list_remote_tags(&self, repo_url: &str) -> Result<Vec<GitTag>> {
+        let config = self.config.clone();
+        let repo_url = repo_url.to_string();
+        tokio::task::spawn_blocking(move || list_remote_tags(&config, &repo_url)).await?
+    }
+}
+
+fn checkout(
+    config: &Config,
+    repo_url: &str,
+    git_ref: &str,
+    sparse_dirs: &[PathBuf],
+) -> Result<PathBuf> {
+    let mirror_dir = get_repo_dir(config, repo_url);
+    let repo = if mirror_dir.exists() {
+        Repository::open_bare(&mirror_dir)
+            .context(format!("Failed to open {}", mirror_dir.display()))?
+    } else {
+        init_mirror(&mirror_dir, repo_url)?
+    };
+
+    let fetched = fetch(&repo, git_ref);
+    let commit = match (&fetched, resolve_cached_ref(&repo, git_ref)) {
+        (Ok(()), Some(commit)) => commit,
+        // Without network access (e.g. after importing an offline bundle),
+        // the ref may still be available in the mirror.
+        (Err(_), Some(commit)) => {
+            tracing::warn!(
+                "git fetch failed for {} at {}, using the cached copy of the repository instead.",
+                repo_url,
+                git_ref
+            );
+            commit
+        }
+        (Err(error), None) => {
+            return Err(anyhow!(
+                "git fetch failed for {} at {}. Reason: {:#}",
+                repo_url,
+                git_ref,
+                error
+            ));
+        }
+        (Ok(()), None) => bail!("{} has no branch, tag or commit {}", repo_url, git_ref),
+    };
+
+    let worktree_dir = get_worktree_dir(config, repo_url, &commit.to_string(), sparse_dirs);
+    if !worktree_dir.exists() {
+        if let Err(error) = export_commit(&repo, commit, &worktree_dir, sparse_dirs) {
+            if !is_partial_mirror(&repo) {
+                return Err(error);
+            }
+            // The mirror was set up by the `git` command, which fetches the blobs of the files
+            // it checks out on demand. libgit2 can't, so the commit is fetched in full instead.
+            tracing::debug!(
+                "Fetching commit {} of {} with all its files: {:#}",
+                commit,
+                repo_url,
+                error
+            );
+            export_fetched_commit(repo_url, commit, &worktree_dir, sparse_dirs)?;
+        }
+        if uses_lfs(&worktree_dir) {
+            tracing::warn!(
+                "{} stores files with Git LFS, which the built-in git client can't download. Install git and git-lfs (https://git-lfs.com) to get them.",
+                repo_url
+            );
+        }
+    }
+    mark_used(&worktree_dir, &commit.to_string())?;
+    Ok(worktree_dir)
+}
+
+/// Creates an empty bare repository for `repo_url`, the same way
+/// [SystemGitClient](crate::git::SystemGitClient) does.
+fn init_mirror(mirror_dir: &Path, repo_url: &str) -> Result<Repository> {
+    let init = || -> Result<Repository> {
+        let repo = Repository::init_bare(mirror_dir)?;
+        repo.remote("origin", repo_url)?;
+        Ok(repo)
+    };
+    init().map_err(|error| {
+        // Don't leave a half set up repository behind for the next run
+        let _ = fs::remove_dir_all(mirror_dir);
+        error.context(format!("Failed to create a mirror of {repo_url}"))
+    })
+}
+
+/// Whether the mirror was set up by [SystemGitClient](crate::git::SystemGitClient) to fetch
+/// commits without their blobs.
+fn is_partial_mirror(repo: &Repository) -> bool {
+    repo.config()
+        .and_then(|config| config.get_bool("remote.origin.promisor"))
+        .unwrap_or(false)
+}
+
+/// Fetches `commit` with all its blobs into a repository of its own, and exports it from
+/// there like [export_commit]. The mirror is left as it is, for the `git` command to use.
+fn export_fetched_commit(
+    repo_url: &str,
+    commit: Oid,
+    worktree_dir: &Path,
+    sparse_dirs: &[PathBuf],
+) -> Result<()> {
+    let fetch_dir = worktree_dir.with_extension("fetch");
+    if fetch_dir.exists() {
+        fs::remove_dir_all(&fetch_dir)
+            .context(format!("Failed to remove {}", fetch_dir.display()))?;
+    }
+    let result = init_mirror(&fetch_dir, repo_url).and_then(|repo| {
+        fetch(&repo, &commit.to_string())
+            .context(format!("Failed to fetch commit {commit} of {repo_url}"))?;
+        export_commit(&repo, commit, worktree_dir, sparse_dirs)
+    });
+    let _ = fs::remove_dir_all(&fetch_dir);
+    result
+}
+
+/// Fetches `git_ref` without its history, keeping it where `git fetch` would: branches under
+/// `refs/remotes/origin`, tags under `refs/tags`, and the default branch at
+/// [DEFAULT_BRANCH_REF].
+fn fetch(repo: &Repository, git_ref: &str) -> Result<()> {
+    let mut remote = repo.find_remote("origin")?;
+    if is_abbreviated_commit(git_ref) {
+        // Abbreviated commit hashes can only be found in the full history
+        let refspecs = [
+            "+refs/heads/*:refs/remotes/origin/*",
+            "+refs/tags/*:refs/tags/*",
+        ];
+        return fetch_refspecs(&mut remote, &refspecs, UNSHALLOW_DEPTH);
+    }
+    if Oid::from_str(git_ref).is_ok_and(|_| git_ref.len() == 40) {
+        return fetch_refspecs(&mut remote, &[git_ref], 1);
+    }
+
+    let connection = remote.connect_auth(Direction::Fetch, None, None)?;
+    let heads: Vec<String> = connection
+        .list()?
+        .iter()
+        .map(|head| head.name().to_string())
+        .collect();
+    let default_branch = connection
+        .default_branch()
+        .ok()
+        .and_then(|name| name.as_str().map(str::to_string));
+    drop(connection);
+
+    let remote_ref = if git_ref.is_empty() {
+        default_branch.context("The repository has no default branch")?
+    } else {
+        [
+            format!("refs/tags/{git_ref}"),
+            format!("refs/heads/{git_ref}"),
+            git_ref.to_string(),
+        ]
+        .into_iter()
+        .find(|name| heads.contains(name))
+        .context(format!("The repository has no branch or tag {git_ref}"))?
+    };
+    let local_ref = match remote_ref.strip_prefix("refs/heads/") {
+        Some(branch) => format!("refs/remotes/origin/{branch}"),
+        None => remote_ref.clone(),
+    };
+    fetch_refspecs(&mut remote, &[&format!("+{remote_ref}:{local_ref}")], 1)?;
+
+    if git_ref.is_empty() {
+        let commit = repo.refname_to_id(&local_ref)?;
+        repo.reference(DEFAULT_BRANCH_REF, commit, true, "gdenv: default branch")?;
+    }
+    Ok(())
+}
+
+fn fetch_refspecs(remote: &mut Remote, refspecs: &[&str], depth: i32) -> Result<()> {
+    let mut options = FetchOptions::new();
+    // libgit2 can't fetch shallowly from repositories on disk, nor does it need to
+    let is_local = remote.url().is_some_and(|url| {
+        url.starts_with("file://") || !url.contains(':') || Path::new(url).is_absolute()
+    });
+    if !is_local {
+        options.depth(depth);
+    }
+    options.download_tags(AutotagOption::None);
+    remote
+        .fetch(refspecs, Some(&mut options), None)
+        .context(format!("Failed to fetch {}", refspecs.join(" ")))
+}
+
+/// Looks up the commit `git_ref` points to using only what is already in the mirror.
+fn resolve_cached_ref(repo: &Repository, git_ref: &str) -> Option<Oid> {
+    let candidates = if git_ref.is_empty() {
+        vec![DEFAULT_BRANCH_REF.to_string()]
+    } else {
+        vec![git_ref.to_string(), format!("origin/{git_ref}")]
+    };
+    candidates.iter().find_map(|candidate| {
+        let object = repo.revparse_single(candidate).ok()?;
+        Some(object.peel_to_commit().ok()?.id())
+    })
+}
+
+/// Writes the files of `commit` in `sparse_dirs` to `worktree_dir`, the same files a sparse
+/// checkout in cone mode would have. The files are written next to it and moved into place
+/// once they're complete.
+fn export_commit(
+    repo: &Repository,
+    commit: Oid,
+    worktree_dir: &Path,
+    sparse_dirs: &[PathBuf],
+) -> Result<()> {
+    let tmp_dir = worktree_dir.with_extension("tmp");
+    if tmp_dir.exists() {
+        fs::remove_dir_all(&tmp_dir).context(format!("Failed to remove {}", tmp_dir.display()))?;
+    }
+    fs::create_dir_all(&tmp_dir).context(format!("Failed to create {}", tmp_dir.display()))?;
+
+    let tree = repo.find_commit(commit)?.tree()?;
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force().target_dir(&tmp_dir).update_index(false);
+    if !sparse_dirs.is_empty() {
+        let sparse_dirs: Vec<String> = sparse_dirs.iter().map(|dir| slash_path(dir)).collect();
+        let mut paths = Vec::new();
+        tree.walk(TreeWalkMode::PreOrder, |parent, entry| {
+            if entry.kind() != Some(ObjectType::Tree)
+                && let Some(name) = entry.name()
+            {
+                let path = format!("{parent}{name}");
+                if in_cone(&path, &sparse_dirs) {
+                    paths.push(path);
+                }
+            }
+            TreeWalkResult::Ok
+        })?;
+        checkout.disable_pathspec_match(true);
+        for path in paths {
+            checkout.path(path);
+        }
+    }
+    repo.checkout_tree(tree.as_object(), Some(&mut checkout))
+        .context(format!("Failed to check out commit {commit}"))?;
+
+    fs::rename(&tmp_dir, worktree_dir).context(format!(
+        "Failed to move {} to {}",
+        tmp_dir.display(),
+        worktree_dir.display()
+    ))
+}
+
+/// Whether a sparse checkout of `sparse_dirs` in cone mode has the file at `path`: files in
+/// the directories, and files directly in the directories above them.
+fn in_cone(path: &str, sparse_dirs: &[String]) -> bool {
+    let parent = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+    sparse_dirs.iter().any(|dir| {
+        path.starts_with(&format!("{dir}/"))
+            || parent.is_empty()
+            || dir.starts_with(&format!("{parent}/"))
+    })
+}
+
+fn list_remote_tags(config: &Config, repo_url: &str) -> Result<Vec<GitTag>> {
+    let list = || -> Result<Vec<GitTag>> {
+        let mut remote = Remote::create_detached(repo_url)?;
+        let connection = remote.connect_auth(Direction::Fetch, None, None)?;
+        let listing: String = connection
+            .list()?
+            .iter()
+            .map(|head| format!("{}\t{}\n", head.oid(), head.name()))
+            .collect();
+        Ok(parse_tags(&listing))
+    };
+    let error = match list() {
+        Ok(tags) => return Ok(tags),
+        Err(error) => error,
+    };
+
+    // Without network access, fall back to the tags of the mirror, like checkout
+    let mirror_dir = get_repo_dir(config, repo_url);
+    if let Ok(repo) = Repository::open_bare(&mirror_dir) {
+        let mut tags = Vec::new();
+        for reference in repo.references_glob("refs/tags/*")? {
+            let reference = reference?;
+            if let (Some(name), Ok(commit)) = (reference.shorthand(), reference.peel_to_commit()) {
+                tags.push(GitTag {
+                    name: name.to_string(),
+                    commit: commit.id().to_string(),
+                });
+            }
+        }
+        tracing::warn!(
+            "git ls-remote failed for {}, using the tags of the cached copy of the repository instead.",
+            repo_url
+        );
+        return Ok(tags);
+    }
+    Err(error.context(format!("git ls-remote failed for {repo_url}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::SystemGitClient;
+    use std::process::Command;
+
+    #[tokio::test]
+    async fn test_builtin_git_client_local_checkout() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = BuiltinGitClient::new(config);
+
+        // 1. Create a repository with two tagged commits, and a bare clone of it to fetch from
+        let source_repo = tmp_dir.path().join("source_repo");
+        git_client.init(&source_repo, Some("main")).await?;
+        let git = |args: &[&str]| -> Result<String> {
+            let output = Command::new("git")
+                .current_dir(&source_repo)
+                .args(["-c", "user.email=test@example.com", "-c", "user.name=test"])
+                .args(args)
+                .output()?;
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        fs::create_dir_all(source_repo.join("addons/foo"))?;
+        fs::create_dir_all(source_repo.join("docs"))?;
+        fs::write(source_repo.join("addons/foo/plugin.cfg"), "[plugin]")?;
+        fs::write(source_repo.join("docs/index.md"), "# Docs")?;
+        fs::write(source_repo.join("version.txt"), "1")?;
+        git(&["add", "."])?;
+        git(&["commit", "-m", "Version 1"])?;
+        git(&["tag", "-a", "v1.0.0", "-m", "Release 1.0.0"])?;
+        let first_commit = git(&["rev-parse", "HEAD"])?;
+        fs::write(source_repo.join("version.txt"), "2")?;
+        git(&["commit", "-am", "Version 2"])?;
+        git(&["tag", "v2.0.0"])?;
+        let second_commit = git(&["rev-parse", "HEAD"])?;
+        let bare_repo = tmp_dir.path().join("bare_repo.git");
+        Command::new("git")
+            .args(["clone", "--bare", "--quiet"])
+            .arg(&source_repo)
+            .arg(&bare_repo)
+            .status()?;
+        let repo_url = url::Url::from_file_path(&bare_repo)
+            .map_err(|_| anyhow!("Invalid path"))?
+            .to_string();
+
+        // 2. Check out branches, tags and commits
+        let read_version = |dir: &Path| fs::read_to_string(dir.join("version.txt"));
+        let main = git_client.checkout(&repo_url, "main", &[]).await?;
+        assert_eq!(read_version(&main)?, "2");
+        assert_eq!(fs::read_to_string(main.join("docs/index.md"))?, "# Docs");
+        assert_eq!(git_client.checkout(&repo_url, "", &[]).await?, main);
+        let v1 = git_client.checkout(&repo_url, "v1.0.0", &[]).await?;
+        assert_eq!(read_version(&v1)?, "1");
+        let commit = git_client.checkout(&repo_url, &first_commit, &[]).await?;
+        assert_eq!(commit, v1);
+        let abbreviated = git_client
+            .checkout(&repo_url, &second_commit[..10], &[])
+            .await?;
+        assert_eq!(abbreviated, main);
+        assert!(
+            git_client
+                .checkout(&repo_url, "no-such-branch", &[])
+                .await
+                .is_err()
+        );
+
+        // 3. Sparse checkouts only have the requested directories and top-level files
+        let sparse = git_client
+            .checkout(&repo_url, "v2.0.0", &[PathBuf::from("addons/foo")])
+            .await?;
+        assert_ne!(sparse, main);
+        assert!(sparse.join("addons/foo/plugin.cfg").exists());
+        assert!(sparse.join("version.txt").exists());
+        assert!(!sparse.join("docs").exists());
+
+        // 4. List the tags, and fall back to the mirror's tags without access to the repository
+        let mut tags = git_client.list_remote_tags(&repo_url).await?;
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        let expected_tags = vec![
+            GitTag {
+                name: "v1.0.0".to_string(),
+                commit: first_commit.clone(),
+            },
+            GitTag {
+                name: "v2.0.0".to_string(),
+                commit: second_commit.clone(),
+            },
+        ];
+        assert_eq!(tags, expected_tags);
+        fs::rename(&bare_repo, tmp_dir.path().join("moved_repo"))?;
+        let mut tags = git_client.list_remote_tags(&repo_url).await?;
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(tags, expected_tags);
+        assert_eq!(git_client.checkout(&repo_url, "v1.0.0", &[]).await?, v1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_builtin_git_client_checkout_from_partial_mirror() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let system_git_client = SystemGitClient::new(config.clone());
+        let git_client = BuiltinGitClient::new(config.clone());
+
+        // 1. Create a repository that serves partial clones
+        let source_repo = tmp_dir.path().join("source_repo");
+        git_client.init(&source_repo, Some("main")).await?;
+        let git = |args: &[&str]| -> Result<()> {
+            Command::new("git")
+                .current_dir(&source_repo)
+                .args(["-c", "user.email=test@example.com", "-c", "user.name=test"])
+                .args(args)
+                .status()?;
+            Ok(())
+        };
+        git(&["config", "uploadpack.allowFilter", "true"])?;
+        fs::create_dir_all(source_repo.join("addons/foo"))?;
+        fs::create_dir_all(source_repo.join("docs"))?;
+        fs::write(source_repo.join("addons/foo/plugin.cfg"), "[plugin]")?;
+        fs::write(source_repo.join("docs/index.md"), "# Docs")?;
+        git(&["add", "."])?;
+        git(&["commit", "-m", "Version 1"])?;
+        git(&["tag", "v1.0.0"])?;
+        let repo_url = url::Url::from_file_path(&source_repo)
+            .map_err(|_| anyhow!("Invalid path"))?
+            .to_string();
+
+        // 2. A sparse checkout with the `git` command leaves the other blobs out of the mirror
+        let sparse_dirs = [PathBuf::from("addons/foo")];
+        let sparse = system_git_client
+            .checkout(&repo_url, "v1.0.0", &sparse_dirs)
+            .await?;
+        assert!(!sparse.join("docs").exists());
+        let mirror_dir = get_repo_dir(&config, &repo_url);
+        assert!(is_partial_mirror(&Repository::open_bare(&mirror_dir)?));
+
+        // 3. The built-in client still checks out every file
+        let full = git_client.checkout(&repo_url, "v1.0.0", &[]).await?;
+        assert_ne!(full, sparse);
+        assert_eq!(fs::read_to_string(full.join("docs/index.md"))?, "# Docs");
+        assert!(full.join("addons/foo/plugin.cfg").exists());
+        assert!(!full.with_extension("fetch").exists());
+
+        // 4. The mirror is still usable by the `git` command
+        let system_full = system_git_client.checkout(&repo_url, "v1.0.0", &[]).await?;
+        assert_eq!(system_full, full);
+        assert!(
+            system_git_client
+                .checkout(&repo_url, "main", &[])
+                .await
+                .is_ok()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_cone() {
+        let sparse_dirs = vec!["addons/foo".to_string()];
+        assert!(in_cone("addons/foo/plugin.cfg", &sparse_dirs));
+        assert!(in_cone("addons/foo/sub/icon.svg", &sparse_dirs));
+        assert!(in_cone("README.md", &sparse_dirs));
+        assert!(in_cone("addons/LICENSE", &sparse_dirs));
+        assert!(!in_cone("addons/bar/plugin.cfg", &sparse_dirs));
+        assert!(!in_cone("addons/foobar/plugin.cfg", &sparse_dirs));
+        assert!(!in_cone("docs/index.md", &sparse_dirs));
+    }
+}
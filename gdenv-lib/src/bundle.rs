@@ -0,0 +1,406 @@
+//! Offline bundles: a single tarball with everything a project needs from the
+//! gdenv data directory (Godot archives, export templates, the releases cache
+//! and git addon repositories), so that it can be used on a machine without
+//! network access.
+
+use crate::addon_updates::resolve_version_tag;
+use crate::config::Config;
+use crate::download_client::DownloadClient;
+use crate::git::{GitClient, get_repo_dir, lock_repo_dir, sparse_checkout_dirs};
+use crate::github::{GitHubClient, GitHubRelease};
+use crate::godot_version::GodotVersion;
+use crate::installer::{download_export_templates, download_godot_archive};
+use crate::project_specification::GitAddonSource;
+use anyhow::{Context, Result, bail};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// Name of the manifest entry, which is always the first entry of a bundle.
+pub const BUNDLE_MANIFEST_FILE: &str = "gdenv-bundle.json";
+
+/// Name of the entry holding the known Godot releases.
+const BUNDLE_RELEASES_FILE: &str = "releases.json";
+
+/// What to put into a bundle.
+#[derive(Debug, Clone)]
+pub struct BundleContents {
+    pub godot_versions: Vec<GodotVersion>,
+    /// Operating system the Godot archives are downloaded for.
+    pub os: String,
+    /// Architecture the Godot archives are downloaded for.
+    pub arch: String,
+    /// Whether to include the export templates of each Godot version.
+    pub export_templates: bool,
+    pub git_addons: Vec<GitAddonSource>,
+}
+
+/// Describes the contents of a bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    /// Version of gdenv that created the bundle.
+    pub gdenv_version: String,
+    pub os: String,
+    pub arch: String,
+    pub godot_versions: Vec<GodotVersion>,
+    pub export_templates: bool,
+    /// URLs of the git repositories in the bundle.
+    pub git_repositories: Vec<String>,
+    /// Cached downloads in the bundle, relative to the data directory.
+    pub files: Vec<PathBuf>,
+}
+
+/// Downloads everything described by `contents` and packages it into a tarball at `output`.
+pub async fn create_bundle<D: DownloadClient, G: GitClient>(
+    config: &Config,
+    download_client: &D,
+    git_client: &G,
+    contents: &BundleContents,
+    output: &Path,
+) -> Result<BundleManifest> {
+    let releases = download_client.godot_releases(false, false).await?;
+
+    let mut files = Vec::new();
+    for version in &contents.godot_versions {
+        let archive = download_godot_archive(
+            config,
+            version,
+            download_client,
+            &contents.os,
+            &contents.arch,
+        )
+        .await
+        .context(format!("Failed to download Godot {version}"))?;
+        files.push(archive);
+
+        if contents.export_templates {
+            let templates = download_export_templates(config, version, download_client)
+                .await
+                .context(format!(
+                    "Failed to download export templates for Godot {version}"
+                ))?;
+            files.push(templates);
+        }
+    }
+    files.sort();
+    files.dedup();
+
+    let mut git_repositories: Vec<String> = contents
+        .git_addons
+        .iter()
+        .map(|addon| addon.git.clone())
+        .collect();
+    git_repositories.sort();
+    git_repositories.dedup();
+
+    let manifest = BundleManifest {
+        gdenv_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: contents.os.clone(),
+        arch: contents.arch.clone(),
+        godot_versions: contents.godot_versions.clone(),
+        export_templates: contents.export_templates,
+        git_repositories,
+        files: files
+            .iter()
+            .map(|file| file.strip_prefix(&config.data_dir).map(Path::to_path_buf))
+            .collect::<Result<_, _>>()
+            .context("Cached downloads must be inside the data directory")?,
+    };
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut builder = tar::Builder::new(
+        File::create(output).context(format!("Failed to create bundle: {}", output.display()))?,
+    );
+    builder.follow_symlinks(false);
+    append_json(&mut builder, BUNDLE_MANIFEST_FILE, &manifest)?;
+    append_json(&mut builder, BUNDLE_RELEASES_FILE, &releases)?;
+
+    for (file, name) in files.iter().zip(&manifest.files) {
+        tracing::info!("Adding {} to bundle", name.display());
+        builder
+            .append_path_with_name(file, name)
+            .context(format!("Failed to add {} to bundle", file.display()))?;
+    }
+
+    // Check out every revision the project uses, so that it's available offline.
+    // The repository lock is held until the repository has been added to the bundle.
+    let mut addons = contents.git_addons.clone();
+    addons.sort_by(|a, b| a.git.cmp(&b.git));
+    for repo_url in &manifest.git_repositories {
+        let _repo_lock = git_client.lock_repo(repo_url).await?;
+        for addon in addons.iter().filter(|addon| &addon.git == repo_url) {
+            let git_ref = match &addon.version {
+                Some(version) => {
+                    resolve_version_tag(git_client, repo_url, version)
+                        .await?
+                        .name
+                }
+                None => addon.rev.clone().unwrap_or_default(),
+            };
+            let sparse_dirs = sparse_checkout_dirs(addon.subdir.as_deref(), None);
+            git_client
+                .checkout(repo_url, &git_ref, &sparse_dirs)
+                .await?;
+        }
+
+        let repo_dir = get_repo_dir(config, repo_url);
+        let name = repo_dir.strip_prefix(&config.data_dir)?;
+        tracing::info!("Adding git repository {} to bundle", repo_url);
+        builder
+            .append_dir_all(name, &repo_dir)
+            .context(format!("Failed to add git repository {repo_url} to bundle"))?;
+    }
+
+    builder
+        .into_inner()
+        .context("Failed to finish writing bundle")?;
+    Ok(manifest)
+}
+
+/// Imports a bundle created by [create_bundle] into the data directory.
+/// Cached downloads and git repositories that already exist are kept as they are.
+pub async fn import_bundle(config: &Config, bundle_path: &Path) -> Result<BundleManifest> {
+    let mut archive = tar::Archive::new(
+        File::open(bundle_path)
+            .context(format!("Failed to open bundle: {}", bundle_path.display()))?,
+    );
+    archive.set_preserve_permissions(true);
+    let mut entries = archive.entries()?;
+
+    let manifest: BundleManifest = match entries.next() {
+        Some(entry) => {
+            let mut entry = entry?;
+            if entry.path()? != Path::new(BUNDLE_MANIFEST_FILE) {
+                bail!("{} is not a gdenv bundle", bundle_path.display());
+            }
+            read_json(&mut entry).context("Failed to read bundle manifest")?
+        }
+        None => bail!("{} is not a gdenv bundle", bundle_path.display()),
+    };
+    for path in &manifest.files {
+        check_bundle_path(path)?;
+    }
+
+    // Lock the repositories, so no other gdenv process uses them while they're unpacked.
+    let mut repo_locks = Vec::new();
+    let mut bundled_repo_dirs = Vec::new();
+    let mut new_repo_dirs = Vec::new();
+    for repo_url in &manifest.git_repositories {
+        repo_locks.push(lock_repo_dir(config, repo_url).await?);
+        let repo_dir = get_repo_dir(config, repo_url);
+        let name = repo_dir.strip_prefix(&config.data_dir)?.to_path_buf();
+        if repo_dir.exists() {
+            tracing::info!(
+                "Git repository {} is already cached, keeping the existing copy",
+                repo_url
+            );
+        } else {
+            new_repo_dirs.push(name.clone());
+        }
+        bundled_repo_dirs.push(name);
+    }
+
+    let mut releases = None;
+    for entry in entries {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+
+        if path == Path::new(BUNDLE_RELEASES_FILE) {
+            releases = Some(read_json::<Vec<GitHubRelease>, _>(&mut entry)?);
+        } else if manifest.files.contains(&path) {
+            if config.data_dir.join(&path).exists() {
+                tracing::info!("{} is already cached, skipping", path.display());
+                continue;
+            }
+            tracing::info!("Importing {}", path.display());
+            unpack_atomically(config, &mut entry, &path)?;
+        } else if new_repo_dirs.iter().any(|dir| path.starts_with(dir)) {
+            entry
+                .unpack_in(&config.data_dir)
+                .context(format!("Failed to unpack {}", path.display()))?;
+        } else if !bundled_repo_dirs.iter().any(|dir| path.starts_with(dir)) {
+            tracing::warn!("Ignoring unexpected bundle entry {}", path.display());
+        }
+    }
+    drop(repo_locks);
+
+    if let Some(releases) = releases {
+        GitHubClient::new(config.clone())
+            .import_releases(releases)
+            .await
+            .context("Failed to import Godot releases")?;
+    }
+
+    Ok(manifest)
+}
+
+/// Makes sure a path from a bundle manifest stays inside the data directory.
+fn check_bundle_path(path: &Path) -> Result<()> {
+    if path.as_os_str().is_empty()
+        || !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        bail!("Invalid bundle entry: {}", path.display());
+    }
+    Ok(())
+}
+
+/// Unpacks a cached download through a temporary file, so that an interrupted import
+/// doesn't leave a partial archive in the cache.
+fn unpack_atomically<R: Read>(
+    config: &Config,
+    entry: &mut tar::Entry<R>,
+    path: &Path,
+) -> Result<()> {
+    let target = config.data_dir.join(path);
+    let file_name = target
+        .file_name()
+        .context(format!("Invalid bundle entry: {}", path.display()))?
+        .to_string_lossy()
+        .to_string();
+    let partial = target.with_file_name(format!("{file_name}.part-{}", std::process::id()));
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let result = entry
+        .unpack(&partial)
+        .map(|_| ())
+        .and_then(|()| fs::rename(&partial, &target));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(e).context(format!("Failed to unpack {}", path.display()));
+    }
+    Ok(())
+}
+
+fn append_json<W: std::io::Write, T: Serialize>(
+    builder: &mut tar::Builder<W>,
+    name: &str,
+    value: &T,
+) -> Result<()> {
+    let content = serde_json::to_vec_pretty(value)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, name, content.as_slice())
+        .context(format!("Failed to add {name} to bundle"))
+}
+
+fn read_json<T: DeserializeOwned, R: Read>(entry: &mut tar::Entry<R>) -> Result<T> {
+    let mut content = String::new();
+    entry.read_to_string(&mut content)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::mock_download_client::MockDownloadClient;
+    use crate::test_helpers::mock_git_client::MockGitClient;
+
+    #[tokio::test]
+    async fn test_bundle_roundtrip() -> Result<()> {
+        let source_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let target_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let bundle_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let bundle_path = bundle_dir.path().join("bundle.tar");
+
+        let source_config = Config::setup(Some(source_data_dir.path()))?;
+        let git_client = MockGitClient::new(source_config.clone());
+        let repo_url = "https://example.com/test-addon1-repo".to_string();
+        let contents = BundleContents {
+            godot_versions: vec![GodotVersion::new("4.2.1-stable", false)?],
+            os: "windows".to_string(),
+            arch: "x86_64".to_string(),
+            export_templates: true,
+            git_addons: vec![GitAddonSource {
+                git: repo_url.clone(),
+                rev: Some("main".to_string()),
+                version: None,
+                subdir: None,
+            }],
+        };
+        let manifest = create_bundle(
+            &source_config,
+            &MockDownloadClient,
+            &git_client,
+            &contents,
+            &bundle_path,
+        )
+        .await?;
+        assert_eq!(
+            manifest.files,
+            vec![
+                PathBuf::from("cache/Godot_v4.2.1-stable_export_templates.tpz"),
+                PathBuf::from("cache/Godot_v4.2.1-stable_win64.exe.zip"),
+            ]
+        );
+
+        let target_config = Config::setup(Some(target_data_dir.path()))?;
+        let imported = import_bundle(&target_config, &bundle_path).await?;
+        assert_eq!(imported, manifest);
+        for file in &manifest.files {
+            assert!(target_config.data_dir.join(file).exists());
+        }
+        assert!(
+            get_repo_dir(&target_config, &repo_url)
+                .join("addons/test-addon1/plugin.cfg")
+                .exists()
+        );
+
+        // The releases must be usable without network access.
+        let releases = GitHubClient::new(target_config)
+            .godot_releases(false, false)
+            .await?;
+        assert_eq!(releases.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_bundle_rejects_path_traversal() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let data_dir = tmp_dir.path().join("data");
+        let bundle_path = tmp_dir.path().join("bundle.tar");
+        let escaped_path = PathBuf::from("../escaped.txt");
+
+        let manifest = BundleManifest {
+            gdenv_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            godot_versions: vec![],
+            export_templates: false,
+            git_repositories: vec![],
+            files: vec![escaped_path.clone()],
+        };
+        let mut builder = tar::Builder::new(File::create(&bundle_path)?);
+        append_json(&mut builder, BUNDLE_MANIFEST_FILE, &manifest)?;
+        // tar::Builder refuses to write `..` paths, so set the name by hand.
+        let content = b"escaped";
+        let mut header = tar::Header::new_gnu();
+        let name = escaped_path.to_str().unwrap().as_bytes();
+        header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name);
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, content.as_slice())?;
+        builder.into_inner()?;
+
+        let config = Config::setup(Some(&data_dir))?;
+        let result = import_bundle(&config, &bundle_path).await;
+        assert!(result.is_err());
+        assert!(!tmp_dir.path().join("escaped.txt").exists());
+        Ok(())
+    }
+}
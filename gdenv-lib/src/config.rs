@@ -2,6 +2,8 @@ use crate::migrate::migrate;
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
 
+pub const DEFAULT_ASSET_LIBRARY_URL: &str = "https://godotengine.org/asset-library/api";
+
 #[derive(Debug, Clone)]
 pub struct Config {
     /// Root directory for gdenv data (installations, cache, symlinks, etc.)
@@ -16,6 +18,15 @@ pub struct Config {
     /// Directory for git repository cache
     pub git_cache_dir: PathBuf,
 
+    /// Cache of the content hashes of synced addon files
+    pub file_hashes_file: PathBuf,
+
+    /// Directory for the snapshots of files that syncing addons overwrote or removed
+    pub sync_snapshots_dir: PathBuf,
+
+    /// Directory for lock files that coordinate concurrent gdenv processes
+    pub locks_dir: PathBuf,
+
     /// Path to the active Godot symlink
     pub active_symlink: PathBuf,
 
@@ -30,6 +41,43 @@ pub struct Config {
 
     /// Platform-specific architecture string.
     pub arch: String,
+
+    /// Base URL of the Godot Asset Library REST API.
+    /// Can be overridden with the `GDENV_ASSET_LIBRARY_URL` environment variable.
+    pub asset_library_url: String,
+
+    /// Which git client to use for git addons.
+    /// Can be set with the `GDENV_GIT_BACKEND` environment variable.
+    pub git_backend: GitBackend,
+}
+
+/// Which git client gdenv uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GitBackend {
+    /// The `git` command if it's installed, otherwise the built-in client (`auto`).
+    #[default]
+    Auto,
+    /// The `git` command, which supports Git LFS and fetches less of large repositories (`system`).
+    System,
+    /// The built-in client, which works without git being installed (`builtin`).
+    Builtin,
+}
+
+impl GitBackend {
+    fn from_env() -> Self {
+        match std::env::var("GDENV_GIT_BACKEND").as_deref() {
+            Err(_) | Ok("" | "auto") => Self::Auto,
+            Ok("system" | "git") => Self::System,
+            Ok("builtin") => Self::Builtin,
+            Ok(other) => {
+                tracing::warn!(
+                    "Unknown GDENV_GIT_BACKEND \"{}\", expected auto, system or builtin. Using auto.",
+                    other
+                );
+                Self::Auto
+            }
+        }
+    }
 }
 
 impl Default for Config {
@@ -45,11 +93,17 @@ impl Config {
             installations_dir: data_dir.join("installations"),
             cache_dir: data_dir.join("cache"),
             git_cache_dir: data_dir.join("cache/git_cache"),
+            file_hashes_file: data_dir.join("cache/sync/file_hashes.json"),
+            sync_snapshots_dir: data_dir.join("sync_snapshots"),
+            locks_dir: data_dir.join("locks"),
             active_symlink: data_dir.join("current"),
             bin_dir: data_dir.join("bin"),
             data_dir_format_version_file: data_dir.join("gdenv_version.txt"),
             os: std::env::consts::OS.to_string(),
             arch: std::env::consts::ARCH.to_string(),
+            asset_library_url: std::env::var("GDENV_ASSET_LIBRARY_URL")
+                .unwrap_or_else(|_| DEFAULT_ASSET_LIBRARY_URL.to_string()),
+            git_backend: GitBackend::from_env(),
         }
     }
 
@@ -67,6 +121,7 @@ impl Config {
         std::fs::create_dir_all(&config.installations_dir)?;
         std::fs::create_dir_all(&config.cache_dir)?;
         std::fs::create_dir_all(&config.git_cache_dir)?;
+        std::fs::create_dir_all(&config.locks_dir)?;
         std::fs::create_dir_all(&config.bin_dir)?;
 
         Ok(config)
@@ -0,0 +1,376 @@
+//! Reads and edits files in Godot's ConfigFile format, such as `project.godot` and
+//! `plugin.cfg`, keeping the formatting and comments of everything that isn't edited.
+
+use anyhow::{Context, Result, bail};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigFile {
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Entry {
+    /// Comments, blank lines and keys before the first section, kept as they are.
+    Other(String),
+    Section {
+        name: String,
+        text: String,
+    },
+    Value {
+        section: String,
+        key: String,
+        /// The value as written in the file, e.g. `"text"` or `PackedStringArray("a", "b")`.
+        value: String,
+        text: String,
+    },
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).context(format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_string()).context(format!("Failed to write {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        let mut section = String::new();
+        let mut rest = text;
+        let mut line_number = 1;
+        while !rest.is_empty() {
+            let line_end = rest.find('\n').map_or(rest.len(), |i| i + 1);
+            let line = &rest[..line_end];
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with(';') || trimmed.starts_with('#') {
+                entries.push(Entry::Other(line.to_string()));
+                rest = &rest[line_end..];
+            } else if let Some(name) = trimmed.strip_prefix('[') {
+                let Some(name) = name.strip_suffix(']') else {
+                    bail!("Invalid section header on line {line_number}: {trimmed}");
+                };
+                section = name.to_string();
+                entries.push(Entry::Section {
+                    name: section.clone(),
+                    text: line.to_string(),
+                });
+                rest = &rest[line_end..];
+            } else {
+                let Some(equals) = line.find('=') else {
+                    bail!("Expected `key=value` on line {line_number}: {trimmed}");
+                };
+                // Values such as dictionaries can span several lines
+                let value_end = equals + 1 + value_len(&rest[equals + 1..]);
+                let text_end = rest[value_end..]
+                    .find('\n')
+                    .map_or(rest.len(), |i| value_end + i + 1);
+                entries.push(Entry::Value {
+                    section: section.clone(),
+                    key: line[..equals].trim().to_string(),
+                    value: rest[equals + 1..value_end].trim().to_string(),
+                    text: rest[..text_end].to_string(),
+                });
+                line_number += rest[..text_end].matches('\n').count();
+                rest = &rest[text_end..];
+                continue;
+            }
+            line_number += 1;
+        }
+        Ok(Self { entries })
+    }
+
+    /// The value of `key` as written in the file.
+    pub fn get_value(&self, section: &str, key: &str) -> Option<&str> {
+        self.entries.iter().find_map(|entry| match entry {
+            Entry::Value {
+                section: s,
+                key: k,
+                value,
+                ..
+            } if s == section && k == key => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Sets `key` to `value`, which must already be formatted the way Godot writes it. New keys
+    /// are added at the end of their section, and new sections in alphabetical order, like
+    /// Godot does for `project.godot`.
+    pub fn set_value(&mut self, section: &str, key: &str, value: &str) {
+        let text = format!("{key}={value}\n");
+        if let Some(Entry::Value {
+            value: old_value,
+            text: old_text,
+            ..
+        }) = self.entries.iter_mut().find(|entry| {
+            matches!(entry, Entry::Value { section: s, key: k, .. } if s == section && k == key)
+        }) {
+            *old_value = value.to_string();
+            *old_text = text;
+            return;
+        }
+
+        let new_value = Entry::Value {
+            section: section.to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+            text,
+        };
+        let last_in_section = self.entries.iter().rposition(|entry| match entry {
+            Entry::Section { name, .. } => name == section,
+            Entry::Value { section: s, .. } => s == section,
+            Entry::Other(_) => false,
+        });
+        if let Some(position) = last_in_section {
+            if matches!(self.entries[position], Entry::Section { .. }) {
+                self.entries
+                    .insert(position + 1, Entry::Other("\n".to_string()));
+                self.entries.insert(position + 2, new_value);
+            } else {
+                self.entries.insert(position + 1, new_value);
+            }
+            return;
+        }
+
+        let next_section = self.entries.iter().position(
+            |entry| matches!(entry, Entry::Section { name, .. } if name.as_str() > section),
+        );
+        let new_section = [
+            Entry::Section {
+                name: section.to_string(),
+                text: format!("[{section}]\n"),
+            },
+            Entry::Other("\n".to_string()),
+            new_value,
+            Entry::Other("\n".to_string()),
+        ];
+        match next_section {
+            Some(position) => {
+                self.entries.splice(position..position, new_section);
+            }
+            None => {
+                if let Some(Entry::Other(text) | Entry::Value { text, .. }) =
+                    self.entries.last_mut()
+                    && !text.ends_with('\n')
+                {
+                    text.push('\n');
+                }
+                if self.entries.last().is_some_and(
+                    |entry| !matches!(entry, Entry::Other(text) if text.trim().is_empty()),
+                ) {
+                    self.entries.push(Entry::Other("\n".to_string()));
+                }
+                self.entries.extend(new_section.into_iter().take(3));
+            }
+        }
+    }
+
+    /// Removes `key`, keeping its section even if it's left empty.
+    pub fn erase_key(&mut self, section: &str, key: &str) {
+        self.entries.retain(|entry| {
+            !matches!(entry, Entry::Value { section: s, key: k, .. } if s == section && k == key)
+        });
+    }
+}
+
+impl fmt::Display for ConfigFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            match entry {
+                Entry::Other(text) | Entry::Section { text, .. } | Entry::Value { text, .. } => {
+                    f.write_str(text)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Length of the value at the start of `text`, up to the end of the line on which its
+/// strings and brackets are closed.
+fn value_len(text: &str) -> usize {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            '\n' if depth == 0 => return i,
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+/// Parses a `PackedStringArray(...)` value (`PoolStringArray(...)` in Godot 3).
+pub fn parse_string_array(value: &str) -> Result<Vec<String>> {
+    let Some(items) = value
+        .strip_prefix("PackedStringArray(")
+        .or_else(|| value.strip_prefix("PoolStringArray("))
+        .and_then(|items| items.strip_suffix(')'))
+    else {
+        bail!("Expected a PackedStringArray, found {value}");
+    };
+
+    let mut strings = Vec::new();
+    let mut chars = items.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => string.push('\n'),
+                            Some('t') => string.push('\t'),
+                            Some(c) => string.push(c),
+                            None => bail!("Unterminated string in {value}"),
+                        },
+                        Some(c) => string.push(c),
+                        None => bail!("Unterminated string in {value}"),
+                    }
+                }
+                strings.push(string);
+            }
+            ',' => {}
+            c if c.is_whitespace() => {}
+            c => bail!("Unexpected {c:?} in {value}"),
+        }
+    }
+    Ok(strings)
+}
+
+/// Formats strings as a `PackedStringArray(...)` value, the way Godot 4 writes it.
+pub fn format_string_array(strings: &[String]) -> String {
+    let items: Vec<String> = strings
+        .iter()
+        .map(|string| {
+            let escaped = string
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\t', "\\t");
+            format!("\"{escaped}\"")
+        })
+        .collect();
+    format!("PackedStringArray({})", items.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT_GODOT: &str = r#"; Engine configuration file.
+; It's best edited using the editor UI and not directly,
+; since the parameters that go here are not all obvious.
+
+config_version=5
+
+[application]
+
+config/name="My \"Game\""
+config/features=PackedStringArray("4.6", "Forward Plus")
+
+[input]
+
+jump={
+"deadzone": 0.2,
+"events": [Object(InputEventKey,"resource_local_to_scene":false,"keycode":32,"unicode":32)
+]
+}
+
+[rendering]
+
+renderer/rendering_method="mobile"
+"#;
+
+    #[test]
+    fn test_parse_and_write_unchanged() -> Result<()> {
+        let config = ConfigFile::parse(PROJECT_GODOT)?;
+        assert_eq!(config.to_string(), PROJECT_GODOT);
+        assert_eq!(config.get_value("", "config_version"), Some("5"));
+        assert_eq!(
+            config.get_value("application", "config/name"),
+            Some(r#""My \"Game\"""#)
+        );
+        assert!(config.get_value("input", "jump").unwrap().ends_with("]\n}"));
+        assert_eq!(
+            config.get_value("rendering", "renderer/rendering_method"),
+            Some("\"mobile\"")
+        );
+        assert_eq!(config.get_value("editor_plugins", "enabled"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_value() -> Result<()> {
+        let mut config = ConfigFile::parse(PROJECT_GODOT)?;
+        config.set_value("application", "config/name", "\"Other\"");
+        config.set_value("application", "run/main_scene", "\"res://main.tscn\"");
+        config.set_value(
+            "editor_plugins",
+            "enabled",
+            &format_string_array(&["res://addons/foo/plugin.cfg".to_string()]),
+        );
+        config.set_value("xr", "openxr/enabled", "true");
+
+        let expected = PROJECT_GODOT
+            .replace(
+                "config/name=\"My \\\"Game\\\"\"\n",
+                "config/name=\"Other\"\n",
+            )
+            .replace(
+                "\"Forward Plus\")\n",
+                "\"Forward Plus\")\nrun/main_scene=\"res://main.tscn\"\n",
+            )
+            .replace(
+                "[input]",
+                "[editor_plugins]\n\nenabled=PackedStringArray(\"res://addons/foo/plugin.cfg\")\n\n[input]",
+            )
+            + "\n[xr]\n\nopenxr/enabled=true\n";
+        assert_eq!(config.to_string(), expected);
+        assert_eq!(ConfigFile::parse(&expected)?, config);
+
+        config.erase_key("editor_plugins", "enabled");
+        assert_eq!(config.get_value("editor_plugins", "enabled"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_arrays() -> Result<()> {
+        let strings = vec![
+            "res://addons/foo/plugin.cfg".to_string(),
+            "quote \" and backslash \\".to_string(),
+        ];
+        let value = format_string_array(&strings);
+        assert_eq!(
+            value,
+            r#"PackedStringArray("res://addons/foo/plugin.cfg", "quote \" and backslash \\")"#
+        );
+        assert_eq!(parse_string_array(&value)?, strings);
+        assert_eq!(
+            parse_string_array(r#"PoolStringArray( "a", "b" )"#)?,
+            vec!["a".to_string(), "b".to_string()]
+        );
+        assert!(parse_string_array("PackedStringArray()")?.is_empty());
+        assert!(parse_string_array("[\"a\"]").is_err());
+        Ok(())
+    }
+}
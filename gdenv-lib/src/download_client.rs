@@ -1,6 +1,7 @@
 use crate::github::{GitHubAsset, GitHubRelease};
-use anyhow::Result;
+use anyhow::{Result, bail};
 use std::path::Path;
+use tokio::io::AsyncWriteExt;
 
 pub trait DownloadClient {
     fn godot_releases(
@@ -15,3 +16,21 @@ pub trait DownloadClient {
         output_path: &Path,
     ) -> impl Future<Output = Result<()>> + Send;
 }
+
+/// Downloads `url` into the file at `path`.
+pub(crate) async fn download_url_to_file(
+    client: &reqwest::Client,
+    url: &str,
+    path: &Path,
+) -> Result<()> {
+    let mut response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        bail!("Download failed: {}", response.status());
+    }
+    let mut file = tokio::fs::File::create(path).await?;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(())
+}
@@ -0,0 +1,266 @@
+//! Enables the editor plugins of synced addons in `project.godot`, so nobody has to tick
+//! them in Project Settings → Plugins after every sync.
+
+use crate::addons::{addon_destination, sorted_addons};
+use crate::config_file::{ConfigFile, format_string_array, parse_string_array};
+use crate::project_specification::ProjectSpecification;
+use anyhow::{Context, Result};
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+const SECTION: &str = "editor_plugins";
+const KEY: &str = "enabled";
+
+/// Plugins added to and removed from `project.godot`'s enabled plugins, as `res://` paths
+/// of their `plugin.cfg` files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EditorPluginChanges {
+    pub enabled: Vec<String>,
+    pub disabled: Vec<String>,
+}
+
+impl EditorPluginChanges {
+    pub fn is_empty(&self) -> bool {
+        self.enabled.is_empty() && self.disabled.is_empty()
+    }
+}
+
+/// Enables the plugins found in the destinations of the project's addons, unless the addon
+/// sets `enable_plugin = false`, in which case they're disabled. Plugins that no longer exist
+/// in an addon's destination or in `removed_destinations` (relative to the project root
+/// directory, like the destinations of orphaned addons) are disabled too. Other plugins are
+/// left alone.
+///
+/// Does nothing if the Godot project has no `project.godot` file yet.
+pub fn update_editor_plugins(
+    project_spec: &ProjectSpecification,
+    removed_destinations: &[PathBuf],
+) -> Result<EditorPluginChanges> {
+    let project_dir = project_spec
+        .project_root_dir
+        .join(&project_spec.godot_project_dir);
+    let project_file = project_dir.join("project.godot");
+    if !project_file.is_file() {
+        tracing::debug!(
+            "{} doesn't exist, not enabling editor plugins",
+            project_file.display()
+        );
+        return Ok(EditorPluginChanges::default());
+    }
+    let mut config = ConfigFile::load(&project_file)?;
+    let current = match config.get_value(SECTION, KEY) {
+        Some(value) => parse_string_array(value).context(format!(
+            "Failed to read the enabled editor plugins of {}",
+            project_file.display()
+        ))?,
+        None => Vec::new(),
+    };
+
+    let mut managed_dirs: Vec<String> = Vec::new();
+    let mut wanted: Vec<String> = Vec::new();
+    let mut unwanted: Vec<String> = Vec::new();
+    for (addon_name, addon_spec) in sorted_addons(project_spec) {
+        let destination = addon_destination(project_spec, addon_name, addon_spec);
+        let Some(res_dir) = res_path(project_spec, &destination) else {
+            tracing::debug!(
+                "Addon {} is outside the Godot project, not enabling its plugins",
+                addon_name
+            );
+            continue;
+        };
+        let dest_dir = project_spec.project_root_dir.join(&destination);
+        let plugins = find_plugins(&dest_dir)
+            .into_iter()
+            .map(|plugin| format!("{res_dir}/{}", slash_path(&plugin)));
+        if addon_spec.enable_plugin == Some(false) {
+            unwanted.extend(plugins);
+        } else {
+            wanted.extend(plugins);
+        }
+        managed_dirs.push(res_dir);
+    }
+    managed_dirs.extend(
+        removed_destinations
+            .iter()
+            .filter_map(|destination| res_path(project_spec, destination)),
+    );
+
+    // Plugins that disappeared from an addon's destination, or whose addon was removed
+    let is_stale = |plugin: &str| {
+        managed_dirs
+            .iter()
+            .any(|dir| plugin.starts_with(&format!("{dir}/")))
+            && !plugin
+                .strip_prefix("res://")
+                .is_some_and(|path| project_dir.join(path).is_file())
+    };
+    let mut changes = EditorPluginChanges::default();
+    let mut plugins = Vec::new();
+    for plugin in current {
+        if !wanted.contains(&plugin) && (unwanted.contains(&plugin) || is_stale(&plugin)) {
+            changes.disabled.push(plugin);
+        } else {
+            plugins.push(plugin);
+        }
+    }
+    for plugin in wanted {
+        if !plugins.contains(&plugin) {
+            changes.enabled.push(plugin.clone());
+            plugins.push(plugin);
+        }
+    }
+
+    if !changes.is_empty() {
+        config.set_value(SECTION, KEY, &format_string_array(&plugins));
+        config.save(&project_file)?;
+    }
+    Ok(changes)
+}
+
+/// The `res://` path of `destination` (relative to the project root directory), or `None`
+/// if it's outside the Godot project.
+fn res_path(project_spec: &ProjectSpecification, destination: &Path) -> Option<String> {
+    let godot_project_dir: PathBuf = project_spec
+        .godot_project_dir
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect();
+    let relative = destination.strip_prefix(godot_project_dir).ok()?;
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+    Some(
+        format!("res://{}", slash_path(relative))
+            .trim_end_matches('/')
+            .to_string(),
+    )
+}
+
+/// `plugin.cfg` files under `dir`, relative to it.
+pub(crate) fn find_plugins(dir: &Path) -> Vec<PathBuf> {
+    let mut plugins: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+        })
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && entry.file_name() == "plugin.cfg")
+        .filter_map(|entry| entry.path().strip_prefix(dir).ok().map(Path::to_path_buf))
+        .collect();
+    plugins.sort();
+    plugins
+}
+
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project_specification::load_godot_project_spec;
+    use anyhow::bail;
+    use std::fs;
+
+    #[test]
+    fn test_update_editor_plugins() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let project_dir = tmp_dir.path().join("godot");
+        fs::write(
+            tmp_dir.path().join("gdenv.toml"),
+            r#"
+[godot]
+version = "4.6.0-stable"
+project_dir = "./godot"
+
+[addon.dialogue]
+path = "../sources/dialogue"
+
+[addon.tools]
+path = "../sources/tools"
+
+[addon.theme]
+path = "../sources/theme"
+enable_plugin = false
+
+[addon.outside]
+path = "../sources/outside"
+destination = "../vendor/outside"
+            "#,
+        )?;
+        for plugin in [
+            "addons/dialogue/plugin.cfg",
+            "addons/tools/debug/plugin.cfg",
+            "addons/tools/profiler/plugin.cfg",
+            "addons/theme/plugin.cfg",
+            "../vendor/outside/plugin.cfg",
+        ] {
+            let path = project_dir.join(plugin);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, "[plugin]\n")?;
+        }
+        let project_godot = r#"config_version=5
+
+[application]
+
+config/name="Test"
+
+[editor_plugins]
+
+enabled=PackedStringArray("res://addons/custom/plugin.cfg", "res://addons/theme/plugin.cfg", "res://addons/removed/plugin.cfg")
+"#;
+        fs::write(project_dir.join("project.godot"), project_godot)?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+
+        let changes =
+            update_editor_plugins(&project_spec, &[PathBuf::from("godot/addons/removed")])?;
+        assert_eq!(
+            changes,
+            EditorPluginChanges {
+                enabled: vec![
+                    "res://addons/dialogue/plugin.cfg".to_string(),
+                    "res://addons/tools/debug/plugin.cfg".to_string(),
+                    "res://addons/tools/profiler/plugin.cfg".to_string(),
+                ],
+                disabled: vec![
+                    "res://addons/theme/plugin.cfg".to_string(),
+                    "res://addons/removed/plugin.cfg".to_string(),
+                ],
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(project_dir.join("project.godot"))?,
+            project_godot.replace(
+                r#"enabled=PackedStringArray("res://addons/custom/plugin.cfg", "res://addons/theme/plugin.cfg", "res://addons/removed/plugin.cfg")"#,
+                r#"enabled=PackedStringArray("res://addons/custom/plugin.cfg", "res://addons/dialogue/plugin.cfg", "res://addons/tools/debug/plugin.cfg", "res://addons/tools/profiler/plugin.cfg")"#
+            )
+        );
+
+        assert!(update_editor_plugins(&project_spec, &[])?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_editor_plugins_without_project_file() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        fs::write(
+            tmp_dir.path().join("gdenv.toml"),
+            "[godot]\nversion = \"4.6.0-stable\"\n\n[addon.dialogue]\npath = \"../dialogue\"\n",
+        )?;
+        fs::create_dir_all(tmp_dir.path().join("addons/dialogue"))?;
+        fs::write(tmp_dir.path().join("addons/dialogue/plugin.cfg"), "")?;
+        let project_spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+
+        assert!(update_editor_plugins(&project_spec, &[])?.is_empty());
+        assert!(!tmp_dir.path().join("project.godot").exists());
+        Ok(())
+    }
+}
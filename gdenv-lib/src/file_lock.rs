@@ -0,0 +1,100 @@
+//! Advisory file locks that keep concurrent gdenv processes from modifying
+//! the same part of the data directory at the same time.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+
+/// An exclusive advisory lock on a file. The lock is released when this value is dropped.
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Acquires an exclusive lock on the file at `path`, creating the file if necessary.
+    /// If another process holds the lock, a message mentioning `activity` is printed
+    /// and this waits until the lock is released.
+    pub async fn acquire(path: &Path, activity: &str) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(path)
+            .context(format!("Failed to open lock file: {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => {
+                return Ok(Self {
+                    file,
+                    path: path.to_path_buf(),
+                });
+            }
+            Err(TryLockError::WouldBlock) => {}
+            Err(TryLockError::Error(e)) => {
+                return Err(e).context(format!("Failed to lock file: {}", path.display()));
+            }
+        }
+
+        tracing::info!(
+            "Waiting for another gdenv process to finish {}... (lock file: {})",
+            activity,
+            path.display()
+        );
+        let file = tokio::task::spawn_blocking(move || file.lock().map(|_| file))
+            .await?
+            .context(format!("Failed to lock file: {}", path.display()))?;
+
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        if let Err(e) = self.file.unlock() {
+            tracing::warn!("Failed to release lock {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_lock_waits_for_release() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let lock_path = tmp_dir.path().join("locks/test.lock");
+
+        let first_lock = FileLock::acquire(&lock_path, "testing").await?;
+        assert!(lock_path.exists());
+
+        let waiting_path = lock_path.clone();
+        let second_lock =
+            tokio::spawn(async move { FileLock::acquire(&waiting_path, "testing").await });
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(
+            !second_lock.is_finished(),
+            "Lock must not be acquired while another holder exists"
+        );
+
+        drop(first_lock);
+        let second_lock = tokio::time::timeout(Duration::from_secs(5), second_lock).await???;
+        assert_eq!(second_lock.path(), lock_path);
+        Ok(())
+    }
+}
@@ -1,73 +1,489 @@
 use anyhow::Context;
-use std::fs;
-use std::hash::{DefaultHasher, Hash, Hasher};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, Metadata, Permissions};
+use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use walkdir::WalkDir;
 
+/// A file, directory or symlink, with the metadata that tells whether it needs to be synced.
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct FileEntry {
     rel_path: PathBuf,
-    hash: u64,
     is_dir: bool,
+    /// Where a symlink points, as stored in the link. Symlinks are synced as symlinks rather
+    /// than followed.
+    link_target: Option<PathBuf>,
+    /// Size of a file in bytes, 0 for directories and symlinks.
+    size: u64,
+    modified: Option<SystemTime>,
+    /// Permissions of a file, `None` for directories and symlinks.
+    permissions: Option<Permissions>,
+}
+
+impl FileEntry {
+    fn new(rel_path: PathBuf, metadata: &Metadata, link_target: Option<PathBuf>) -> Self {
+        let is_file = metadata.is_file();
+        Self {
+            rel_path,
+            is_dir: metadata.is_dir(),
+            link_target,
+            size: if is_file { metadata.len() } else { 0 },
+            modified: metadata.modified().ok().filter(|_| is_file),
+            permissions: is_file.then(|| metadata.permissions()),
+        }
+    }
+
+    /// Whether the entries are the same without looking at the contents of files. Files
+    /// with the same size, permissions and modification time are assumed to be the same,
+    /// like rsync does. Returns `None` if the contents need to be compared.
+    fn same_metadata(&self, other: &Self) -> Option<bool> {
+        if self.is_dir || other.is_dir {
+            return Some(self.is_dir == other.is_dir);
+        }
+        if self.link_target.is_some() || other.link_target.is_some() {
+            return Some(self.link_target == other.link_target);
+        }
+        if self.size != other.size || self.permissions != other.permissions {
+            return Some(false);
+        }
+        if self.modified.is_some() && self.modified == other.modified {
+            return Some(true);
+        }
+        None
+    }
+}
+
+/// The coarsest resolution of modification times, which is that of FAT file systems.
+const MODIFIED_RESOLUTION: Duration = Duration::from_secs(2);
+
+/// SHA-256 hashes of files, which are reused as long as the size and modification time of
+/// a file stay the same. Saved between runs, so syncing a large addon again only reads the
+/// files that changed.
+#[derive(Debug, Default)]
+pub struct HashCache {
+    /// Where the cache is saved. An in-memory cache isn't saved.
+    path: Option<PathBuf>,
+    entries: Mutex<HashCacheEntries>,
+}
+
+#[derive(Debug, Default)]
+struct HashCacheEntries {
+    by_path: BTreeMap<PathBuf, CachedHash>,
+    changed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CachedHash {
+    size: u64,
+    /// Modification time in nanoseconds since the Unix epoch.
+    modified: u64,
+    sha256: String,
+}
+
+impl HashCache {
+    /// Loads the cache saved at `path`. A missing or unreadable cache is empty.
+    pub fn load(path: &Path) -> Self {
+        let by_path = fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self {
+            path: Some(path.to_path_buf()),
+            entries: Mutex::new(HashCacheEntries {
+                by_path,
+                changed: false,
+            }),
+        }
+    }
+
+    /// Saves the cache if it changed, forgetting the files that no longer exist.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut entries = self.entries.lock().expect("Hash cache lock poisoned");
+        if !entries.changed {
+            return Ok(());
+        }
+        entries.by_path.retain(|file, _| file.exists());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let partial_path = path.with_extension(format!("json.part-{}", std::process::id()));
+        fs::write(&partial_path, serde_json::to_string(&entries.by_path)?)?;
+        fs::rename(&partial_path, path)
+            .context(format!("Failed to write hash cache {}", path.display()))?;
+        entries.changed = false;
+        Ok(())
+    }
+
+    /// Returns the lowercase hex encoded SHA-256 hash of the file at `path`, reading the file
+    /// only if it changed since it was last hashed.
+    pub fn sha256(&self, path: &Path) -> anyhow::Result<String> {
+        let path = std::path::absolute(path)?;
+        let metadata = fs::metadata(&path)?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_nanos() as u64);
+        let cached = |entries: &HashCacheEntries| {
+            let cached = entries.by_path.get(&path)?;
+            (Some(cached.modified) == modified && cached.size == metadata.len())
+                .then(|| cached.sha256.clone())
+        };
+        if let Some(sha256) = cached(&self.entries.lock().expect("Hash cache lock poisoned")) {
+            return Ok(sha256);
+        }
+
+        let sha256 = sha256_file(&path)?;
+        // A file that is changed again within the resolution of its modification time looks
+        // unchanged, so the hashes of recently modified files aren't kept, like git does
+        let settled = metadata.modified().is_ok_and(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .is_ok_and(|age| age >= MODIFIED_RESOLUTION)
+        });
+        let mut entries = self.entries.lock().expect("Hash cache lock poisoned");
+        match modified {
+            Some(modified) if settled => {
+                entries.by_path.insert(
+                    path,
+                    CachedHash {
+                        size: metadata.len(),
+                        modified,
+                        sha256: sha256.clone(),
+                    },
+                );
+            }
+            _ => {
+                entries.by_path.remove(&path);
+            }
+        }
+        entries.changed = true;
+        Ok(sha256)
+    }
+
+    /// Hashes the files at `paths` in parallel, see [`Self::sha256`].
+    pub fn sha256_all(&self, paths: &[PathBuf]) -> anyhow::Result<Vec<String>> {
+        paths
+            .par_iter()
+            .map(|path| {
+                self.sha256(path)
+                    .context(format!("Failed to hash {}", path.display()))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+/// A single file or directory that a sync will add, overwrite or remove in the destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncChange {
+    pub kind: SyncChangeKind,
+    /// Path relative to the source and destination directories.
+    pub rel_path: PathBuf,
+    pub is_dir: bool,
+}
+
+/// The changes needed to make a destination directory match a source directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncPlan {
+    /// Sorted so children are removed before their parents.
+    pub removed: Vec<SyncChange>,
+    /// Sorted so parents are created before their children.
+    pub added: Vec<SyncChange>,
+    pub modified: Vec<SyncChange>,
+}
+
+impl SyncPlan {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty() && self.modified.is_empty()
+    }
+
+    /// All changes, ordered by path.
+    pub fn changes(&self) -> Vec<&SyncChange> {
+        let mut changes: Vec<&SyncChange> = self
+            .added
+            .iter()
+            .chain(&self.modified)
+            .chain(&self.removed)
+            .collect();
+        changes.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+        changes
+    }
+
+    /// Unified diffs of the modified files whose old and new contents are both text.
+    pub fn text_diffs(
+        &self,
+        source_base: &Path,
+        dest_base: &Path,
+    ) -> anyhow::Result<Vec<(PathBuf, String)>> {
+        let mut diffs = Vec::new();
+        for change in &self.modified {
+            let old = fs::read(dest_base.join(&change.rel_path))?;
+            let new = fs::read(source_base.join(&change.rel_path))?;
+            if let Some(diff) = text_diff(Some(&old), Some(&new), &change.rel_path) {
+                diffs.push((change.rel_path.clone(), diff));
+            }
+        }
+        Ok(diffs)
+    }
+}
+
+/// Unified diff between two versions of the file at `rel_path`, where `None` means the file
+/// doesn't exist. Returns `None` if either version isn't text.
+pub fn text_diff(old: Option<&[u8]>, new: Option<&[u8]>, rel_path: &Path) -> Option<String> {
+    let old_text = old.map_or(Some(""), as_text)?;
+    let new_text = new.map_or(Some(""), as_text)?;
+    let name = rel_path.to_string_lossy().replace('\\', "/");
+    let old_header = old.map_or("/dev/null".to_string(), |_| format!("a/{name}"));
+    let new_header = new.map_or("/dev/null".to_string(), |_| format!("b/{name}"));
+    Some(
+        similar::TextDiff::from_lines(old_text, new_text)
+            .unified_diff()
+            .header(&old_header, &new_header)
+            .to_string(),
+    )
+}
+
+fn as_text(contents: &[u8]) -> Option<&str> {
+    if contents.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(contents).ok()
+}
+
+/// Returns the lowercase hex encoded SHA-256 hash of the file at `path`.
+pub fn sha256_file(path: &Path) -> anyhow::Result<String> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// If `rel_path` is a file Godot generates next to an imported asset (`<asset>.import`) or
+/// a resource (`<asset>.uid`, since Godot 4.4), returns the path of that asset.
+pub fn godot_sidecar_asset(rel_path: &Path) -> Option<PathBuf> {
+    let extension = rel_path.extension()?;
+    if extension != "import" && extension != "uid" {
+        return None;
+    }
+    let asset = rel_path.with_extension("");
+    asset.extension()?;
+    Some(asset)
 }
 
 pub fn sync_recursive(
     source_base: &Path,
     dest_base: &Path,
-    includes: Option<&[PathBuf]>,
-    excludes: Option<&[PathBuf]>,
+    includes: Option<&[String]>,
+    excludes: Option<&[String]>,
 ) -> anyhow::Result<()> {
+    let plan = plan_sync(
+        source_base,
+        dest_base,
+        includes,
+        excludes,
+        false,
+        &HashCache::default(),
+    )?;
+    apply_sync_plan(source_base, dest_base, &plan)
+}
+
+/// Compares the source and destination directories without modifying either of them.
+/// With `preserve_godot_files`, Godot sidecar files in the destination (see
+/// [`godot_sidecar_asset`]) are kept as long as their asset is part of the source.
+///
+/// Files are compared by their metadata first (see [`FileEntry::same_metadata`]), and only
+/// by content if that isn't enough, with the hashes in `hashes`.
+pub fn plan_sync(
+    source_base: &Path,
+    dest_base: &Path,
+    includes: Option<&[String]>,
+    excludes: Option<&[String]>,
+    preserve_godot_files: bool,
+    hashes: &HashCache,
+) -> anyhow::Result<SyncPlan> {
     let source_list = get_file_list(source_base).context("Failed to get source file list")?;
     let filtered_source_list = {
-        let mut l = file_list_filtered(source_list, includes, excludes);
+        let mut l = file_list_filtered(source_list, includes, excludes)?;
         // Sort so parents are added before children
         l.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
         l
     };
 
-    let dest_list = get_file_list(dest_base).context("Failed to get destination file list")?;
+    // A destination that is a link is replaced with a directory rather than synced through
+    let dest_list = if is_link(dest_base) {
+        Vec::new()
+    } else {
+        get_file_list(dest_base).context("Failed to get destination file list")?
+    };
     let filtered_dest_list = {
-        let mut l = file_list_filtered(dest_list, None, None);
+        let mut l = file_list_filtered(dest_list, None, None)?;
         // Reverse sort so children are removed before parents
         l.sort_by(|a, b| b.rel_path.cmp(&a.rel_path));
         l
     };
 
-    // For every file in the destination list that isn't in the source list, delete it.
-    // If it is in the source list, but the hash doesn't match, delete it.
+    let source_entries: HashMap<&Path, &FileEntry> = filtered_source_list
+        .iter()
+        .map(|entry| (entry.rel_path.as_path(), entry))
+        .collect();
+    let dest_entries: HashMap<&Path, &FileEntry> = filtered_dest_list
+        .iter()
+        .map(|entry| (entry.rel_path.as_path(), entry))
+        .collect();
+
+    // Files whose metadata doesn't tell whether they changed are compared by hash
+    let unknown: Vec<&Path> = filtered_dest_list
+        .iter()
+        .filter(|dest_entry| {
+            source_entries
+                .get(dest_entry.rel_path.as_path())
+                .is_some_and(|source_entry| source_entry.same_metadata(dest_entry).is_none())
+        })
+        .map(|dest_entry| dest_entry.rel_path.as_path())
+        .collect();
+    let source_hashes = hashes.sha256_all(
+        &unknown
+            .iter()
+            .map(|rel_path| source_base.join(rel_path))
+            .collect::<Vec<_>>(),
+    )?;
+    let dest_hashes = hashes.sha256_all(
+        &unknown
+            .iter()
+            .map(|rel_path| dest_base.join(rel_path))
+            .collect::<Vec<_>>(),
+    )?;
+    let changed: HashSet<&Path> = unknown
+        .into_iter()
+        .zip(source_hashes.iter().zip(&dest_hashes))
+        .filter(|(_, (source_hash, dest_hash))| source_hash != dest_hash)
+        .map(|(rel_path, _)| rel_path)
+        .collect();
+
+    let mut plan = SyncPlan::default();
+
+    // Every entry in the destination that isn't in the source is removed.
+    // A file or symlink that is in both, but differs, is overwritten.
     for dest_entry in &filtered_dest_list {
-        let target_path = dest_base.join(&dest_entry.rel_path);
-        let is_matching = filtered_source_list.iter().any(|s| s == dest_entry);
-
-        if !is_matching {
-            if dest_entry.is_dir {
-                tracing::debug!("Removing directory {:?}", target_path);
-                fs::remove_dir_all(&target_path).context(format!(
-                    "Failed to remove target directory: {:?}",
-                    target_path
-                ))?;
-            } else {
-                tracing::debug!("Removing file {:?}", target_path);
-                fs::remove_file(&target_path)
-                    .context(format!("Failed to remove target file: {:?}", target_path))?;
+        let source_entry = source_entries.get(dest_entry.rel_path.as_path());
+        match source_entry {
+            Some(source_entry)
+                if source_entry
+                    .same_metadata(dest_entry)
+                    .unwrap_or_else(|| !changed.contains(&dest_entry.rel_path.as_path())) => {}
+            None if preserve_godot_files
+                && !dest_entry.is_dir
+                && godot_sidecar_asset(&dest_entry.rel_path).is_some_and(|asset| {
+                    source_entries
+                        .get(asset.as_path())
+                        .is_some_and(|s| !s.is_dir)
+                }) => {}
+            Some(source_entry) if !source_entry.is_dir && !dest_entry.is_dir => {
+                plan.modified.push(SyncChange {
+                    kind: SyncChangeKind::Modified,
+                    rel_path: dest_entry.rel_path.clone(),
+                    is_dir: false,
+                })
             }
+            _ => plan.removed.push(SyncChange {
+                kind: SyncChangeKind::Removed,
+                rel_path: dest_entry.rel_path.clone(),
+                is_dir: dest_entry.is_dir,
+            }),
         }
     }
+    plan.modified.reverse();
 
-    // For every file in the source list that isn't in the destination list, copy it over.
+    // Every entry in the source that isn't in the destination, or is being replaced by an
+    // entry of another type, is added.
     for source_entry in &filtered_source_list {
-        let source_path = source_base.join(&source_entry.rel_path);
-        let target_path = dest_base.join(&source_entry.rel_path);
+        let in_dest = dest_entries
+            .get(source_entry.rel_path.as_path())
+            .is_some_and(|d| d.is_dir == source_entry.is_dir);
+        if !in_dest {
+            plan.added.push(SyncChange {
+                kind: SyncChangeKind::Added,
+                rel_path: source_entry.rel_path.clone(),
+                is_dir: source_entry.is_dir,
+            });
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Applies a plan computed by [`plan_sync`] for the same directories.
+pub fn apply_sync_plan(
+    source_base: &Path,
+    dest_base: &Path,
+    plan: &SyncPlan,
+) -> anyhow::Result<()> {
+    if is_link(dest_base) {
+        tracing::debug!("Replacing link {:?} with a directory", dest_base);
+        remove_link(dest_base)?;
+        fs::create_dir_all(dest_base)?;
+    }
+
+    for change in &plan.removed {
+        let target_path = dest_base.join(&change.rel_path);
+        if change.is_dir {
+            tracing::debug!("Removing directory {:?}", target_path);
+            fs::remove_dir_all(&target_path).context(format!(
+                "Failed to remove target directory: {:?}",
+                target_path
+            ))?;
+        } else {
+            tracing::debug!("Removing file {:?}", target_path);
+            remove_file_or_link(&target_path)
+                .context(format!("Failed to remove target file: {:?}", target_path))?;
+        }
+    }
 
-        if source_entry.is_dir {
+    for change in plan.added.iter().chain(&plan.modified) {
+        let source_path = source_base.join(&change.rel_path);
+        let target_path = dest_base.join(&change.rel_path);
+
+        if change.is_dir {
             tracing::debug!("Creating directory {:?}", target_path);
             fs::create_dir_all(&target_path).context(format!(
                 "Failed to create target directory: {:?}",
                 target_path
             ))?;
+        } else if is_link(&source_path) {
+            tracing::debug!("Copying link {:?} to {:?}", source_path, target_path);
+            copy_link(&source_path, &target_path).with_context(|| {
+                format!("Failed to copy {:?} to {:?}", source_path, target_path)
+            })?;
         } else {
             tracing::debug!("Copying file {:?} to {:?}", source_path, target_path);
-            fs::copy(&source_path, &target_path).with_context(|| {
+            copy_file(&source_path, &target_path).with_context(|| {
                 format!("Failed to copy {:?} to {:?}", source_path, target_path)
             })?;
         }
@@ -76,6 +492,127 @@ pub fn sync_recursive(
     Ok(())
 }
 
+/// Copies a file along with its permissions and modification time, so the next sync can
+/// tell by the metadata alone that the copy is up to date.
+pub(crate) fn copy_file(source: &Path, target: &Path) -> anyhow::Result<()> {
+    // Writing to a link would change the file it points to, and a read-only file from an
+    // earlier sync can't be written to at all
+    if let Ok(metadata) = fs::symlink_metadata(target)
+        && (metadata.is_symlink() || metadata.permissions().readonly())
+    {
+        remove_file_or_link(target)?;
+    }
+    let mut source_file = File::open(source)?;
+    let metadata = source_file.metadata()?;
+    let mut target_file = File::create(target)?;
+    std::io::copy(&mut source_file, &mut target_file)?;
+    if let Ok(modified) = metadata.modified() {
+        target_file.set_modified(modified)?;
+    }
+    target_file.set_permissions(metadata.permissions())?;
+    Ok(())
+}
+
+/// Recreates the symlink at `source` at `target`, pointing to the same path. Relative links
+/// keep pointing into the synced directory. On Windows, where creating symlinks may not be
+/// allowed, a link to a directory falls back to a junction, and a link to a file to a copy
+/// of the file.
+pub(crate) fn copy_link(source: &Path, target: &Path) -> anyhow::Result<()> {
+    if fs::symlink_metadata(target).is_ok() {
+        remove_file_or_link(target)?;
+    }
+    let link_target = fs::read_link(source)?;
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&link_target, target)?;
+
+    #[cfg(windows)]
+    {
+        let points_to_dir = fs::metadata(source).is_ok_and(|metadata| metadata.is_dir());
+        let result = if points_to_dir {
+            std::os::windows::fs::symlink_dir(&link_target, target)
+        } else {
+            std::os::windows::fs::symlink_file(&link_target, target)
+        };
+        if let Err(e) = result {
+            tracing::debug!("Failed to create symlink ({}), copying instead", e);
+            let resolved_target = target.parent().unwrap_or(Path::new("")).join(&link_target);
+            if points_to_dir {
+                create_dir_link(&std::path::absolute(resolved_target)?, target)?;
+            } else {
+                copy_file(source, target)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn remove_file_or_link(path: &Path) -> anyhow::Result<()> {
+    if is_link(path) {
+        return remove_link(path);
+    }
+    // Read-only files can't be removed on Windows
+    #[cfg(windows)]
+    {
+        let mut permissions = fs::metadata(path)?.permissions();
+        if permissions.readonly() {
+            permissions.set_readonly(false);
+            fs::set_permissions(path, permissions)?;
+        }
+    }
+    fs::remove_file(path).context(format!("Failed to remove {:?}", path))
+}
+
+/// Whether `path` is a symlink, or a junction on Windows.
+pub fn is_link(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+/// Removes the link at `path`, leaving the directory it points to untouched.
+pub fn remove_link(path: &Path) -> anyhow::Result<()> {
+    // Directory symlinks and junctions are directories on Windows
+    #[cfg(windows)]
+    let result = fs::remove_dir(path).or_else(|_| fs::remove_file(path));
+    #[cfg(not(windows))]
+    let result = fs::remove_file(path);
+    result.context(format!("Failed to remove link: {:?}", path))
+}
+
+/// Creates a link at `link` to the directory `target`. On Windows, creating symlinks requires
+/// developer mode or administrator rights, so this falls back to a directory junction.
+pub fn create_dir_link(target: &Path, link: &Path) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, link)
+        .context(format!("Failed to link {:?} to {:?}", link, target))?;
+
+    #[cfg(windows)]
+    if let Err(e) = std::os::windows::fs::symlink_dir(target, link) {
+        tracing::debug!(
+            "Failed to create symlink ({}), creating a junction instead",
+            e
+        );
+        let output = std::process::Command::new("cmd")
+            .arg("/C")
+            .arg("mklink")
+            .arg("/J")
+            .arg(link)
+            .arg(target)
+            .output()
+            .context("Failed to run mklink")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to create a junction from {:?} to {:?}: {}",
+                link,
+                target,
+                String::from_utf8_lossy(&output.stdout).trim()
+            );
+        }
+    }
+
+    Ok(())
+}
+
 fn get_file_list(base: &Path) -> anyhow::Result<Vec<FileEntry>> {
     let mut entries = Vec::new();
     if !base.exists() {
@@ -89,47 +626,94 @@ fn get_file_list(base: &Path) -> anyhow::Result<Vec<FileEntry>> {
             continue;
         }
 
-        let is_dir = path.is_dir();
-        let hash = if is_dir {
-            0
+        // Doesn't follow symlinks, so this is the metadata of the link itself
+        let metadata = entry
+            .metadata()
+            .context(format!("Failed to read metadata of {:?}", path))?;
+        let link_target = if metadata.is_symlink() {
+            Some(fs::read_link(path).context(format!("Failed to read link {:?}", path))?)
         } else {
-            let contents = fs::read(path)?;
-            let mut hasher = DefaultHasher::new();
-            contents.hash(&mut hasher);
-            hasher.finish()
+            None
         };
-
-        entries.push(FileEntry {
-            rel_path: rel_path.to_path_buf(),
-            hash,
-            is_dir,
-        });
+        entries.push(FileEntry::new(
+            rel_path.to_path_buf(),
+            &metadata,
+            link_target,
+        ));
     }
     Ok(entries)
 }
 
+/// Gitignore-style patterns matched against paths relative to the source directory:
+///  - `*` and `?` match within a path component, `**` matches any number of components.
+///  - A pattern without a `/` (other than a trailing one) matches at any depth, e.g. `*.md`;
+///    otherwise it is relative to the source directory, e.g. `/docs` or `addons/foo/**/*.gd`.
+///  - A trailing `/` only matches directories. Matching a directory also matches its contents,
+///    unless a pattern matches them directly.
+///  - `!` negates a pattern, so paths matched by earlier patterns are matched no longer.
+///    The last pattern that matches a path wins. Use e.g. `!dir/**` to negate a directory's
+///    contents.
+pub struct PathPatterns {
+    matcher: Gitignore,
+}
+
+impl PathPatterns {
+    pub fn new(patterns: &[String]) -> anyhow::Result<Self> {
+        let mut builder = GitignoreBuilder::new("");
+        for pattern in patterns {
+            builder
+                .add_line(None, pattern)
+                .context(format!("Invalid pattern: {pattern}"))?;
+        }
+        Ok(Self {
+            matcher: builder.build()?,
+        })
+    }
+
+    /// Whether the path, or one of its parent directories, is matched.
+    pub fn matches(&self, rel_path: &Path, is_dir: bool) -> bool {
+        self.matcher
+            .matched_path_or_any_parents(rel_path, is_dir)
+            .is_ignore()
+    }
+}
+
+/// Keeps the entries that match `includes` (all of them if `None`) and don't match `excludes`.
+/// Directories are kept if they contain a kept entry, so the kept files can be reached.
 fn file_list_filtered(
     list: Vec<FileEntry>,
-    includes: Option<&[PathBuf]>,
-    excludes: Option<&[PathBuf]>,
-) -> Vec<FileEntry> {
-    list.into_iter()
-        .filter(|entry| {
-            let rel_path = &entry.rel_path;
-
-            // Skip if it matches any exclude pattern
-            if excludes.is_some_and(|exs| exs.iter().any(|ex| rel_path.starts_with(ex))) {
-                return false;
-            }
+    includes: Option<&[String]>,
+    excludes: Option<&[String]>,
+) -> anyhow::Result<Vec<FileEntry>> {
+    let includes = includes.map(PathPatterns::new).transpose()?;
+    let excludes = excludes.map(PathPatterns::new).transpose()?;
 
-            // If includes are specified, the path must be inside one of them
-            // or be a parent of one of them (to allow traversing into it)
-            includes.is_none_or(|incs| {
-                incs.iter()
-                    .any(|inc| rel_path.starts_with(inc) || inc.starts_with(rel_path))
-            })
-        })
-        .collect()
+    let (matched, unmatched): (Vec<FileEntry>, Vec<FileEntry>) =
+        list.into_iter().partition(|entry| {
+            !excludes
+                .as_ref()
+                .is_some_and(|ex| ex.matches(&entry.rel_path, entry.is_dir))
+                && includes
+                    .as_ref()
+                    .is_none_or(|inc| inc.matches(&entry.rel_path, entry.is_dir))
+        });
+
+    // Keep the directories that lead to matched entries, even excluded ones, since a negated
+    // exclude can re-include a file inside an excluded directory
+    let mut ancestors: HashSet<&Path> = HashSet::new();
+    for entry in &matched {
+        for ancestor in entry.rel_path.ancestors().skip(1) {
+            // Its own ancestors were added along with it
+            if !ancestors.insert(ancestor) {
+                break;
+            }
+        }
+    }
+    let parent_dirs: Vec<FileEntry> = unmatched
+        .into_iter()
+        .filter(|entry| entry.is_dir && ancestors.contains(entry.rel_path.as_path()))
+        .collect();
+    Ok(matched.into_iter().chain(parent_dirs).collect())
 }
 
 #[cfg(test)]
@@ -138,38 +722,33 @@ mod tests {
     use std::fs;
     use std::path::PathBuf;
 
+    fn entry(path: &str, is_dir: bool) -> FileEntry {
+        FileEntry {
+            rel_path: PathBuf::from(path),
+            is_dir,
+            link_target: None,
+            size: 0,
+            modified: None,
+            permissions: None,
+        }
+    }
+
     #[test]
-    fn test_filter_file_list() {
+    fn test_filter_file_list() -> anyhow::Result<()> {
         let list = vec![
-            FileEntry {
-                rel_path: PathBuf::from("src/main.rs"),
-                hash: 1,
-                is_dir: false,
-            },
-            FileEntry {
-                rel_path: PathBuf::from("src/lib.rs"),
-                hash: 2,
-                is_dir: false,
-            },
-            FileEntry {
-                rel_path: PathBuf::from("docs/index.html"),
-                hash: 3,
-                is_dir: false,
-            },
-            FileEntry {
-                rel_path: PathBuf::from("target/debug/app"),
-                hash: 4,
-                is_dir: false,
-            },
+            entry("src/main.rs", false),
+            entry("src/lib.rs", false),
+            entry("docs/index.html", false),
+            entry("target/debug/app", false),
         ];
 
         // Test 1: No filters
-        let filtered = file_list_filtered(list.clone(), None, None);
+        let filtered = file_list_filtered(list.clone(), None, None)?;
         assert_eq!(filtered.len(), 4);
 
         // Test 2: Only excludes
-        let excludes = vec![PathBuf::from("target"), PathBuf::from("docs/index.html")];
-        let filtered = file_list_filtered(list.clone(), None, Some(&excludes));
+        let excludes = vec!["target".to_string(), "docs/index.html".to_string()];
+        let filtered = file_list_filtered(list.clone(), None, Some(&excludes))?;
         assert_eq!(filtered.len(), 2);
         assert!(
             filtered
@@ -183,24 +762,137 @@ mod tests {
         );
 
         // Test 3: Only includes
-        let includes = vec![PathBuf::from("src")];
-        let filtered = file_list_filtered(list.clone(), Some(&includes), None);
+        let includes = vec!["src".to_string()];
+        let filtered = file_list_filtered(list.clone(), Some(&includes), None)?;
         assert_eq!(filtered.len(), 2);
         assert!(filtered.iter().all(|e| e.rel_path.starts_with("src")));
 
         // Test 4: Combined includes and excludes
         // Include everything in 'src', but exclude 'src/lib.rs'
-        let includes = vec![PathBuf::from("src")];
-        let excludes = vec![PathBuf::from("src/lib.rs")];
-        let filtered = file_list_filtered(list.clone(), Some(&includes), Some(&excludes));
+        let includes = vec!["src".to_string()];
+        let excludes = vec!["src/lib.rs".to_string()];
+        let filtered = file_list_filtered(list.clone(), Some(&includes), Some(&excludes))?;
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0].rel_path, PathBuf::from("src/main.rs"));
 
         // Test 5: Include a parent directory of a file in the list
-        let includes = vec![PathBuf::from("docs")];
-        let filtered = file_list_filtered(list.clone(), Some(&includes), None);
+        let includes = vec!["docs".to_string()];
+        let filtered = file_list_filtered(list.clone(), Some(&includes), None)?;
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0].rel_path, PathBuf::from("docs/index.html"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_file_list_with_globs() -> anyhow::Result<()> {
+        let list = vec![
+            entry("README.md", false),
+            entry("addons", true),
+            entry("addons/foo", true),
+            entry("addons/foo/plugin.gd", false),
+            entry("addons/foo/CHANGELOG.md", false),
+            entry("addons/foo/icon.png", false),
+            entry("addons/foo/ui", true),
+            entry("addons/foo/ui/panel.gd", false),
+            entry("addons/foo/tests", true),
+            entry("addons/foo/tests/test_plugin.gd", false),
+            entry("docs", true),
+            entry("docs/guide.md", false),
+        ];
+        let patterns =
+            |patterns: &[&str]| -> Vec<String> { patterns.iter().map(|p| p.to_string()).collect() };
+        let paths = |filtered: Vec<FileEntry>| -> Vec<String> {
+            let mut paths: Vec<String> = filtered
+                .into_iter()
+                .map(|e| e.rel_path.to_string_lossy().replace('\\', "/"))
+                .collect();
+            paths.sort();
+            paths
+        };
+
+        // Patterns without a slash match at any depth, `**` matches any number of components
+        let excludes = patterns(&["*.md", "**/tests/**"]);
+        assert_eq!(
+            paths(file_list_filtered(list.clone(), None, Some(&excludes))?),
+            vec![
+                "addons",
+                "addons/foo",
+                "addons/foo/icon.png",
+                "addons/foo/plugin.gd",
+                "addons/foo/tests",
+                "addons/foo/ui",
+                "addons/foo/ui/panel.gd",
+                "docs",
+            ]
+        );
+
+        // A leading slash anchors the pattern, negation re-includes a path
+        let excludes = patterns(&["*.md", "!/README.md", "tests/"]);
+        assert_eq!(
+            paths(file_list_filtered(list.clone(), None, Some(&excludes))?),
+            vec![
+                "README.md",
+                "addons",
+                "addons/foo",
+                "addons/foo/icon.png",
+                "addons/foo/plugin.gd",
+                "addons/foo/ui",
+                "addons/foo/ui/panel.gd",
+                "docs",
+            ]
+        );
+
+        // Included files keep their parent directories
+        let includes = patterns(&["addons/foo/**/*.gd", "!addons/foo/tests/**"]);
+        assert_eq!(
+            paths(file_list_filtered(list.clone(), Some(&includes), None)?),
+            vec![
+                "addons",
+                "addons/foo",
+                "addons/foo/plugin.gd",
+                "addons/foo/ui",
+                "addons/foo/ui/panel.gd",
+            ]
+        );
+
+        // Excludes take precedence over includes
+        let includes = patterns(&["addons/"]);
+        let excludes = patterns(&["*.gd"]);
+        assert_eq!(
+            paths(file_list_filtered(
+                list.clone(),
+                Some(&includes),
+                Some(&excludes)
+            )?),
+            vec![
+                "addons",
+                "addons/foo",
+                "addons/foo/CHANGELOG.md",
+                "addons/foo/icon.png",
+                "addons/foo/tests",
+                "addons/foo/ui",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_reincluded_file_in_excluded_dir() -> anyhow::Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let source = tmp_dir.path().join("source");
+        let dest = tmp_dir.path().join("dest");
+        fs::create_dir_all(source.join("tests"))?;
+        fs::write(source.join("plugin.gd"), "extends Node")?;
+        fs::write(source.join("tests/keep.gd"), "extends Node")?;
+        fs::write(source.join("tests/test_plugin.gd"), "extends Node")?;
+        fs::create_dir_all(&dest)?;
+
+        let excludes = vec!["tests/".to_string(), "!tests/keep.gd".to_string()];
+        sync_recursive(&source, &dest, None, Some(&excludes))?;
+        assert!(dest.join("plugin.gd").exists());
+        assert!(dest.join("tests/keep.gd").exists());
+        assert!(!dest.join("tests/test_plugin.gd").exists());
+        Ok(())
     }
 
     #[test]
@@ -247,23 +939,145 @@ mod tests {
             .find(|e| e.rel_path == PathBuf::from("dir1"))
             .ok_or(anyhow::anyhow!("File not found"))?;
 
+        // Files are listed with their metadata, directories without
+        assert_eq!(f1.size, 8);
+        assert!(f1.modified.is_some() && f1.permissions.is_some());
+        assert!(d1.is_dir);
+        assert_eq!((d1.size, d1.modified, &d1.permissions), (0, None, &None));
+
         // Verify hashes for identical files are equal
+        let hashes = HashCache::default();
+        let hash = |entry: &FileEntry| hashes.sha256(&base.join(&entry.rel_path));
         assert_eq!(
-            f1.hash, f1_copy.hash,
+            hash(f1)?,
+            hash(f1_copy)?,
             "Hashes of files with identical content must match"
         );
-        assert_ne!(f1.hash, 0);
 
         // Verify hashes for different files are different
         assert_ne!(
-            f1.hash, f2.hash,
+            hash(f1)?,
+            hash(f2)?,
             "Hashes of files with different content must not match"
         );
 
-        // Verify directory properties
-        assert!(d1.is_dir);
-        assert_eq!(d1.hash, 0, "Directories must have a hash of 0");
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_cache() -> anyhow::Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let cache_path = tmp_dir.path().join("cache/file_hashes.json");
+        let file_path = tmp_dir.path().join("file.txt");
+        let write = |contents: &str, modified: SystemTime| -> anyhow::Result<()> {
+            fs::write(&file_path, contents)?;
+            File::options()
+                .write(true)
+                .open(&file_path)?
+                .set_modified(modified)?;
+            Ok(())
+        };
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+
+        write("content1", an_hour_ago)?;
+        let hashes = HashCache::load(&cache_path);
+        let hash1 = hashes.sha256(&file_path)?;
+        assert_eq!(hash1, sha256_file(&file_path)?);
+        hashes.save()?;
+
+        // The file isn't read again while its size and modification time are the same
+        write("content2", an_hour_ago)?;
+        let hashes = HashCache::load(&cache_path);
+        assert_eq!(hashes.sha256(&file_path)?, hash1);
+        write("content2", an_hour_ago + Duration::from_secs(1))?;
+        let hash2 = hashes.sha256(&file_path)?;
+        assert_ne!(hash2, hash1);
+
+        // Files that were just modified aren't cached, since they may change again unnoticed
+        write("content3", SystemTime::now())?;
+        let hash3 = hashes.sha256(&file_path)?;
+        assert_ne!(hash3, hash2);
+        hashes.save()?;
+        let saved = fs::read_to_string(&cache_path)?;
+        assert!(!saved.contains(&hash2) && !saved.contains(&hash3));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sync_preserves_metadata_and_links() -> anyhow::Result<()> {
+        use std::os::unix::fs::{PermissionsExt, symlink};
+
+        let source = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let dest = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let (source, dest) = (source.path(), dest.path());
+        let mode = |path: &Path| -> anyhow::Result<u32> {
+            Ok(fs::metadata(path)?.permissions().mode() & 0o777)
+        };
+        fs::create_dir(source.join("bin"))?;
+        fs::write(source.join("bin/tool.sh"), "#!/bin/sh")?;
+        fs::set_permissions(source.join("bin/tool.sh"), Permissions::from_mode(0o755))?;
+        fs::write(source.join("plugin.cfg"), "[plugin]")?;
+        symlink("bin/tool.sh", source.join("tool"))?;
+        symlink("bin", source.join("bin_link"))?;
+
+        let hashes = HashCache::default();
+        let plan = plan_sync(source, dest, None, None, false, &hashes)?;
+        let added: Vec<&Path> = plan.added.iter().map(|c| c.rel_path.as_path()).collect();
+        assert_eq!(
+            added,
+            vec![
+                Path::new("bin"),
+                Path::new("bin/tool.sh"),
+                Path::new("bin_link"),
+                Path::new("plugin.cfg"),
+                Path::new("tool"),
+            ]
+        );
+        apply_sync_plan(source, dest, &plan)?;
+
+        // Links are copied as links, files with their permissions and modification time
+        assert_eq!(fs::read_link(dest.join("tool"))?, Path::new("bin/tool.sh"));
+        assert_eq!(fs::read_link(dest.join("bin_link"))?, Path::new("bin"));
+        assert_eq!(mode(&dest.join("bin/tool.sh"))?, 0o755);
+        assert_eq!(
+            fs::metadata(dest.join("plugin.cfg"))?.modified()?,
+            fs::metadata(source.join("plugin.cfg"))?.modified()?
+        );
+        assert!(plan_sync(source, dest, None, None, false, &hashes)?.is_empty());
+
+        // A file with the same contents but another modification time is unchanged
+        File::options()
+            .write(true)
+            .open(dest.join("plugin.cfg"))?
+            .set_modified(SystemTime::now() - Duration::from_secs(3600))?;
+        assert!(plan_sync(source, dest, None, None, false, &hashes)?.is_empty());
+
+        // Changing permissions or where a link points modifies the file or link
+        fs::set_permissions(source.join("bin/tool.sh"), Permissions::from_mode(0o644))?;
+        fs::remove_file(source.join("tool"))?;
+        symlink("plugin.cfg", source.join("tool"))?;
+        let plan = plan_sync(source, dest, None, None, false, &hashes)?;
+        let modified: Vec<&Path> = plan.modified.iter().map(|c| c.rel_path.as_path()).collect();
+        assert_eq!(modified, vec![Path::new("bin/tool.sh"), Path::new("tool")]);
+        apply_sync_plan(source, dest, &plan)?;
+        assert_eq!(mode(&dest.join("bin/tool.sh"))?, 0o644);
+        assert_eq!(fs::read_link(dest.join("tool"))?, Path::new("plugin.cfg"));
 
+        // Read-only files and links are replaced rather than written through
+        fs::set_permissions(source.join("plugin.cfg"), Permissions::from_mode(0o444))?;
+        fs::remove_file(source.join("tool"))?;
+        fs::write(source.join("tool"), "not a link")?;
+        sync_recursive(source, dest, None, None)?;
+        fs::write(source.join("plugin.cfg"), "[plugin]\nname=\"New\"")?;
+        sync_recursive(source, dest, None, None)?;
+        assert!(!is_link(&dest.join("tool")));
+        assert_eq!(fs::read_to_string(dest.join("tool"))?, "not a link");
+        assert_eq!(
+            fs::read_to_string(dest.join("plugin.cfg"))?,
+            "[plugin]\nname=\"New\""
+        );
+        assert!(plan_sync(source, dest, None, None, false, &hashes)?.is_empty());
         Ok(())
     }
 
@@ -356,4 +1170,125 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_plan_sync() -> anyhow::Result<()> {
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        let test_addon1_path: PathBuf = [manifest_dir, "test-data", "test-addon1-repo"]
+            .iter()
+            .collect();
+        let test_addon1v2_path: PathBuf = [manifest_dir, "test-data", "test-addon1v2-repo"]
+            .iter()
+            .collect();
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        sync_recursive(&test_addon1_path, tmp_dir.path(), None, None)?;
+
+        let plan = plan_sync(
+            &test_addon1v2_path,
+            tmp_dir.path(),
+            None,
+            None,
+            false,
+            &HashCache::default(),
+        )?;
+        let paths = |changes: &[SyncChange]| -> Vec<PathBuf> {
+            changes.iter().map(|c| c.rel_path.clone()).collect()
+        };
+        assert_eq!(
+            paths(&plan.added),
+            vec![
+                PathBuf::from("addons/test-addon1/directory_changed_to_file.txt"),
+                PathBuf::from("addons/test-addon1/file_changed_to_directory.txt"),
+                PathBuf::from("addons/test-addon1/file_changed_to_directory.txt/file.txt"),
+                PathBuf::from("addons/test-addon1/new_file.txt"),
+            ]
+        );
+        assert_eq!(
+            paths(&plan.modified),
+            vec![
+                PathBuf::from("addons/test-addon1/changed_file.txt"),
+                PathBuf::from("addons/test-addon1/plugin.cfg"),
+            ]
+        );
+        assert!(paths(&plan.removed).contains(&PathBuf::from("addons/test-addon1/old_file.txt")));
+
+        // Planning doesn't touch the destination
+        assert!(
+            tmp_dir
+                .path()
+                .join("addons/test-addon1/old_file.txt")
+                .exists()
+        );
+
+        let diffs = plan.text_diffs(&test_addon1v2_path, tmp_dir.path())?;
+        assert_eq!(diffs.len(), 2);
+        assert!(
+            diffs[0]
+                .1
+                .contains("+++ b/addons/test-addon1/changed_file.txt")
+        );
+
+        apply_sync_plan(&test_addon1v2_path, tmp_dir.path(), &plan)?;
+        assert!(
+            plan_sync(
+                &test_addon1v2_path,
+                tmp_dir.path(),
+                None,
+                None,
+                false,
+                &HashCache::default(),
+            )?
+            .is_empty()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_sync_preserves_godot_files() -> anyhow::Result<()> {
+        let source = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let dest = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        fs::write(source.path().join("icon.svg"), "<svg/>")?;
+        fs::write(source.path().join("plugin.gd"), "extends EditorPlugin")?;
+        fs::write(source.path().join("shipped.gd"), "extends Node")?;
+        fs::write(source.path().join("shipped.gd.uid"), "uid://shipped")?;
+        sync_recursive(source.path(), dest.path(), None, None)?;
+
+        // Godot imports the addon
+        fs::write(dest.path().join("icon.svg.import"), "[remap]")?;
+        fs::write(dest.path().join("plugin.gd.uid"), "uid://plugin")?;
+        fs::write(dest.path().join("shipped.gd.uid"), "uid://regenerated")?;
+        fs::write(dest.path().join("removed.gd.uid"), "uid://removed")?;
+
+        let plan = plan_sync(
+            source.path(),
+            dest.path(),
+            None,
+            None,
+            true,
+            &HashCache::default(),
+        )?;
+        let removed: Vec<&Path> = plan.removed.iter().map(|c| c.rel_path.as_path()).collect();
+        assert_eq!(removed, vec![Path::new("removed.gd.uid")]);
+        // Sidecar files that the addon ships are synced like any other file
+        let modified: Vec<&Path> = plan.modified.iter().map(|c| c.rel_path.as_path()).collect();
+        assert_eq!(modified, vec![Path::new("shipped.gd.uid")]);
+
+        let plan = plan_sync(
+            source.path(),
+            dest.path(),
+            None,
+            None,
+            false,
+            &HashCache::default(),
+        )?;
+        assert_eq!(plan.removed.len(), 3);
+
+        assert_eq!(
+            godot_sidecar_asset(Path::new("sub/icon.png.import")),
+            Some(PathBuf::from("sub/icon.png"))
+        );
+        assert_eq!(godot_sidecar_asset(Path::new("icon.png")), None);
+        assert_eq!(godot_sidecar_asset(Path::new("project.uid")), None);
+        Ok(())
+    }
 }
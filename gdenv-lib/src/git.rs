@@ -1,19 +1,58 @@
-use crate::config::Config;
+use crate::builtin_git::BuiltinGitClient;
+use crate::config::{Config, GitBackend};
+use crate::file_lock::FileLock;
 use anyhow::{Context, Result, anyhow, bail};
-use std::path::{Path, PathBuf};
-use std::process::Command;
+use sha2::{Digest, Sha256};
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+use std::process::Output;
+use tokio::process::Command;
+use walkdir::WalkDir;
+
+/// The directory of the git cache that worktrees are checked out in, next to the mirrors.
+pub(crate) const WORKTREES_DIR: &str = "worktrees";
+
+/// Where the commit of a repository's default branch is kept, once it has been fetched.
+pub(crate) const DEFAULT_BRANCH_REF: &str = "refs/remotes/origin/HEAD";
+
+/// The file in a mirror that keeps the tags of the repository as they were last listed.
+/// Only the tags that were checked out are fetched into the mirror, so this is how all of
+/// them can be listed without network access.
+pub(crate) const REMOTE_TAGS_FILE: &str = "gdenv-remote-tags";
 
 pub trait GitClient: Send + Sync {
     /// Initializes a new git repository at the specified path.
     fn init(&self, path: &Path, branch: Option<&str>) -> impl Future<Output = Result<()>> + Send;
 
-    /// Clones or updates a repository and checks out the specified ref.
-    /// Returns the path to the checked-out repository.
+    /// Locks the cached repository for `repo_url`, so other gdenv processes can't
+    /// fetch into it or prune its worktrees while this process checks it out and reads
+    /// from it.
+    fn lock_repo(&self, repo_url: &str) -> impl Future<Output = Result<FileLock>> + Send;
+
+    /// Fetches the specified ref into the repository's mirror and checks it out in a worktree
+    /// of its own, or reuses the worktree if the commit was checked out before.
+    /// Only the files in `sparse_dirs` (relative to the repository root, see
+    /// [sparse_checkout_dirs]) and at the top of their parent directories are checked out,
+    /// or the whole tree if it's empty.
+    /// Returns the path to the worktree, whose files must not be modified.
+    /// The caller should hold the lock from [Self::lock_repo] while using the checkout.
     fn checkout(
         &self,
         repo_url: &str,
         git_ref: &str,
+        sparse_dirs: &[PathBuf],
     ) -> impl Future<Output = Result<PathBuf>> + Send;
+
+    /// Lists the tags of the remote repository, without cloning it.
+    fn list_remote_tags(&self, repo_url: &str) -> impl Future<Output = Result<Vec<GitTag>>> + Send;
+}
+
+/// A tag of a remote repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitTag {
+    pub name: String,
+    /// The commit the tag points to.
+    pub commit: String,
 }
 
 pub struct SystemGitClient {
@@ -27,6 +66,10 @@ impl SystemGitClient {
 }
 
 impl GitClient for SystemGitClient {
+    async fn lock_repo(&self, repo_url: &str) -> Result<FileLock> {
+        lock_repo_dir(&self.config, repo_url).await
+    }
+
     async fn init(&self, path: &Path, branch: Option<&str>) -> Result<()> {
         let mut cmd = Command::new("git");
         cmd.arg("init");
@@ -38,6 +81,7 @@ impl GitClient for SystemGitClient {
         let output = cmd
             .arg(path)
             .output()
+            .await
             .context("Failed to execute git init")?;
 
         if !output.status.success() {
@@ -51,73 +95,594 @@ impl GitClient for SystemGitClient {
         Ok(())
     }
 
-    async fn checkout(&self, repo_url: &str, git_ref: &str) -> Result<PathBuf> {
-        let repo_dir = get_repo_dir(&self.config, repo_url);
+    async fn checkout(
+        &self,
+        repo_url: &str,
+        git_ref: &str,
+        sparse_dirs: &[PathBuf],
+    ) -> Result<PathBuf> {
+        let mirror_dir = get_repo_dir(&self.config, repo_url);
 
-        if !repo_dir.exists() {
-            // Clone the repository if it doesn't exist
-            let output = Command::new("git")
-                .args(["clone", "--no-checkout", "--filter=blob:none", repo_url])
-                .arg(&repo_dir)
-                .output()
-                .context("Failed to execute git clone")?;
+        if !mirror_dir.exists() {
+            // Set up the mirror like `git clone --bare --filter=blob:none` would, without
+            // fetching anything yet: only the commits that are checked out are fetched.
+            init_mirror(&mirror_dir, repo_url).await?;
+        }
 
-            if !output.status.success() {
-                bail!(
-                    "git clone failed for {}. Reason: {}",
+        // Fetch just the commit of the ref, without its history
+        let fetch_ref = if git_ref.is_empty() { "HEAD" } else { git_ref };
+        let output = run_git(&mirror_dir, &["fetch", "--depth=1", "origin", fetch_ref]).await?;
+        let mut fetched = output.status.success();
+        if !fetched && is_abbreviated_commit(git_ref) {
+            // Abbreviated commit hashes can only be found in the full history
+            tracing::debug!(
+                "Fetching the history of {} to find commit {}",
+                repo_url,
+                git_ref
+            );
+            fetched = run_git(&mirror_dir, &["fetch", "--depth=2147483647", "origin"])
+                .await?
+                .status
+                .success();
+        } else if fetched {
+            keep_fetched_ref(&mirror_dir, git_ref).await?;
+        }
+
+        let cached_ref = if fetched && !is_abbreviated_commit(git_ref) {
+            "FETCH_HEAD"
+        } else {
+            git_ref
+        };
+        let commit = match resolve_cached_ref(&mirror_dir, cached_ref).await {
+            Some(commit) => {
+                // Without network access (e.g. after importing an offline bundle),
+                // the ref may still be available in the mirror.
+                if !fetched {
+                    tracing::warn!(
+                        "git fetch failed for {} at {}, using the cached copy of the repository instead.",
+                        repo_url,
+                        git_ref
+                    );
+                }
+                commit
+            }
+            None => {
+                return Err(anyhow!(
+                    "git fetch failed for {} at {}. Reason: {}",
                     repo_url,
+                    git_ref,
                     String::from_utf8_lossy(&output.stderr)
-                );
+                ));
             }
+        };
+
+        // Every commit (and set of sparse directories) gets its own worktree, which never
+        // changes once it's created, so addons at different revisions don't get in each
+        // other's way.
+        let worktree_dir = get_worktree_dir(&self.config, repo_url, &commit, sparse_dirs);
+        if !worktree_dir.exists() {
+            add_worktree(&mirror_dir, &worktree_dir, repo_url, &commit, sparse_dirs).await?;
         }
+        mark_used(&worktree_dir, &commit)?;
+
+        Ok(worktree_dir)
+    }
 
-        // Fetch and checkout the specific ref
+    async fn list_remote_tags(&self, repo_url: &str) -> Result<Vec<GitTag>> {
         let output = Command::new("git")
-            .current_dir(&repo_dir)
-            .args(["fetch", "origin", git_ref])
+            .args(["ls-remote", "--tags", repo_url])
             .output()
-            .context("Failed to execute git fetch")?;
+            .await
+            .context("Failed to execute git ls-remote")?;
 
-        if !output.status.success() {
-            return Err(anyhow!(
-                "git fetch failed for {} at {}. Reason: {}",
-                repo_url,
-                git_ref,
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        let repo_dir = get_repo_dir(&self.config, repo_url);
+        if output.status.success() {
+            let listing = String::from_utf8_lossy(&output.stdout);
+            if repo_dir.exists() {
+                save_remote_tags(&repo_dir, &listing);
+            }
+            return Ok(parse_tags(&listing));
+        }
+
+        // Without network access, fall back to the tags of the cached repository, like checkout
+        if repo_dir.exists() {
+            let mut listing =
+                std::fs::read_to_string(repo_dir.join(REMOTE_TAGS_FILE)).unwrap_or_default();
+            let cached = Command::new("git")
+                .current_dir(&repo_dir)
+                .args(["show-ref", "--tags", "--dereference"])
+                .output()
+                .await
+                .context("Failed to execute git show-ref")?;
+            listing.push_str(&String::from_utf8_lossy(&cached.stdout));
+            if !listing.is_empty() {
+                tracing::warn!(
+                    "git ls-remote failed for {}, using the tags of the cached copy of the repository instead.",
+                    repo_url
+                );
+                return Ok(parse_tags(&listing));
+            }
         }
+        bail!(
+            "git ls-remote failed for {}. Reason: {}",
+            repo_url,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+/// Keeps the output of `git ls-remote --tags` in the mirror at `repo_dir`, see
+/// [REMOTE_TAGS_FILE]. It's only a fallback, so failing to write it isn't an error.
+fn save_remote_tags(repo_dir: &Path, listing: &str) {
+    let path = repo_dir.join(REMOTE_TAGS_FILE);
+    let tmp_path = path.with_extension(format!("tmp-{}", std::process::id()));
+    let result =
+        std::fs::write(&tmp_path, listing).and_then(|()| std::fs::rename(&tmp_path, &path));
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&tmp_path);
+        tracing::debug!("Failed to save the tags to {}: {}", path.display(), e);
+    }
+}
+
+/// Parses the output of `git ls-remote --tags` or `git show-ref --tags --dereference`.
+/// Annotated tags are listed twice: once with the tag object, and once with a `^{}` suffix
+/// and the commit it points to, which is the one kept. Tags that are listed again after
+/// that, e.g. in the output of both commands, are ignored.
+pub(crate) fn parse_tags(output: &str) -> Vec<GitTag> {
+    let mut tags: Vec<GitTag> = Vec::new();
+    for line in output.lines() {
+        let Some((commit, git_ref)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let Some(name) = git_ref.strip_prefix("refs/tags/") else {
+            continue;
+        };
+        match name.strip_suffix("^{}") {
+            Some(name) => {
+                if let Some(tag) = tags.iter_mut().find(|tag| tag.name == name) {
+                    tag.commit = commit.to_string();
+                }
+            }
+            None if tags.iter().any(|tag| tag.name == name) => {}
+            None => tags.push(GitTag {
+                name: name.to_string(),
+                commit: commit.to_string(),
+            }),
+        }
+    }
+    tags
+}
+
+/// The git client selected by [Config::git_backend]: [SystemGitClient] if the `git` command is
+/// available, or [BuiltinGitClient] otherwise.
+pub enum AutoGitClient {
+    System(SystemGitClient),
+    Builtin(BuiltinGitClient),
+}
+
+impl AutoGitClient {
+    pub fn new(config: Config) -> Self {
+        let use_system = match config.git_backend {
+            GitBackend::System => true,
+            GitBackend::Builtin => false,
+            GitBackend::Auto => {
+                let found = std::process::Command::new("git")
+                    .arg("--version")
+                    .output()
+                    .is_ok_and(|output| output.status.success());
+                if !found {
+                    tracing::debug!("git isn't installed, using the built-in git client");
+                }
+                found
+            }
+        };
+        if use_system {
+            Self::System(SystemGitClient::new(config))
+        } else {
+            Self::Builtin(BuiltinGitClient::new(config))
+        }
+    }
+}
+
+impl GitClient for AutoGitClient {
+    async fn init(&self, path: &Path, branch: Option<&str>) -> Result<()> {
+        match self {
+            Self::System(client) => client.init(path, branch).await,
+            Self::Builtin(client) => client.init(path, branch).await,
+        }
+    }
+
+    async fn lock_repo(&self, repo_url: &str) -> Result<FileLock> {
+        match self {
+            Self::System(client) => client.lock_repo(repo_url).await,
+            Self::Builtin(client) => client.lock_repo(repo_url).await,
+        }
+    }
+
+    async fn checkout(
+        &self,
+        repo_url: &str,
+        git_ref: &str,
+        sparse_dirs: &[PathBuf],
+    ) -> Result<PathBuf> {
+        match self {
+            Self::System(client) => client.checkout(repo_url, git_ref, sparse_dirs).await,
+            Self::Builtin(client) => client.checkout(repo_url, git_ref, sparse_dirs).await,
+        }
+    }
+
+    async fn list_remote_tags(&self, repo_url: &str) -> Result<Vec<GitTag>> {
+        match self {
+            Self::System(client) => client.list_remote_tags(repo_url).await,
+            Self::Builtin(client) => client.list_remote_tags(repo_url).await,
+        }
+    }
+}
+
+/// Creates an empty bare repository for `repo_url`, set up to fetch commits without their
+/// blobs, which are fetched on demand for the files that are checked out.
+async fn init_mirror(mirror_dir: &Path, repo_url: &str) -> Result<()> {
+    std::fs::create_dir_all(mirror_dir)
+        .context(format!("Failed to create {}", mirror_dir.display()))?;
+    let commands: [&[&str]; 4] = [
+        &["init", "--bare", "--quiet"],
+        &["remote", "add", "origin", repo_url],
+        &["config", "remote.origin.promisor", "true"],
+        &["config", "remote.origin.partialclonefilter", "blob:none"],
+    ];
+    for args in commands {
+        if let Err(error) = run_git_checked(mirror_dir, args, repo_url).await {
+            // Don't leave a half set up repository behind for the next run
+            let _ = std::fs::remove_dir_all(mirror_dir);
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// Checks out `commit` of the mirror into a new worktree at `worktree_dir`. The worktree is
+/// set up next to it and moved into place once it's complete.
+async fn add_worktree(
+    mirror_dir: &Path,
+    worktree_dir: &Path,
+    repo_url: &str,
+    commit: &str,
+    sparse_dirs: &[PathBuf],
+) -> Result<()> {
+    let tmp_dir = worktree_dir.with_extension("tmp");
+    if tmp_dir.exists() {
+        std::fs::remove_dir_all(&tmp_dir)
+            .context(format!("Failed to remove {}", tmp_dir.display()))?;
+    }
+    // Forget about worktrees that were removed, such as an interrupted one at `tmp_dir`
+    run_git_checked(mirror_dir, &["worktree", "prune"], repo_url).await?;
+    run_git_checked(
+        mirror_dir,
+        &[
+            OsStr::new("worktree"),
+            OsStr::new("add"),
+            OsStr::new("--no-checkout"),
+            OsStr::new("--detach"),
+            tmp_dir.as_os_str(),
+            OsStr::new(commit),
+        ],
+        repo_url,
+    )
+    .await?;
+
+    // Only check out the directories the addon uses. The blobs of the other files are
+    // never fetched, which matters for addons that live in large repositories.
+    if !sparse_dirs.is_empty() {
+        let mut args = vec![
+            "sparse-checkout".to_string(),
+            "set".to_string(),
+            "--cone".to_string(),
+            "--".to_string(),
+        ];
+        args.extend(sparse_dirs.iter().map(|dir| slash_path(dir)));
+        run_git_checked(&tmp_dir, &args, repo_url).await?;
+    }
+
+    // Git LFS files are downloaded all at once below, rather than one by one
+    let output = Command::new("git")
+        .current_dir(&tmp_dir)
+        .env("GIT_LFS_SKIP_SMUDGE", "1")
+        .args(["reset", "--hard", "--quiet"])
+        .output()
+        .await
+        .context("Failed to execute git reset")?;
+    if !output.status.success() {
+        bail!(
+            "git checkout failed for {} at {}. Reason: {}",
+            repo_url,
+            commit,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    if uses_lfs(&tmp_dir) {
+        pull_lfs_files(&tmp_dir, repo_url, sparse_dirs).await?;
+    }
+
+    run_git_checked(
+        mirror_dir,
+        &[
+            OsStr::new("worktree"),
+            OsStr::new("move"),
+            tmp_dir.as_os_str(),
+            worktree_dir.as_os_str(),
+        ],
+        repo_url,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Keeps a tag or default branch fetched by name, so it can be listed and checked out
+/// without network access. Fetching a single ref only records it in `FETCH_HEAD`.
+async fn keep_fetched_ref(mirror_dir: &Path, git_ref: &str) -> Result<()> {
+    let fetch_head = std::fs::read_to_string(mirror_dir.join("FETCH_HEAD")).unwrap_or_default();
+    let Some((object, description)) = fetch_head
+        .lines()
+        .next()
+        .and_then(|line| line.split_once('\t'))
+    else {
+        return Ok(());
+    };
+    let kept_ref = if git_ref.is_empty() {
+        DEFAULT_BRANCH_REF.to_string()
+    } else if description.contains(&format!("tag '{git_ref}'")) {
+        format!("refs/tags/{git_ref}")
+    } else {
+        return Ok(());
+    };
+    let output = run_git(mirror_dir, &["update-ref", "--no-deref", &kept_ref, object]).await?;
+    if !output.status.success() {
+        tracing::debug!(
+            "Failed to keep {}: {}",
+            kept_ref,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
 
+/// Whether `git_ref` looks like a commit hash that is too short to be fetched by itself.
+pub(crate) fn is_abbreviated_commit(git_ref: &str) -> bool {
+    (4..40).contains(&git_ref.len()) && git_ref.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Whether the checked out files include `.gitattributes` files that store files with Git LFS.
+pub(crate) fn uses_lfs(repo_dir: &Path) -> bool {
+    WalkDir::new(repo_dir)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && entry.file_name() == ".gitattributes")
+        .any(|entry| {
+            std::fs::read_to_string(entry.path())
+                .is_ok_and(|attributes| attributes.contains("filter=lfs"))
+        })
+}
+
+/// Downloads the Git LFS files in `sparse_dirs` (all of them if empty), replacing their pointer
+/// files in the checkout.
+async fn pull_lfs_files(repo_dir: &Path, repo_url: &str, sparse_dirs: &[PathBuf]) -> Result<()> {
+    let mut args = vec!["lfs".to_string(), "pull".to_string()];
+    if !sparse_dirs.is_empty() {
+        let includes: Vec<String> = sparse_dirs
+            .iter()
+            .map(|dir| format!("{}/**", slash_path(dir)))
+            .collect();
+        args.push(format!("--include={}", includes.join(",")));
+    }
+    let output = run_git(repo_dir, &args).await?;
+    if !output.status.success() {
+        bail!(
+            "git lfs pull failed for {}. The repository stores files with Git LFS, make sure git-lfs is installed (https://git-lfs.com). Reason: {}",
+            repo_url,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// Like [run_git], failing if git does.
+async fn run_git_checked<S: AsRef<OsStr>>(
+    dir: &Path,
+    args: &[S],
+    repo_url: &str,
+) -> Result<Output> {
+    let output = run_git(dir, args).await?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed for {}. Reason: {}",
+            args.first()
+                .map(|arg| arg.as_ref().to_string_lossy())
+                .unwrap_or_default(),
+            repo_url,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(output)
+}
+
+pub(crate) async fn run_git<S: AsRef<OsStr>>(dir: &Path, args: &[S]) -> Result<Output> {
+    Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .await
+        .context(format!(
+            "Failed to execute git {}",
+            args.first()
+                .map(|arg| arg.as_ref().to_string_lossy())
+                .unwrap_or_default()
+        ))
+}
+
+pub(crate) fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Looks up the commit `git_ref` points to using only what is already in the repository.
+async fn resolve_cached_ref(repo_dir: &Path, git_ref: &str) -> Option<String> {
+    let candidates = if git_ref.is_empty() {
+        vec![DEFAULT_BRANCH_REF.to_string()]
+    } else {
+        vec![git_ref.to_string(), format!("origin/{git_ref}")]
+    };
+    for candidate in candidates {
         let output = Command::new("git")
-            .current_dir(&repo_dir)
-            .args(["checkout", "FETCH_HEAD"])
+            .current_dir(repo_dir)
+            .args(["rev-parse", "--verify", "--quiet"])
+            .arg(format!("{candidate}^{{commit}}"))
             .output()
-            .context("Failed to execute git checkout")?;
-
-        if !output.status.success() {
-            bail!(
-                "git checkout failed for {}. Reason: {}",
-                git_ref,
-                String::from_utf8_lossy(&output.stderr)
-            );
+            .await
+            .ok()?;
+        if output.status.success() {
+            return Some(String::from_utf8_lossy(&output.stdout).trim().to_string());
         }
+    }
+    None
+}
 
-        Ok(repo_dir)
+/// The directories to check out for an addon in `subdir` of a repository (the whole
+/// repository if `None`), narrowed down to the literal directories its `include` patterns
+/// start with. Patterns that can match anywhere, like `*.gd`, keep all of `subdir`.
+/// Returns an empty list if the whole tree is needed.
+pub fn sparse_checkout_dirs(subdir: Option<&Path>, include: Option<&[String]>) -> Vec<PathBuf> {
+    let base: PathBuf = subdir
+        .unwrap_or(Path::new(""))
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect();
+    let mut dirs = Vec::new();
+    for pattern in include.unwrap_or_default() {
+        if pattern.starts_with('!') {
+            continue;
+        }
+        // Like in .gitignore files, patterns without a slash in the middle match at any depth
+        let anchored = pattern.trim_end_matches('/').contains('/');
+        let components: Vec<&str> = pattern
+            .trim_start_matches('/')
+            .split('/')
+            .filter(|component| !component.is_empty())
+            .collect();
+        let literal: Vec<&str> = components
+            .iter()
+            .take_while(|component| !component.contains(['*', '?', '[', '\\']))
+            .copied()
+            .collect();
+        // The last component may be a file, unless the pattern ends with a slash
+        let dir_len = if literal.len() == components.len() && !pattern.ends_with('/') {
+            literal.len().saturating_sub(1)
+        } else {
+            literal.len()
+        };
+        if !anchored || dir_len == 0 {
+            dirs = vec![base.clone()];
+            break;
+        }
+        dirs.push(
+            literal[..dir_len]
+                .iter()
+                .fold(base.clone(), |dir, c| dir.join(c)),
+        );
+    }
+    if dirs.is_empty() {
+        dirs.push(base);
     }
+    dirs.sort();
+    dirs.dedup();
+    // Checking out a directory includes its subdirectories
+    let mut kept: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if !kept.iter().any(|parent| dir.starts_with(parent)) {
+            kept.push(dir);
+        }
+    }
+    kept.retain(|dir| !dir.as_os_str().is_empty());
+    kept
 }
 
+/// The bare mirror of `repo_url` in the git cache, which the worktrees of its checkouts share.
 pub fn get_repo_dir(config: &Config, repo_url: &str) -> PathBuf {
+    config
+        .git_cache_dir
+        .join(format!("{}.git", repo_cache_name(repo_url)))
+}
+
+/// The directory the worktrees of `repo_url` are checked out in.
+pub(crate) fn get_worktrees_dir(config: &Config, repo_url: &str) -> PathBuf {
+    config
+        .git_cache_dir
+        .join(WORKTREES_DIR)
+        .join(repo_cache_name(repo_url))
+}
+
+/// The worktree `commit` is checked out in with `sparse_dirs`, named after the commit and,
+/// for sparse checkouts, a hash of the directories.
+pub(crate) fn get_worktree_dir(
+    config: &Config,
+    repo_url: &str,
+    commit: &str,
+    sparse_dirs: &[PathBuf],
+) -> PathBuf {
+    let mut name = commit[..commit.len().min(12)].to_string();
+    if !sparse_dirs.is_empty() {
+        let mut hasher = Sha256::new();
+        for dir in sparse_dirs {
+            hasher.update(slash_path(dir));
+            hasher.update("\n");
+        }
+        let hash: String = hasher.finalize()[..4]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        name = format!("{name}-{hash}");
+    }
+    get_worktrees_dir(config, repo_url).join(name)
+}
+
+/// The file whose modification time is when the worktree at `worktree_dir` was last used.
+/// It contains the commit the worktree has checked out.
+pub(crate) fn last_used_file(worktree_dir: &Path) -> PathBuf {
+    worktree_dir.with_extension("last-used")
+}
+
+pub(crate) fn mark_used(worktree_dir: &Path, commit: &str) -> Result<()> {
+    let path = last_used_file(worktree_dir);
+    std::fs::write(&path, commit).context(format!("Failed to write {}", path.display()))
+}
+
+/// Locks the git cache directory for `repo_url`. See [GitClient::lock_repo].
+pub async fn lock_repo_dir(config: &Config, repo_url: &str) -> Result<FileLock> {
+    FileLock::acquire(
+        &config
+            .locks_dir
+            .join(format!("git-{}.lock", repo_cache_name(repo_url))),
+        &format!("using git repository {repo_url}"),
+    )
+    .await
+}
+
+fn repo_cache_name(repo_url: &str) -> String {
     // Create a unique directory name based on the URL to avoid collisions in cache
-    let safe_name = repo_url
+    repo_url
         .replace("://", "_")
         .replace("/", "_")
-        .replace(":", "_");
-    config.git_cache_dir.join(safe_name)
+        .replace(":", "_")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
+    use std::process::Command;
 
     #[tokio::test]
     async fn test_system_git_client_local_checkout() -> Result<()> {
@@ -163,7 +728,7 @@ mod tests {
         let repo_url = source_repo
             .to_str()
             .ok_or(anyhow::anyhow!("Invalid path"))?;
-        let checked_out_path = git_client.checkout(repo_url, "main").await?; // Handle different default branch names
+        let checked_out_path = git_client.checkout(repo_url, "main", &[]).await?; // Handle different default branch names
 
         // 4. Verify the file exists in the checked-out location
         assert!(checked_out_path.exists());
@@ -172,6 +737,257 @@ mod tests {
         let content = fs::read_to_string(checked_out_path.join("hello.txt"))?;
         assert_eq!(content, "world");
 
+        // 5. List the tags without cloning
+        Command::new("git")
+            .current_dir(&source_repo)
+            .args(["tag", "-a", "v1.0.0", "-m", "Release 1.0.0"])
+            .status()?;
+        Command::new("git")
+            .current_dir(&source_repo)
+            .args(["tag", "v1.1.0"])
+            .status()?;
+        let head = Command::new("git")
+            .current_dir(&source_repo)
+            .args(["rev-parse", "HEAD"])
+            .output()?;
+        let head = String::from_utf8_lossy(&head.stdout).trim().to_string();
+        let mut tags = git_client.list_remote_tags(repo_url).await?;
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(
+            tags,
+            vec![
+                GitTag {
+                    name: "v1.0.0".to_string(),
+                    commit: head.clone(),
+                },
+                GitTag {
+                    name: "v1.1.0".to_string(),
+                    commit: head.clone(),
+                },
+            ]
+        );
+
+        // 6. Without access to the repository, the cached tags are listed instead
+        git_client.checkout(repo_url, "v1.1.0", &[]).await?;
+        fs::rename(&source_repo, tmp_dir.path().join("moved_repo"))?;
+        let mut tags = git_client.list_remote_tags(repo_url).await?;
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        let tag_names: Vec<&str> = tags.iter().map(|tag| tag.name.as_str()).collect();
+        assert_eq!(tag_names, vec!["v1.0.0", "v1.1.0"]);
+        assert!(tags.iter().all(|tag| tag.commit == head));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_system_git_client_sparse_shallow_checkout() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = SystemGitClient::new(config.clone());
+
+        // 1. Create a repository with two commits, which serves partial clones
+        let source_repo = tmp_dir.path().join("source_repo");
+        git_client.init(&source_repo, Some("main")).await?;
+        let git = |dir: &Path, args: &[&str]| -> Result<std::process::Output> {
+            Ok(Command::new("git")
+                .current_dir(dir)
+                .args(["-c", "user.email=test@example.com", "-c", "user.name=test"])
+                .args(args)
+                .output()?)
+        };
+        let stdout = |output: std::process::Output| {
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        git(&source_repo, &["config", "uploadpack.allowFilter", "true"])?;
+        fs::create_dir_all(source_repo.join("addons/foo"))?;
+        fs::create_dir_all(source_repo.join("docs"))?;
+        fs::write(source_repo.join("addons/foo/plugin.cfg"), "[plugin]")?;
+        fs::write(source_repo.join("docs/index.md"), "# Docs")?;
+        fs::write(source_repo.join("hello.txt"), "world")?;
+        git(&source_repo, &["add", "."])?;
+        git(&source_repo, &["commit", "-m", "First commit"])?;
+        let first_commit = stdout(git(&source_repo, &["rev-parse", "HEAD"])?);
+        fs::write(source_repo.join("hello.txt"), "again")?;
+        git(&source_repo, &["commit", "-am", "Second commit"])?;
+        git(&source_repo, &["tag", "v1.0.0"])?;
+        let head = stdout(git(&source_repo, &["rev-parse", "HEAD"])?);
+        let docs_blob = stdout(git(&source_repo, &["rev-parse", "HEAD:docs/index.md"])?);
+        let repo_url = url::Url::from_file_path(&source_repo)
+            .map_err(|_| anyhow!("Invalid path"))?
+            .to_string();
+
+        // 2. Sparse checkouts only contain the requested directories and top-level files
+        let sparse_dirs = [PathBuf::from("addons/foo")];
+        let checked_out_path = git_client
+            .checkout(&repo_url, "v1.0.0", &sparse_dirs)
+            .await?;
+        assert!(checked_out_path.join("addons/foo/plugin.cfg").exists());
+        assert_eq!(
+            fs::read_to_string(checked_out_path.join("hello.txt"))?,
+            "again"
+        );
+        assert!(!checked_out_path.join("docs").exists());
+
+        // 3. Only the commit itself was fetched, without its history or the blobs of the
+        // files that weren't checked out
+        let mirror_dir = get_repo_dir(&config, &repo_url);
+        // Listing all objects only reads the mirror, while `cat-file -e` fetches missing ones
+        let objects = stdout(git(
+            &mirror_dir,
+            &["cat-file", "--batch-all-objects", "--batch-check"],
+        )?);
+        let has_object = |object: &str| objects.lines().any(|line| line.starts_with(object));
+        assert_eq!(
+            stdout(git(&mirror_dir, &["rev-parse", "--is-shallow-repository"])?),
+            "true"
+        );
+        assert!(has_object(&head));
+        assert!(!has_object(&first_commit));
+        assert!(!has_object(&docs_blob));
+
+        // 4. Abbreviated commit hashes are found in the history
+        let checked_out_path = git_client
+            .checkout(&repo_url, &first_commit[..10], &sparse_dirs)
+            .await?;
+        assert_eq!(
+            fs::read_to_string(checked_out_path.join("hello.txt"))?,
+            "world"
+        );
+        assert!(!checked_out_path.join("docs").exists());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_system_git_client_worktrees() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = SystemGitClient::new(config.clone());
+
+        // 1. Create a repository with two tagged commits
+        let source_repo = tmp_dir.path().join("source_repo");
+        git_client.init(&source_repo, Some("main")).await?;
+        let git = |dir: &Path, args: &[&str]| -> Result<String> {
+            let output = Command::new("git")
+                .current_dir(dir)
+                .args(["-c", "user.email=test@example.com", "-c", "user.name=test"])
+                .args(args)
+                .output()?;
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        fs::create_dir_all(source_repo.join("addons/foo"))?;
+        fs::write(source_repo.join("addons/foo/plugin.cfg"), "[plugin]")?;
+        fs::write(source_repo.join("version.txt"), "1")?;
+        git(&source_repo, &["add", "."])?;
+        git(&source_repo, &["commit", "-m", "Version 1"])?;
+        git(&source_repo, &["tag", "v1.0.0"])?;
+        let first_commit = git(&source_repo, &["rev-parse", "HEAD"])?;
+        fs::write(source_repo.join("version.txt"), "2")?;
+        git(&source_repo, &["commit", "-am", "Version 2"])?;
+        git(&source_repo, &["tag", "v2.0.0"])?;
+        let repo_url = url::Url::from_file_path(&source_repo)
+            .map_err(|_| anyhow!("Invalid path"))?
+            .to_string();
+
+        // 2. Each commit is checked out in a worktree of its own, and both stay usable
+        let v1 = git_client.checkout(&repo_url, "v1.0.0", &[]).await?;
+        let v2 = git_client.checkout(&repo_url, "v2.0.0", &[]).await?;
+        assert_ne!(v1, v2);
+        assert_eq!(fs::read_to_string(v1.join("version.txt"))?, "1");
+        assert_eq!(fs::read_to_string(v2.join("version.txt"))?, "2");
+        assert!(v1.starts_with(get_worktrees_dir(&config, &repo_url)));
+        assert_eq!(fs::read_to_string(last_used_file(&v1))?, first_commit);
+
+        // 3. Checking out a commit again, by any name, reuses its worktree, while a sparse
+        // checkout of the same commit gets another one
+        assert_eq!(git_client.checkout(&repo_url, "v1.0.0", &[]).await?, v1);
+        assert_eq!(
+            git_client.checkout(&repo_url, &first_commit, &[]).await?,
+            v1
+        );
+        assert_eq!(git_client.checkout(&repo_url, "main", &[]).await?, v2);
+        let sparse = git_client
+            .checkout(&repo_url, "v1.0.0", &[PathBuf::from("addons/foo")])
+            .await?;
+        assert_ne!(sparse, v1);
+
+        // 4. The worktrees share a single bare mirror in the git cache
+        let mirror_dir = get_repo_dir(&config, &repo_url);
+        assert_eq!(
+            git(&mirror_dir, &["rev-parse", "--is-bare-repository"])?,
+            "true"
+        );
+        let mirrors: Vec<_> = fs::read_dir(&config.git_cache_dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name() != WORKTREES_DIR)
+            .collect();
+        assert_eq!(mirrors.len(), 1);
+        let worktrees = git(&mirror_dir, &["worktree", "list", "--porcelain"])?;
+        for worktree in [&v1, &v2, &sparse] {
+            assert!(worktree.join(".git").is_file());
+            let line = format!("worktree {}", fs::canonicalize(worktree)?.display());
+            assert!(worktrees.lines().any(|l| l == line), "{worktrees}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_uses_lfs() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        fs::create_dir_all(tmp_dir.path().join("addons/foo"))?;
+        fs::write(tmp_dir.path().join(".gitattributes"), "*.sh text eol=lf\n")?;
+        assert!(!uses_lfs(tmp_dir.path()));
+        fs::write(
+            tmp_dir.path().join("addons/foo/.gitattributes"),
+            "*.png filter=lfs diff=lfs merge=lfs -text\n",
+        )?;
+        assert!(uses_lfs(tmp_dir.path()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sparse_checkout_dirs() {
+        let subdir = Some(Path::new("addons/foo"));
+        let include = |patterns: &[&str]| -> Vec<String> {
+            patterns.iter().map(|pattern| pattern.to_string()).collect()
+        };
+        assert!(sparse_checkout_dirs(None, None).is_empty());
+        assert!(sparse_checkout_dirs(Some(Path::new(".")), None).is_empty());
+        assert!(sparse_checkout_dirs(None, Some(&include(&["*.gd"]))).is_empty());
+        assert_eq!(
+            sparse_checkout_dirs(subdir, None),
+            vec![PathBuf::from("addons/foo")]
+        );
+        assert_eq!(
+            sparse_checkout_dirs(subdir, Some(&include(&["scripts", "!*.md"]))),
+            vec![PathBuf::from("addons/foo")]
+        );
+        assert_eq!(
+            sparse_checkout_dirs(
+                None,
+                Some(&include(&[
+                    "addons/gdUnit4/",
+                    "addons/gdUnit4/src/**/*.gd",
+                    "/addons/tools/icon.svg",
+                    "!addons/gdUnit4/test",
+                ]))
+            ),
+            vec![
+                PathBuf::from("addons/gdUnit4"),
+                PathBuf::from("addons/tools")
+            ]
+        );
+        assert_eq!(
+            sparse_checkout_dirs(subdir, Some(&include(&["src/*/*.gd", "docs/**/*.md"]))),
+            vec![
+                PathBuf::from("addons/foo/docs"),
+                PathBuf::from("addons/foo/src")
+            ]
+        );
+    }
 }
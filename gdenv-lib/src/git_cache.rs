@@ -0,0 +1,296 @@
+//! Lists and prunes the git cache, which has a bare mirror of every addon repository and
+//! the worktrees its commits are checked out in.
+
+use crate::config::Config;
+use crate::git::{WORKTREES_DIR, last_used_file, lock_repo_dir};
+use anyhow::{Context, Result};
+use git2::Repository;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+/// The mirror of a repository in the git cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedRepository {
+    /// `None` if the mirror is broken, e.g. because creating it was interrupted.
+    pub url: Option<String>,
+    pub mirror_dir: PathBuf,
+    /// Size of the mirror in bytes, without its worktrees.
+    pub size: u64,
+    pub worktrees: Vec<CachedWorktree>,
+}
+
+/// A worktree that a commit of a cached repository is checked out in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedWorktree {
+    pub dir: PathBuf,
+    pub commit: Option<String>,
+    pub size: u64,
+    pub last_used: Option<SystemTime>,
+}
+
+/// What [prune_git_cache] removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrunedGitCache {
+    pub worktrees: usize,
+    pub repositories: usize,
+    /// Checkouts made by versions of gdenv from before the git cache had mirrors.
+    pub old_checkouts: usize,
+    /// Bytes freed.
+    pub size: u64,
+}
+
+/// The repositories in the git cache, sorted by URL.
+pub async fn list_git_cache(config: &Config) -> Result<Vec<CachedRepository>> {
+    let mut repositories = Vec::new();
+    for mirror_dir in cache_entries(config)?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|extension| extension == "git"))
+    {
+        // Read with libgit2, since the `git` command may not be installed
+        let url = Repository::open_bare(&mirror_dir).ok().and_then(|repo| {
+            let remote = repo.find_remote("origin").ok()?;
+            remote.url().map(str::to_string)
+        });
+
+        let mut worktrees = Vec::new();
+        for dir in worktree_dirs(config, &mirror_dir)? {
+            let last_used_file = last_used_file(&dir);
+            worktrees.push(CachedWorktree {
+                size: dir_size(&dir),
+                commit: fs::read_to_string(&last_used_file)
+                    .ok()
+                    .filter(|commit| !commit.is_empty()),
+                last_used: fs::metadata(&last_used_file)
+                    .and_then(|metadata| metadata.modified())
+                    .ok(),
+                dir,
+            });
+        }
+
+        repositories.push(CachedRepository {
+            url,
+            size: dir_size(&mirror_dir),
+            mirror_dir,
+            worktrees,
+        });
+    }
+    repositories.sort_by(|a, b| a.url.cmp(&b.url));
+    Ok(repositories)
+}
+
+/// Removes the worktrees that weren't used for `unused_for`, and the checkouts of older
+/// versions of gdenv. Mirrors are kept, since they may be needed offline, unless
+/// `unused_for` is `None`, in which case the whole git cache is removed.
+pub async fn prune_git_cache(
+    config: &Config,
+    unused_for: Option<Duration>,
+) -> Result<PrunedGitCache> {
+    let mut pruned = PrunedGitCache::default();
+    let now = SystemTime::now();
+    for repository in list_git_cache(config).await? {
+        let _lock = match &repository.url {
+            Some(url) => Some(lock_repo_dir(config, url).await?),
+            None => None,
+        };
+        let is_unused = |worktree: &CachedWorktree| match unused_for {
+            Some(unused_for) => worktree.last_used.is_none_or(|last_used| {
+                now.duration_since(last_used).unwrap_or_default() >= unused_for
+            }),
+            None => true,
+        };
+        for worktree in repository
+            .worktrees
+            .iter()
+            .filter(|worktree| is_unused(worktree))
+        {
+            tracing::debug!("Removing worktree {}", worktree.dir.display());
+            remove_dir(&worktree.dir)?;
+            let _ = fs::remove_file(last_used_file(&worktree.dir));
+            pruned.worktrees += 1;
+            pruned.size += worktree.size;
+        }
+
+        if unused_for.is_some() {
+            prune_worktree_metadata(&repository.mirror_dir);
+            continue;
+        }
+        tracing::debug!("Removing mirror {}", repository.mirror_dir.display());
+        remove_dir(&repository.mirror_dir)?;
+        let worktrees_dir = repository_worktrees_dir(config, &repository.mirror_dir);
+        if worktrees_dir.exists() {
+            pruned.size += dir_size(&worktrees_dir);
+            remove_dir(&worktrees_dir)?;
+        }
+        pruned.repositories += 1;
+        pruned.size += repository.size;
+    }
+
+    for old_checkout in cache_entries(config)?.into_iter().filter(|path| {
+        path.extension().is_none_or(|extension| extension != "git")
+            && path.file_name().is_some_and(|name| name != WORKTREES_DIR)
+    }) {
+        tracing::debug!("Removing old checkout {}", old_checkout.display());
+        pruned.size += dir_size(&old_checkout);
+        remove_dir(&old_checkout)?;
+        pruned.old_checkouts += 1;
+    }
+    Ok(pruned)
+}
+
+/// Makes the mirror forget about the worktrees that were removed, like `git worktree prune`.
+/// Checkouts of the built-in git client aren't worktrees of the mirror, so there's nothing to
+/// forget about for those.
+fn prune_worktree_metadata(mirror_dir: &Path) {
+    let Ok(repo) = Repository::open_bare(mirror_dir) else {
+        return;
+    };
+    for name in repo.worktrees().iter().flatten().flatten() {
+        if let Ok(worktree) = repo.find_worktree(name)
+            && worktree.validate().is_err()
+            && let Err(error) = worktree.prune(None)
+        {
+            tracing::debug!("Failed to prune worktree {}: {}", name, error);
+        }
+    }
+}
+
+/// The directories at the top of the git cache.
+fn cache_entries(config: &Config) -> Result<Vec<PathBuf>> {
+    if !config.git_cache_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(&config.git_cache_dir)
+        .context(format!("Failed to read {}", config.git_cache_dir.display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() {
+            entries.push(path);
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+fn repository_worktrees_dir(config: &Config, mirror_dir: &Path) -> PathBuf {
+    let name = mirror_dir.file_stem().unwrap_or_default();
+    config.git_cache_dir.join(WORKTREES_DIR).join(name)
+}
+
+/// The complete worktrees of the repository of `mirror_dir`, leaving out any that are
+/// still being checked out.
+fn worktree_dirs(config: &Config, mirror_dir: &Path) -> Result<Vec<PathBuf>> {
+    let worktrees_dir = repository_worktrees_dir(config, mirror_dir);
+    if !worktrees_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(&worktrees_dir)? {
+        let path = entry?.path();
+        if path.is_dir() && path.extension().is_none() {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+pub(crate) fn dir_size(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn remove_dir(dir: &Path) -> Result<()> {
+    fs::remove_dir_all(dir).context(format!("Failed to remove {}", dir.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{GitClient, SystemGitClient};
+    use std::process::Command;
+
+    #[tokio::test]
+    async fn test_list_and_prune_git_cache() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let git_client = SystemGitClient::new(config.clone());
+
+        let source_repo = tmp_dir.path().join("source_repo");
+        fs::create_dir_all(&source_repo)?;
+        git_client.init(&source_repo, Some("main")).await?;
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .current_dir(&source_repo)
+                .args(["-c", "user.email=test@example.com", "-c", "user.name=test"])
+                .args(args)
+                .output()
+        };
+        fs::write(source_repo.join("version.txt"), "1")?;
+        git(&["add", "."])?;
+        git(&["commit", "-m", "Version 1"])?;
+        git(&["tag", "v1"])?;
+        fs::write(source_repo.join("version.txt"), "2")?;
+        git(&["commit", "-am", "Version 2"])?;
+        git(&["tag", "v2"])?;
+        fs::create_dir_all(config.git_cache_dir.join("old_checkout"))?;
+
+        // Both revisions get a worktree of their own, which stays as it is
+        let repo_url = source_repo.to_str().unwrap();
+        let v1 = git_client.checkout(repo_url, "v1", &[]).await?;
+        let v2 = git_client.checkout(repo_url, "v2", &[]).await?;
+        assert_ne!(v1, v2);
+        assert_eq!(fs::read_to_string(v1.join("version.txt"))?, "1");
+        assert_eq!(fs::read_to_string(v2.join("version.txt"))?, "2");
+        assert_eq!(git_client.checkout(repo_url, "v1", &[]).await?, v1);
+
+        let repositories = list_git_cache(&config).await?;
+        assert_eq!(repositories.len(), 1);
+        assert_eq!(repositories[0].url.as_deref(), Some(repo_url));
+        let worktree_dirs: Vec<&Path> = repositories[0]
+            .worktrees
+            .iter()
+            .map(|worktree| worktree.dir.as_path())
+            .collect();
+        assert_eq!(worktree_dirs.len(), 2);
+        assert!(worktree_dirs.contains(&v1.as_path()) && worktree_dirs.contains(&v2.as_path()));
+        assert!(repositories[0].worktrees.iter().all(|worktree| {
+            worktree
+                .commit
+                .as_ref()
+                .is_some_and(|commit| commit.len() == 40)
+                && worktree.last_used.is_some()
+        }));
+
+        // Recently used worktrees are kept
+        let pruned = prune_git_cache(&config, Some(Duration::from_secs(3600))).await?;
+        assert_eq!(pruned.worktrees, 0);
+        assert_eq!(pruned.old_checkouts, 1);
+        assert!(!config.git_cache_dir.join("old_checkout").exists());
+
+        let pruned = prune_git_cache(&config, Some(Duration::ZERO)).await?;
+        assert_eq!(pruned.worktrees, 2);
+        assert!(!v1.exists() && !v2.exists());
+        assert_eq!(list_git_cache(&config).await?[0].worktrees, Vec::new());
+
+        // The mirror still has the commits, to check them out again
+        assert_eq!(git_client.checkout(repo_url, "v1", &[]).await?, v1);
+        assert_eq!(fs::read_to_string(v1.join("version.txt"))?, "1");
+
+        let pruned = prune_git_cache(&config, None).await?;
+        assert_eq!((pruned.worktrees, pruned.repositories), (1, 1));
+        assert!(pruned.size > 0);
+        assert!(list_git_cache(&config).await?.is_empty());
+        Ok(())
+    }
+}
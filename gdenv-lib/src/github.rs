@@ -1,6 +1,7 @@
 use crate::config::Config;
 use crate::download_client::DownloadClient;
-use crate::godot::get_platform_patterns;
+use crate::file_lock::FileLock;
+use crate::godot::{get_platform_patterns, is_server_build_asset};
 use crate::godot_version::GodotVersion;
 use crate::logging::{progress_bar_style, spinner_style};
 use anyhow::{Context, Result, bail};
@@ -16,10 +17,36 @@ use tracing_indicatif::span_ext::IndicatifSpanExt;
 
 pub const CACHE_VALIDITY_DAYS: u64 = 7;
 
+/// Operating systems and architectures gdenv checks release availability for.
+pub const SUPPORTED_PLATFORMS: &[(&str, &str)] = &[
+    ("linux", "x86_64"),
+    ("linux", "x86"),
+    ("linux", "arm"),
+    ("linux", "aarch64"),
+    ("windows", "x86_64"),
+    ("windows", "x86"),
+    ("windows", "aarch64"),
+    ("macos", "x86_64"),
+    ("macos", "aarch64"),
+];
+
+/// A single GitHub release. Standard and .NET builds share the same release,
+/// so `version.is_dotnet` is always false.
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct GitHubRelease {
     pub version: GodotVersion,
     pub assets: Vec<GitHubAsset>,
+    /// The platforms and variants this release provides a Godot build for.
+    #[serde(default)]
+    pub platforms: Vec<ReleasePlatform>,
+}
+
+/// A platform and variant (standard or .NET) that a release provides a Godot build for.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ReleasePlatform {
+    pub os: String,
+    pub arch: String,
+    pub is_dotnet: bool,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
@@ -45,6 +72,42 @@ struct GitHubAssetJson {
 }
 
 impl GitHubRelease {
+    pub fn new(version: GodotVersion, assets: Vec<GitHubAsset>) -> Self {
+        let mut release = Self {
+            version,
+            assets,
+            platforms: Vec::new(),
+        };
+        release.platforms = release.detect_platforms();
+        release
+    }
+
+    /// Whether this release provides a Godot build of the variant for `os` and `arch`.
+    pub fn provides(&self, is_dotnet: bool, os: &str, arch: &str) -> bool {
+        if !SUPPORTED_PLATFORMS.contains(&(os, arch)) {
+            return self.find_godot_asset(is_dotnet, os, arch).is_ok();
+        }
+        self.platforms
+            .iter()
+            .any(|p| p.is_dotnet == is_dotnet && p.os == os && p.arch == arch)
+    }
+
+    fn detect_platforms(&self) -> Vec<ReleasePlatform> {
+        [false, true]
+            .into_iter()
+            .flat_map(|is_dotnet| {
+                SUPPORTED_PLATFORMS
+                    .iter()
+                    .filter(move |(os, arch)| self.find_godot_asset(is_dotnet, os, arch).is_ok())
+                    .map(move |(os, arch)| ReleasePlatform {
+                        os: os.to_string(),
+                        arch: arch.to_string(),
+                        is_dotnet,
+                    })
+            })
+            .collect()
+    }
+
     /// Find a Godot asset for the current platform
     pub fn find_godot_asset(&self, is_dotnet: bool, os: &str, arch: &str) -> Result<&GitHubAsset> {
         if self.assets.is_empty() {
@@ -62,21 +125,39 @@ impl GitHubRelease {
                 let has_mono = name.contains("mono");
                 let is_zip = name.ends_with(".zip");
 
-                has_platform && has_godot && is_zip && (is_dotnet == has_mono)
+                has_platform
+                    && has_godot
+                    && is_zip
+                    && (is_dotnet == has_mono)
+                    && !is_server_build_asset(&name)
             }) {
                 return Ok(asset);
             }
         }
 
-        let os = std::env::consts::OS;
-        let arch = std::env::consts::ARCH;
         bail!(
-            "No matching Godot asset found for the current platform: OS={}, ARCH={}",
+            "No matching Godot asset found for the platform: OS={}, ARCH={}",
             os,
             arch
         );
     }
 
+    /// Find the export templates asset. Export templates are shared by all platforms.
+    pub fn find_export_templates_asset(&self, is_dotnet: bool) -> Result<&GitHubAsset> {
+        self.assets
+            .iter()
+            .find(|asset| {
+                let name = asset.name.to_lowercase();
+                name.contains("export_templates")
+                    && name.ends_with(".tpz")
+                    && (is_dotnet == name.contains("mono"))
+            })
+            .context(format!(
+                "No export templates found for Godot {}",
+                self.version
+            ))
+    }
+
     fn from_json_struct(json: &GitHubReleaseJson) -> Result<Self> {
         let version =
             GodotVersion::new(&json.tag_name, false).context("Failed to parse Godot version")?;
@@ -89,7 +170,7 @@ impl GitHubRelease {
                 size: a.size,
             })
             .collect();
-        Ok(GitHubRelease { version, assets })
+        Ok(GitHubRelease::new(version, assets))
     }
 }
 
@@ -116,9 +197,11 @@ impl DownloadClient for GitHubClient {
                 .context("Failed to load releases cache. Use `gdenv godot fetch` to refresh it.");
         }
 
-        let cache_exists = cache_file.exists();
         let new_releases = self.fetch_releases_from_api(partial_fetch).await?;
 
+        let _cache_lock = self.lock_cache().await?;
+        let cache_exists = cache_file.exists();
+
         let mut all_releases = if cache_exists && partial_fetch {
             self.merge_with_cache(new_releases, &cache_file)?
         } else {
@@ -259,15 +342,7 @@ impl GitHubClient {
 
         for json in releases {
             match GitHubRelease::from_json_struct(&json) {
-                Ok(release) => {
-                    // Add the standard version
-                    all_releases.push(release.clone());
-
-                    // Add the .NET version
-                    let mut dotnet_release = release;
-                    dotnet_release.version.is_dotnet = true;
-                    all_releases.push(dotnet_release);
-                }
+                Ok(release) => all_releases.push(release),
                 Err(e) => {
                     tracing::error!(
                         "Warn: Failed to parse release from GitHub API response; this release will be unavailable to download: {}, reason: {}",
@@ -293,6 +368,34 @@ impl GitHubClient {
         None
     }
 
+    /// Adds `releases` to the releases cache, keeping the releases that are already cached.
+    pub async fn import_releases(&self, releases: Vec<GitHubRelease>) -> Result<()> {
+        let cache_file = self.config.cache_dir.join("releases_cache.json");
+        let _cache_lock = self.lock_cache().await?;
+
+        let releases = upgrade_releases(releases);
+        let mut all_releases = if cache_file.exists() {
+            self.merge_with_cache(releases, &cache_file)?
+        } else {
+            releases
+        };
+        all_releases.sort();
+        all_releases.dedup_by(|a, b| a.version == b.version);
+
+        self.save_cache(&cache_file, &all_releases)
+            .context("Failed to save releases cache")
+    }
+
+    /// Hold this lock while reading and rewriting the cache, so that concurrent
+    /// gdenv processes don't lose each other's releases.
+    async fn lock_cache(&self) -> Result<FileLock> {
+        FileLock::acquire(
+            &self.config.locks_dir.join("releases_cache.lock"),
+            "updating the releases cache",
+        )
+        .await
+    }
+
     /// Merge the fetched releases with the cached ones, ensuring no duplicates
     fn merge_with_cache(
         &self,
@@ -333,7 +436,8 @@ impl GitHubClient {
 
     fn load_cache(&self, path: &Path) -> Result<Vec<GitHubRelease>> {
         let content = std::fs::read_to_string(path)?;
-        let mut releases: Vec<GitHubRelease> = serde_json::from_str(&content)?;
+        let releases: Vec<GitHubRelease> = serde_json::from_str(&content)?;
+        let mut releases = upgrade_releases(releases);
         releases.sort();
 
         Ok(releases)
@@ -344,11 +448,30 @@ impl GitHubClient {
             std::fs::create_dir_all(parent)?;
         }
         let content = serde_json::to_string_pretty(releases)?;
-        std::fs::write(path, content)?;
+        // Write to a temporary file first, so readers never see a partially written cache.
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(&tmp_path, path)?;
         Ok(())
     }
 }
 
+/// Caches written by older gdenv versions list every release twice, once as a .NET version,
+/// and don't record the platforms. Drops the duplicates and detects the platforms.
+fn upgrade_releases(releases: Vec<GitHubRelease>) -> Vec<GitHubRelease> {
+    releases
+        .into_iter()
+        .filter(|release| !release.version.is_dotnet)
+        .map(|release| {
+            if release.platforms.is_empty() {
+                GitHubRelease::new(release.version, release.assets)
+            } else {
+                release
+            }
+        })
+        .collect()
+}
+
 impl Ord for GitHubRelease {
     fn cmp(&self, other: &Self) -> Ordering {
         self.version.cmp(&other.version)
@@ -425,6 +548,193 @@ mod tests {
         assert!(dotnet_asset.is_ok());
         let dotnet_asset = dotnet_asset?;
         assert!(dotnet_asset.name.to_lowercase().contains("mono"));
+
+        // Test finding an asset for another platform
+        let windows_asset = release.find_godot_asset(false, "windows", "x86_64")?;
+        assert_eq!(windows_asset.name, "Godot_v4.2.1-stable_win64.exe.zip");
+        Ok(())
+    }
+
+    fn release_with_assets(tag_name: &str, asset_names: &[&str]) -> Result<GitHubRelease> {
+        GitHubRelease::from_json_struct(&GitHubReleaseJson {
+            tag_name: tag_name.to_string(),
+            assets: asset_names
+                .iter()
+                .map(|name| GitHubAssetJson {
+                    name: name.to_string(),
+                    browser_download_url: format!("https://example.com/{name}"),
+                    size: 1000,
+                })
+                .collect(),
+        })
+    }
+
+    #[test]
+    fn test_find_godot3_asset() -> Result<()> {
+        // The assets of the 3.5.3-stable release
+        let release = release_with_assets(
+            "3.5.3-stable",
+            &[
+                "Godot_v3.5.3-stable_export_templates.tpz",
+                "Godot_v3.5.3-stable_linux_headless.64.zip",
+                "Godot_v3.5.3-stable_linux_server.64.zip",
+                "Godot_v3.5.3-stable_mono_export_templates.tpz",
+                "Godot_v3.5.3-stable_mono_linux_headless_64.zip",
+                "Godot_v3.5.3-stable_mono_linux_server_64.zip",
+                "Godot_v3.5.3-stable_mono_osx.universal.zip",
+                "Godot_v3.5.3-stable_mono_win32.zip",
+                "Godot_v3.5.3-stable_mono_win64.zip",
+                "Godot_v3.5.3-stable_mono_x11_32.zip",
+                "Godot_v3.5.3-stable_mono_x11_64.zip",
+                "Godot_v3.5.3-stable_osx.universal.zip",
+                "Godot_v3.5.3-stable_win32.exe.zip",
+                "Godot_v3.5.3-stable_win64.exe.zip",
+                "Godot_v3.5.3-stable_x11.32.zip",
+                "Godot_v3.5.3-stable_x11.64.zip",
+                "godot-3.5.3-stable.tar.xz",
+                "godot-3.5.3-stable.tar.xz.sha256",
+            ],
+        )?;
+
+        let cases = [
+            (false, "linux", "x86_64", "Godot_v3.5.3-stable_x11.64.zip"),
+            (
+                true,
+                "linux",
+                "x86_64",
+                "Godot_v3.5.3-stable_mono_x11_64.zip",
+            ),
+            (false, "linux", "x86", "Godot_v3.5.3-stable_x11.32.zip"),
+            (true, "linux", "x86", "Godot_v3.5.3-stable_mono_x11_32.zip"),
+            (false, "linux", "aarch64", "Godot_v3.5.3-stable_x11.64.zip"),
+            (
+                false,
+                "windows",
+                "x86_64",
+                "Godot_v3.5.3-stable_win64.exe.zip",
+            ),
+            (
+                true,
+                "windows",
+                "x86_64",
+                "Godot_v3.5.3-stable_mono_win64.zip",
+            ),
+            (false, "windows", "x86", "Godot_v3.5.3-stable_win32.exe.zip"),
+            (
+                false,
+                "macos",
+                "aarch64",
+                "Godot_v3.5.3-stable_osx.universal.zip",
+            ),
+            (
+                true,
+                "macos",
+                "x86_64",
+                "Godot_v3.5.3-stable_mono_osx.universal.zip",
+            ),
+        ];
+        for (is_dotnet, os, arch, expected) in cases {
+            assert_eq!(
+                release.find_godot_asset(is_dotnet, os, arch)?.name,
+                expected,
+                "dotnet={is_dotnet}, os={os}, arch={arch}"
+            );
+        }
+
+        // Headless and server builds can't run the editor, so they're never picked
+        assert!(release.find_godot_asset(false, "linux", "arm").is_err());
+
+        // Releases before 3.3 have no universal macOS builds
+        let release = release_with_assets(
+            "3.2.3-stable",
+            &[
+                "Godot_v3.2.3-stable_mono_osx.64.zip",
+                "Godot_v3.2.3-stable_mono_x11_64.zip",
+                "Godot_v3.2.3-stable_osx.64.zip",
+                "Godot_v3.2.3-stable_x11.64.zip",
+            ],
+        )?;
+        assert_eq!(
+            release.find_godot_asset(false, "macos", "x86_64")?.name,
+            "Godot_v3.2.3-stable_osx.64.zip"
+        );
+        assert_eq!(
+            release.find_godot_asset(true, "macos", "x86_64")?.name,
+            "Godot_v3.2.3-stable_mono_osx.64.zip"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_release_platforms() -> Result<()> {
+        // Early 4.0 prereleases shipped without .NET builds
+        let release = release_with_assets(
+            "4.0-alpha1",
+            &[
+                "Godot_v4.0-alpha1_linux.64.zip",
+                "Godot_v4.0-alpha1_macos.universal.zip",
+                "Godot_v4.0-alpha1_win64.exe.zip",
+            ],
+        )?;
+        assert!(release.provides(false, "linux", "x86_64"));
+        assert!(release.provides(false, "macos", "aarch64"));
+        assert!(!release.provides(true, "linux", "x86_64"));
+        assert!(!release.provides(true, "windows", "x86_64"));
+
+        let release = release_with_assets(
+            "4.2.1-stable",
+            &[
+                "Godot_v4.2.1-stable_linux.x86_64.zip",
+                "Godot_v4.2.1-stable_mono_linux_x86_64.zip",
+                "Godot_v4.2.1-stable_win64.exe.zip",
+            ],
+        )?;
+        assert!(release.provides(true, "linux", "x86_64"));
+        assert!(!release.provides(true, "windows", "x86_64"));
+        assert!(!release.provides(false, "macos", "aarch64"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_releases() -> Result<()> {
+        // Caches of older gdenv versions duplicate each release as a .NET version
+        // and have no platforms
+        let standard = GitHubRelease {
+            platforms: Vec::new(),
+            ..release_with_assets("4.2.1-stable", &["Godot_v4.2.1-stable_win64.exe.zip"])?
+        };
+        let dotnet = GitHubRelease {
+            version: GodotVersion::new("4.2.1-stable", true)?,
+            ..standard.clone()
+        };
+
+        let upgraded = upgrade_releases(vec![standard, dotnet]);
+        assert_eq!(upgraded.len(), 1);
+        assert!(!upgraded[0].version.is_dotnet);
+        assert!(upgraded[0].provides(false, "windows", "x86_64"));
+        assert!(!upgraded[0].provides(true, "windows", "x86_64"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_export_templates_asset() -> Result<()> {
+        let release = release_with_assets(
+            "4.2.1-stable",
+            &[
+                "Godot_v4.2.1-stable_linux.x86_64.zip",
+                "Godot_v4.2.1-stable_export_templates.tpz",
+                "Godot_v4.2.1-stable_mono_export_templates.tpz",
+            ],
+        )?;
+
+        assert_eq!(
+            release.find_export_templates_asset(false)?.name,
+            "Godot_v4.2.1-stable_export_templates.tpz"
+        );
+        assert_eq!(
+            release.find_export_templates_asset(true)?.name,
+            "Godot_v4.2.1-stable_mono_export_templates.tpz"
+        );
         Ok(())
     }
 
@@ -468,18 +778,9 @@ mod tests {
         let v_normal = GodotVersion::new("4.2.1", false)?;
         let v_dotnet = GodotVersion::new("4.2.1", true)?;
 
-        let r1 = GitHubRelease {
-            version: v_normal.clone(),
-            assets: vec![],
-        };
-        let r2 = GitHubRelease {
-            version: v_dotnet.clone(),
-            assets: vec![],
-        };
-        let r3 = GitHubRelease {
-            version: v_normal.clone(),
-            assets: vec![],
-        };
+        let r1 = GitHubRelease::new(v_normal.clone(), vec![]);
+        let r2 = GitHubRelease::new(v_dotnet.clone(), vec![]);
+        let r3 = GitHubRelease::new(v_normal.clone(), vec![]);
 
         let mut releases = vec![r1, r2, r3];
         releases.sort();
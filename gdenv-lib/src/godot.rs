@@ -17,18 +17,50 @@ pub fn platform_suffix(os: &str, arch: &str) -> &'static str {
     }
 }
 
-/// Get platform patterns for asset matching, in order of preference
+/// Get the platform suffix Godot 3.x builds use for the given OS and architecture
+pub fn legacy_platform_suffix(os: &str, arch: &str) -> &'static str {
+    match (os, arch) {
+        ("windows", "x86") => "win32.exe",
+        ("windows", _) => "win64.exe",
+        ("macos", _) => "osx.universal",
+        ("linux", "x86") => "x11.32",
+        _ => "x11.64",
+    }
+}
+
+/// Get the platform suffix of the builds of `version` for the given OS and architecture
+fn version_platform_suffix(version: &GodotVersion, os: &str, arch: &str) -> &'static str {
+    if version.major < 4 {
+        legacy_platform_suffix(os, arch)
+    } else {
+        platform_suffix(os, arch)
+    }
+}
+
+/// .NET builds are archived in a folder named after the platform suffix, with dots replaced by
+/// underscores and without the `.exe` extension (e.g. `mono_linux_x86_64`, `mono_x11_64`, `mono_win64`).
+fn dotnet_folder_suffix(platform_suffix: &str) -> String {
+    platform_suffix.trim_end_matches(".exe").replace('.', "_")
+}
+
+/// Get platform patterns for asset matching, in order of preference.
+/// Patterns cover both the Godot 4 names (e.g. `linux.x86_64`, `macos.universal`) and
+/// the Godot 3 names (e.g. `x11.64`, `x11_64`, `osx.universal`, `osx.64`).
 pub fn get_platform_patterns(os: &str, arch: &str) -> Vec<&'static str> {
     match (os, arch) {
         ("windows", "x86_64") => vec!["win64"],
         ("windows", "x86") => vec!["win32", "win64"], // Fallback to 64-bit if 32-bit not available
-        ("macos", _) => vec!["macos"],                // macOS universal binaries
-        ("linux", "x86_64") => vec!["linux.x86_64", "linux_x86_64", "linux"], // Prefer specific, fallback to generic
+        ("macos", _) => vec!["macos", "osx.universal", "osx.64"], // macOS universal binaries, then 3.x Intel builds
+        ("linux", "x86_64") => vec!["linux.x86_64", "linux_x86_64", "x11.64", "x11_64", "linux"], // Prefer specific, fallback to generic
         ("linux", "x86") => vec![
             "linux.x86_32",
             "linux_x86_32",
+            "x11.32",
+            "x11_32",
             "linux.x86_64",
             "linux_x86_64",
+            "x11.64",
+            "x11_64",
             "linux",
         ],
         ("linux", "arm") => vec![
@@ -43,15 +75,23 @@ pub fn get_platform_patterns(os: &str, arch: &str) -> Vec<&'static str> {
             "linux_arm64",
             "linux.x86_64",
             "linux_x86_64",
+            "x11.64",
+            "x11_64",
             "linux",
         ], // ARM64 preferred
         // Fallbacks
         ("windows", _) => vec!["win64", "win32"],
-        ("linux", _) => vec!["linux.x86_64", "linux"],
-        _ => vec!["linux.x86_64", "linux"], // Ultimate fallback
+        ("linux", _) => vec!["linux.x86_64", "x11.64", "x11_64", "linux"],
+        _ => vec!["linux.x86_64", "x11.64", "x11_64", "linux"], // Ultimate fallback
     }
 }
 
+/// Whether the asset is a Godot 3.x headless or server build, which can't run the editor.
+pub fn is_server_build_asset(asset_name: &str) -> bool {
+    let name = asset_name.to_lowercase();
+    name.contains("headless") || name.contains("server")
+}
+
 /// Get the expected executable path within the extracted directory
 pub fn extracted_godot_executable_path(version: &GodotVersion, os: &str, arch: &str) -> String {
     match os {
@@ -62,23 +102,15 @@ pub fn extracted_godot_executable_path(version: &GodotVersion, os: &str, arch: &
                 "Godot.app/Contents/MacOS/Godot".to_string()
             }
         }
-        "windows" => {
+        "windows" | "linux" => {
             let version_part = version.as_godot_version_str();
-            if version.is_dotnet {
-                format!(
-                    "Godot_v{}_mono_{}/Godot_v{}_mono_{}.exe",
-                    version_part, "win64", version_part, "win64"
-                )
-            } else {
-                format!("Godot_v{}_{}.exe", version_part, "win64")
-            }
-        }
-        "linux" => {
-            let version_part = version.as_godot_version_str();
-            let platform_suffix = platform_suffix(os, arch);
+            let platform_suffix = version_platform_suffix(version, os, arch);
             if version.is_dotnet {
                 // Dotnet versions extract to a subfolder
-                let folder_name = format!("Godot_v{version_part}_mono_{platform_suffix}");
+                let folder_name = format!(
+                    "Godot_v{version_part}_mono_{}",
+                    dotnet_folder_suffix(platform_suffix)
+                );
                 let exe_name = format!("Godot_v{version_part}_mono_{platform_suffix}");
                 format!("{folder_name}/{exe_name}")
             } else {
@@ -102,11 +134,15 @@ pub fn godot_installation_name(version: &GodotVersion) -> String {
 }
 
 pub fn godot_archive_name(version: &GodotVersion) -> String {
-    let platform_suffix = platform_suffix(std::env::consts::OS, std::env::consts::ARCH);
+    let os = std::env::consts::OS;
+    let platform_suffix = version_platform_suffix(version, os, std::env::consts::ARCH);
     let version_part = version.as_godot_version_str();
 
-    if version.is_dotnet {
+    if version.is_dotnet && os == "macos" {
         format!("Godot_v{version_part}_mono_{platform_suffix}.zip")
+    } else if version.is_dotnet {
+        let folder_suffix = dotnet_folder_suffix(platform_suffix);
+        format!("Godot_v{version_part}_mono_{folder_suffix}.zip")
     } else {
         format!("Godot_v{version_part}_{platform_suffix}.zip")
     }
@@ -207,6 +243,76 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_executable_paths_for_platforms() -> Result<()> {
+        let godot3 = GodotVersion::new("3.5.3", false)?;
+        let godot3_dotnet = GodotVersion::new("3.5.3", true)?;
+        let godot4 = GodotVersion::new("4.2.1", false)?;
+        let godot4_dotnet = GodotVersion::new("4.2.1", true)?;
+
+        let cases = [
+            (&godot3, "linux", "x86_64", "Godot_v3.5.3-stable_x11.64"),
+            (
+                &godot3_dotnet,
+                "linux",
+                "x86_64",
+                "Godot_v3.5.3-stable_mono_x11_64/Godot_v3.5.3-stable_mono_x11.64",
+            ),
+            (&godot3, "linux", "x86", "Godot_v3.5.3-stable_x11.32"),
+            (
+                &godot3,
+                "windows",
+                "x86_64",
+                "Godot_v3.5.3-stable_win64.exe",
+            ),
+            (
+                &godot3_dotnet,
+                "windows",
+                "x86_64",
+                "Godot_v3.5.3-stable_mono_win64/Godot_v3.5.3-stable_mono_win64.exe",
+            ),
+            (
+                &godot3,
+                "macos",
+                "aarch64",
+                "Godot.app/Contents/MacOS/Godot",
+            ),
+            (
+                &godot3_dotnet,
+                "macos",
+                "x86_64",
+                "Godot_mono.app/Contents/MacOS/Godot",
+            ),
+            (
+                &godot4,
+                "linux",
+                "x86_64",
+                "Godot_v4.2.1-stable_linux.x86_64",
+            ),
+            (
+                &godot4_dotnet,
+                "linux",
+                "x86_64",
+                "Godot_v4.2.1-stable_mono_linux_x86_64/Godot_v4.2.1-stable_mono_linux.x86_64",
+            ),
+            (&godot4, "windows", "x86", "Godot_v4.2.1-stable_win32.exe"),
+            (
+                &godot4_dotnet,
+                "windows",
+                "x86_64",
+                "Godot_v4.2.1-stable_mono_win64/Godot_v4.2.1-stable_mono_win64.exe",
+            ),
+        ];
+        for (version, os, arch, expected) in cases {
+            assert_eq!(
+                extracted_godot_executable_path(version, os, arch),
+                expected,
+                "version={version}, os={os}, arch={arch}"
+            );
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_installation_name() -> Result<()> {
         // Test .NET versions
@@ -1,8 +1,11 @@
 use crate::download_client::DownloadClient;
+use crate::file_lock::FileLock;
+use crate::github::{GitHubAsset, GitHubRelease};
 use crate::godot::{extracted_godot_executable_path, godot_installation_name};
-use crate::logging::spinner_style;
+use crate::logging::{count_progress_bar_style, spinner_style};
 use crate::{config::Config, godot_version::GodotVersion};
-use anyhow::{Result, anyhow, bail};
+use anyhow::{Context, Result, anyhow, bail};
+use futures_util::StreamExt;
 use std::fs;
 use std::path::{Path, PathBuf};
 use tracing::instrument;
@@ -19,27 +22,285 @@ pub async fn ensure_installed<D: DownloadClient>(
         return get_executable_path(config, version);
     }
 
+    let _install_lock = lock_installation(config, version).await?;
+
+    // Another gdenv process may have installed this version while we waited for the lock.
+    if !force && list_installed(config)?.contains(version) {
+        return get_executable_path(config, version);
+    }
+
     // 1. Fetch releases
     let releases = download_client.godot_releases(false, false).await?;
 
     // 2. Find release & asset
-    let release = releases
-        .iter()
-        .find(|r| r.version == *version)
-        .ok_or_else(|| anyhow!("Godot Version '{}' not found in available releases. The cache may be out of date. Use `gdenv godot fetch` to find new releases.", version))?;
-
-    let asset = release.find_godot_asset(version.is_dotnet, &config.os, &config.arch)?;
+    let asset = find_release_asset(config, &releases, version)?;
 
     // 3. Download to cache
-    let cache_path = config.cache_dir.join(&asset.name);
-    if !cache_path.exists() {
-        download_client.download_asset(asset, &cache_path).await?;
-    }
+    let cache_path = download_to_cache(config, asset, download_client).await?;
 
     // 4. Install
     install_version_from_archive(config, version, &cache_path).await
 }
 
+/// Downloads the Godot archive of `version` built for `os` and `arch` into the download cache,
+/// without installing it. The platform doesn't need to match the current machine.
+/// Returns the path to the cached archive.
+pub async fn download_godot_archive<D: DownloadClient>(
+    config: &Config,
+    version: &GodotVersion,
+    download_client: &D,
+    os: &str,
+    arch: &str,
+) -> Result<PathBuf> {
+    let releases = download_client.godot_releases(false, false).await?;
+    check_available(&releases, version, os, arch)?;
+    let asset = find_release(&releases, version)?.find_godot_asset(version.is_dotnet, os, arch)?;
+    download_to_cache(config, asset, download_client).await
+}
+
+/// Downloads the export templates of `version` into the download cache.
+/// Returns the path to the cached templates archive.
+pub async fn download_export_templates<D: DownloadClient>(
+    config: &Config,
+    version: &GodotVersion,
+    download_client: &D,
+) -> Result<PathBuf> {
+    let releases = download_client.godot_releases(false, false).await?;
+    let asset = find_release(&releases, version)?.find_export_templates_asset(version.is_dotnet)?;
+    download_to_cache(config, asset, download_client).await
+}
+
+/// Downloads `asset` into the download cache, unless it's already there.
+/// The download is written to a temporary file first, so an interrupted download
+/// or a concurrent gdenv process never leaves a partial archive in the cache.
+async fn download_to_cache<D: DownloadClient>(
+    config: &Config,
+    asset: &GitHubAsset,
+    download_client: &D,
+) -> Result<PathBuf> {
+    let cache_path = config.cache_dir.join(&asset.name);
+    if cache_path.exists() {
+        return Ok(cache_path);
+    }
+
+    let partial_path = config
+        .cache_dir
+        .join(format!("{}.part-{}", asset.name, std::process::id()));
+    let result = download_client.download_asset(asset, &partial_path).await;
+    let result = result.and_then(|()| {
+        fs::rename(&partial_path, &cache_path).context(format!(
+            "Failed to move download into cache: {}",
+            cache_path.display()
+        ))
+    });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial_path);
+        return Err(e);
+    }
+    Ok(cache_path)
+}
+
+/// The result of installing a single version with [ensure_installed_all].
+#[derive(Debug)]
+pub struct InstallOutcome {
+    pub version: GodotVersion,
+    /// Path to the installed Godot executable, or the reason the installation failed.
+    pub result: Result<PathBuf>,
+}
+
+/// Installs several Godot versions in one go.
+/// Archives are downloaded concurrently, at most `max_concurrent_downloads` at a time,
+/// while extraction and installation happen one version at a time.
+/// A failure to install one version does not prevent the others from being installed.
+/// Outcomes are returned in the same order as `versions`, without duplicates.
+pub async fn ensure_installed_all<D: DownloadClient>(
+    config: &Config,
+    versions: &[GodotVersion],
+    download_client: &D,
+    force: bool,
+    max_concurrent_downloads: usize,
+) -> Result<Vec<InstallOutcome>> {
+    let mut unique_versions: Vec<GodotVersion> = Vec::new();
+    for version in versions {
+        if !unique_versions.contains(version) {
+            unique_versions.push(version.clone());
+        }
+    }
+
+    let mut results: Vec<Option<Result<PathBuf>>> = unique_versions.iter().map(|_| None).collect();
+    let installed = list_installed(config)?;
+    for (index, version) in unique_versions.iter().enumerate() {
+        if !force && installed.contains(version) {
+            results[index] = Some(get_executable_path(config, version));
+        }
+    }
+
+    // Lock in a stable order, so that two processes installing overlapping sets of versions
+    // can't each end up waiting on a lock the other one holds.
+    let mut versions_to_lock: Vec<&GodotVersion> = unique_versions
+        .iter()
+        .zip(&results)
+        .filter(|(_, result)| result.is_none())
+        .map(|(version, _)| version)
+        .collect();
+    versions_to_lock.sort_by_key(|version| godot_installation_name(version));
+    let mut install_locks = Vec::new();
+    for version in versions_to_lock {
+        install_locks.push(lock_installation(config, version).await?);
+    }
+
+    // Another gdenv process may have installed some of the versions while we waited for the locks.
+    let installed = list_installed(config)?;
+    for (index, version) in unique_versions.iter().enumerate() {
+        if results[index].is_none() && !force && installed.contains(version) {
+            results[index] = Some(get_executable_path(config, version));
+        }
+    }
+
+    if results.iter().any(Option::is_none) {
+        // 1. Fetch releases
+        let releases = download_client.godot_releases(false, false).await?;
+
+        // 2. Find release & asset for every version that still needs to be installed
+        let mut downloads = Vec::new();
+        for (index, version) in unique_versions.iter().enumerate() {
+            if results[index].is_some() {
+                continue;
+            }
+            match find_release_asset(config, &releases, version) {
+                Ok(asset) => downloads.push(PendingDownload {
+                    index,
+                    asset: asset.clone(),
+                }),
+                Err(e) => results[index] = Some(Err(e)),
+            }
+        }
+
+        // 3. Download to cache, concurrently
+        let download_results = download_archives(
+            config,
+            download_client,
+            &downloads,
+            max_concurrent_downloads,
+        )
+        .await?;
+
+        // 4. Install, one version at a time
+        for (download, download_result) in downloads.iter().zip(download_results) {
+            let version = &unique_versions[download.index];
+            results[download.index] = Some(match download_result {
+                Ok(cache_path) => install_version_from_archive(config, version, &cache_path).await,
+                Err(e) => Err(e),
+            });
+        }
+    }
+
+    Ok(unique_versions
+        .into_iter()
+        .zip(results)
+        .map(|(version, result)| InstallOutcome {
+            version,
+            result: result.unwrap_or_else(|| Err(anyhow!("Installation was not attempted"))),
+        })
+        .collect())
+}
+
+struct PendingDownload {
+    /// Index of the version this download belongs to.
+    index: usize,
+    asset: GitHubAsset,
+}
+
+/// Downloads the archives that are not already cached.
+/// Returns the cached archive path for each download, in the same order as `downloads`.
+#[instrument(skip_all)]
+async fn download_archives<D: DownloadClient>(
+    config: &Config,
+    download_client: &D,
+    downloads: &[PendingDownload],
+    max_concurrent_downloads: usize,
+) -> Result<Vec<Result<PathBuf>>> {
+    let current_span = tracing::Span::current();
+    current_span.pb_set_style(&count_progress_bar_style()?);
+    current_span.pb_set_length(downloads.len() as u64);
+    current_span.pb_set_message("Downloading Godot archives...");
+    current_span.pb_set_finish_message("Downloading Godot archives... Complete!");
+
+    let mut results: Vec<(usize, Result<PathBuf>)> =
+        futures_util::stream::iter(downloads.iter().enumerate())
+            .map(|(position, download)| {
+                let current_span = current_span.clone();
+                async move {
+                    let result = download_to_cache(config, &download.asset, download_client).await;
+                    current_span.pb_inc(1);
+                    (position, result)
+                }
+            })
+            .buffer_unordered(max_concurrent_downloads.max(1))
+            .collect()
+            .await;
+
+    results.sort_by_key(|(position, _)| *position);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+/// Prevents other gdenv processes from downloading or installing the same version at the same time.
+async fn lock_installation(config: &Config, version: &GodotVersion) -> Result<FileLock> {
+    let installation_name = godot_installation_name(version);
+    FileLock::acquire(
+        &config.locks_dir.join(format!("{installation_name}.lock")),
+        &format!("installing Godot {version}"),
+    )
+    .await
+}
+
+fn find_release_asset<'a>(
+    config: &Config,
+    releases: &'a [GitHubRelease],
+    version: &GodotVersion,
+) -> Result<&'a GitHubAsset> {
+    check_available(releases, version, &config.os, &config.arch)?;
+    find_release(releases, version)?.find_godot_asset(version.is_dotnet, &config.os, &config.arch)
+}
+
+/// Checks that a build of `version` exists for `os` and `arch`, without downloading anything.
+/// For .NET versions, the release must ship a .NET build for the platform.
+pub fn check_available(
+    releases: &[GitHubRelease],
+    version: &GodotVersion,
+    os: &str,
+    arch: &str,
+) -> Result<()> {
+    let release = find_release(releases, version)?;
+    if release.provides(version.is_dotnet, os, arch) {
+        return Ok(());
+    }
+    if version.is_dotnet && release.provides(false, os, arch) {
+        bail!(
+            "Godot {} has no .NET build for {}/{}. Install it without --dotnet instead.",
+            release.version,
+            os,
+            arch
+        );
+    }
+    bail!("Godot {} is not available for {}/{}", version, os, arch);
+}
+
+/// Finds the release of `version`. Standard and .NET versions share the same release.
+fn find_release<'a>(
+    releases: &'a [GitHubRelease],
+    version: &GodotVersion,
+) -> Result<&'a GitHubRelease> {
+    let release_version = GodotVersion {
+        is_dotnet: false,
+        ..version.clone()
+    };
+    releases
+        .iter()
+        .find(|r| r.version == release_version)
+        .ok_or_else(|| anyhow!("Godot Version '{}' not found in available releases. The cache may be out of date. Use `gdenv godot fetch` to find new releases.", version))
+}
+
 /// Returns the path to the installed Godot executable.
 #[instrument(skip_all)]
 pub async fn install_version_from_archive(
@@ -74,7 +335,7 @@ pub async fn install_version_from_archive(
     find_godot_executable(&install_path, version, &config.os, &config.arch)
 }
 
-fn extract_zip(archive_path: &Path, destination: &Path) -> Result<()> {
+pub(crate) fn extract_zip(archive_path: &Path, destination: &Path) -> Result<()> {
     let file = fs::File::open(archive_path)?;
     let mut archive = zip::ZipArchive::new(file)?;
 
@@ -194,9 +455,12 @@ fn find_godot_executable(
     #[cfg(target_os = "macos")]
     {
         // On macOS, the executable is inside Godot.app/Contents/MacOS/Godot
-        let godot_app_exe = install_path.join("Godot.app/Contents/MacOS/Godot");
-        if godot_app_exe.exists() {
-            return Ok(godot_app_exe);
+        // (Godot_mono.app for .NET builds)
+        for app_name in ["Godot.app", "Godot_mono.app"] {
+            let godot_app_exe = install_path.join(app_name).join("Contents/MacOS/Godot");
+            if godot_app_exe.exists() {
+                return Ok(godot_app_exe);
+            }
         }
     }
 
@@ -209,6 +473,7 @@ fn find_godot_executable(
             if let Some(name) = path.file_name().and_then(|n| n.to_str())
                 && name.starts_with("Godot")
                 && name.ends_with(".exe")
+                && !name.ends_with("_console.exe")
             {
                 return Ok(path.into());
             }
@@ -361,6 +626,53 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_check_available_rejects_missing_dotnet_build() -> Result<()> {
+        let releases = MockDownloadClient.godot_releases(false, false).await?;
+        let standard = GodotVersion::new("4.2.1", false)?;
+        let dotnet = GodotVersion::new("4.2.1", true)?;
+
+        check_available(&releases, &standard, "linux", "x86_64")?;
+        check_available(&releases, &standard, "windows", "x86_64")?;
+        // The mock release doesn't ship any .NET build
+        let error = check_available(&releases, &dotnet, "linux", "x86_64")
+            .expect_err("A .NET build must not be available");
+        assert!(error.to_string().contains(".NET"), "{error}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_install_multiple_versions() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let config = Config::setup(Some(tmp_dir.path()))?;
+        let config = Config {
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            ..config
+        };
+        let client = MockDownloadClient;
+        let available = GodotVersion::new("4.2.1", false)?;
+        let unavailable = GodotVersion::new("9.9.9", false)?;
+
+        let outcomes = ensure_installed_all(
+            &config,
+            &[available.clone(), unavailable.clone(), available.clone()],
+            &client,
+            false,
+            2,
+        )
+        .await?;
+
+        // Duplicates are dropped and the order of the request is preserved
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0].version, available);
+        assert!(outcomes[0].result.is_ok());
+        assert_eq!(outcomes[1].version, unavailable);
+        assert!(outcomes[1].result.is_err());
+        assert_eq!(list_installed(&config)?, vec![available]);
+        Ok(())
+    }
+
     #[test]
     fn test_update_symlink_create_new() -> Result<()> {
         let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
@@ -1,15 +1,26 @@
 #[cfg(test)]
 pub mod test_helpers;
 
+pub mod addon_dependencies;
+pub mod addon_updates;
+pub mod addon_watcher;
 pub mod addons;
 pub mod api;
+pub mod archive;
+pub mod asset_library;
+pub mod builtin_git;
+pub mod bundle;
 pub mod cargo;
 pub mod command_runner;
 pub mod config;
+pub mod config_file;
 pub mod download_client;
+pub mod editor_plugins;
+pub mod file_lock;
 pub mod file_sync;
 pub mod gdextension_config;
 pub mod git;
+pub mod git_cache;
 pub mod github;
 pub mod godot;
 pub mod godot_version;
@@ -18,3 +29,6 @@ pub mod logging;
 pub mod migrate;
 pub mod path_extension;
 pub mod project_specification;
+pub mod spec_editor;
+pub mod sync_manifest;
+pub mod sync_snapshot;
@@ -24,6 +24,16 @@ pub fn progress_bar_style() -> Result<ProgressStyle> {
     Ok(style)
 }
 
+pub fn count_progress_bar_style() -> Result<ProgressStyle> {
+    let style = ProgressStyle::with_template(
+        "{spinner:.green} [{elapsed_precise}] {msg} [{wide_bar:.cyan/blue}] {pos}/{len}",
+    )?;
+    let style = style
+        .progress_chars("#>-")
+        .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏", "⠿"]);
+    Ok(style)
+}
+
 pub fn spinner_style(template: &str) -> Result<ProgressStyle> {
     let style = ProgressStyle::with_template(&format!(
         "{{spinner:.green}} [{{elapsed_precise}}] {}",
@@ -150,31 +150,56 @@ pub struct SpecRustGdExtension {
 /// [addon.<name>]
 #[derive(Serialize, Deserialize, Documented, DocumentedFieldsOpt, Debug, Eq, PartialEq, Clone)]
 pub struct AddonSpec {
-    /// # Paths to include from the addon's source directory. Optional.
-    /// #include = []
-    pub include: Option<Vec<PathBuf>>,
-
-    /// # Paths to exclude from the addon's source directory. Optional.
-    /// #exclude = []
-    pub exclude: Option<Vec<PathBuf>>,
+    /// # Paths to include from the addon's source directory, as gitignore-style patterns.
+    /// # Defaults to everything. Optional.
+    /// #  - `*` and `?` match within a path component, `**` matches any number of components.
+    /// #  - Patterns without a `/` (other than a trailing one) match at any depth, e.g. "*.md".
+    /// #    Other patterns are relative to the source directory, e.g. "/docs" or "addons/foo/**/*.gd".
+    /// #  - A trailing `/` only matches directories. Matching a directory also matches its
+    /// #    contents, unless a pattern matches them directly.
+    /// #  - A leading `!` negates a pattern. The last pattern that matches a path wins.
+    /// #    Use e.g. "!dir/**" to negate a directory's contents.
+    /// #include = ["addons/foo/**/*.gd", "!addons/foo/examples/**"]
+    pub include: Option<Vec<String>>,
+
+    /// # Paths to exclude from the addon's source directory, as gitignore-style patterns
+    /// # (see `include`). Exclusion takes precedence over inclusion. Optional.
+    /// #exclude = ["**/*.md", "**/tests/**", "!README.md"]
+    pub exclude: Option<Vec<String>>,
 
     /// # Path relative to project_dir to place addon files.
     /// # Defaults to <godot_project_dir>/addons/<addon_name>.
     /// #destination = "./custom/location"
     pub destination: Option<PathBuf>,
 
+    /// # Keep the `.import` and `.uid` files Godot generates next to the addon's files,
+    /// # removing them only when the file they belong to is removed from the addon. Optional.
+    /// #preserve_godot_files = true
+    pub preserve_godot_files: Option<bool>,
+
+    /// # Enable the editor plugins (`plugin.cfg` files) of the addon in `project.godot`
+    /// # after syncing it. Optional.
+    /// #enable_plugin = true
+    pub enable_plugin: Option<bool>,
+
     #[serde(flatten)]
     pub source: AddonSource,
 }
 
 /// # Addons can be sourced from one of the following options:
 /// #  - Git repository.
+/// #  - Godot Asset Library.
+/// #  - Archive URL.
 /// #  - Local directory.
 #[derive(Serialize, Deserialize, Documented, Debug, Eq, PartialEq, Clone)]
 #[serde(untagged, rename = "addon source type")]
 pub enum AddonSource {
     /// Addon sourced from a Git repository.
     Git(GitAddonSource),
+    /// Addon sourced from the Godot Asset Library.
+    AssetLibrary(AssetLibraryAddonSource),
+    /// Addon sourced from a zip or tar.gz archive.
+    Archive(ArchiveAddonSource),
     /// Addon sourced from a local directory.
     Local(LocalAddonSource),
 }
@@ -191,11 +216,52 @@ pub struct GitAddonSource {
     /// #rev = "main"
     pub rev: Option<String>,
 
+    /// # Semver requirement the addon's version must match, e.g. "^0.10" or ">=1.2, <2".
+    /// # Checks out the repository's newest tag that matches it. Tags may have a `v` prefix.
+    /// # Can't be combined with `rev`. Optional.
+    /// #version = "^0.10"
+    pub version: Option<String>,
+
     /// # Sub-directory, relative to the repository root, to source the addon files from. Optional.
     /// #subdir = ""
     pub subdir: Option<PathBuf>,
 }
 
+/// # -- Godot Asset Library specific addon fields:
+#[derive(Serialize, Deserialize, Documented, DocumentedFieldsOpt, Debug, Eq, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AssetLibraryAddonSource {
+    /// # Asset ID, as shown in the asset's URL on the Asset Library. Required.
+    /// #asset_id = 1234
+    pub asset_id: u64,
+
+    /// # Version of the asset, as shown on the Asset Library. Defaults to the latest version. Optional.
+    /// #version = "1.0.0"
+    pub version: Option<String>,
+
+    /// # Sub-directory, relative to the asset's top-level folder, to source the addon files from. Optional.
+    /// #subdir = "addons/my_addon"
+    pub subdir: Option<PathBuf>,
+}
+
+/// # -- Archive URL specific addon fields:
+#[derive(Serialize, Deserialize, Documented, DocumentedFieldsOpt, Debug, Eq, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ArchiveAddonSource {
+    /// # URL of a .zip or .tar.gz archive. `file://` URLs are supported. Required.
+    /// #url = "https://example.com/my_addon-1.0.0.zip"
+    pub url: String,
+
+    /// # SHA-256 checksum of the archive. The download is rejected if it doesn't match.
+    /// # Lets gdenv reuse the cached archive without downloading it again. Optional.
+    /// #sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    pub sha256: Option<String>,
+
+    /// # Path inside the archive to source the addon files from. Optional.
+    /// #strip_prefix = "my_addon-1.0.0/addons/my_addon"
+    pub strip_prefix: Option<PathBuf>,
+}
+
 /// # -- Local directory specific addon fields:
 #[derive(Serialize, Deserialize, Documented, DocumentedFieldsOpt, Debug, Eq, PartialEq, Clone)]
 #[serde(deny_unknown_fields)]
@@ -203,6 +269,13 @@ pub struct LocalAddonSource {
     /// # Path to the directory whose contents will be copied to the destination directory. Required.
     /// #path = "/path/to/local/addon"
     pub path: PathBuf,
+
+    /// # Link the destination directory to `path` instead of copying the files, so changes to the
+    /// # addon show up in the project right away. Creates a directory symlink, or a junction on
+    /// # Windows when symlinks aren't permitted. Can't be combined with `include` or `exclude`.
+    /// # Optional. Defaults to false.
+    /// #link = true
+    pub link: Option<bool>,
 }
 
 pub fn spec_documentation() -> Result<String> {
@@ -213,6 +286,8 @@ pub fn spec_documentation() -> Result<String> {
         struct_doc_f::<SpecRustGdExtension>(),
         struct_doc_f::<AddonSpec>(),
         struct_doc_f::<GitAddonSource>(),
+        struct_doc_f::<AssetLibraryAddonSource>(),
+        struct_doc_f::<ArchiveAddonSource>(),
         struct_doc_f::<LocalAddonSource>(),
     ];
     Ok(out.join("\n"))
@@ -263,6 +338,11 @@ pub fn load_godot_project_spec<P: CargoInfoProvider>(
                 file_path.display()
             ))?;
             let project_dir = spec.godot.project_dir.unwrap_or(PathBuf::from("."));
+            let addons = spec.addon.unwrap_or_default();
+            for (addon_name, addon_spec) in &addons {
+                validate_addon_spec(addon_name, addon_spec)
+                    .map_err(|e| ProjectSpecError::ParseError(file_path.clone(), e))?;
+            }
             Ok(ProjectSpecification {
                 project_root_dir: dir_path,
                 spec_file_path: Some(file_path.clone()),
@@ -280,7 +360,7 @@ pub fn load_godot_project_spec<P: CargoInfoProvider>(
                     &project_dir,
                     cargo_target_path_provider,
                 )?,
-                addons: spec.addon.unwrap_or_default(),
+                addons,
             })
         }
         SpecFileType::Version {
@@ -308,6 +388,34 @@ pub fn load_godot_project_spec<P: CargoInfoProvider>(
     }
 }
 
+/// Checks the settings of an addon that can't be expressed in its type, returning a message
+/// describing the first problem found.
+pub fn validate_addon_spec(addon_name: &str, addon_spec: &AddonSpec) -> Result<(), String> {
+    if let AddonSource::Git(git) = &addon_spec.source
+        && let Some(version) = &git.version
+    {
+        if git.rev.is_some() {
+            return Err(format!(
+                "Addon \"{addon_name}\" can't have both `rev` and `version`. Use `version` to pick the newest matching tag, or `rev` to pin a tag, branch or commit."
+            ));
+        }
+        if let Err(e) = semver::VersionReq::parse(version) {
+            return Err(format!(
+                "Addon \"{addon_name}\" has an invalid version requirement \"{version}\": {e}"
+            ));
+        }
+    }
+    if let AddonSource::Local(local) = &addon_spec.source
+        && local.link == Some(true)
+        && (addon_spec.include.is_some() || addon_spec.exclude.is_some())
+    {
+        return Err(format!(
+            "Addon \"{addon_name}\" can't combine `link = true` with `include` or `exclude`, because a linked addon always shows all of its files."
+        ));
+    }
+    Ok(())
+}
+
 fn gdextension_generator_to_config<P: CargoInfoProvider>(
     working_dir: &Path,
     generators: HashMap<String, SpecGdExtensionGenerator>,
@@ -481,9 +589,12 @@ path = "../local-project"
                         include: None,
                         exclude: None,
                         destination: None,
+                        preserve_godot_files: None,
+                        enable_plugin: None,
                         source: AddonSource::Git(GitAddonSource {
                             git: "https://github.com/dialogic-godot/dialogic".to_string(),
                             rev: Some("main".to_string()),
+                            version: None,
                             subdir: None,
                         }),
                     },
@@ -494,9 +605,12 @@ path = "../local-project"
                         include: None,
                         exclude: None,
                         destination: None,
+                        preserve_godot_files: None,
+                        enable_plugin: None,
                         source: AddonSource::Git(GitAddonSource {
                             git: "https://github.com/DragonAxe/gd-bvy-curtains".to_string(),
                             rev: Some("other_ref".to_string()),
+                            version: None,
                             subdir: None,
                         }),
                     },
@@ -504,12 +618,15 @@ path = "../local-project"
                 (
                     "gdunit4".to_string(),
                     AddonSpec {
-                        include: Some(vec![PathBuf::from("addons/gdUnit4")]),
+                        include: Some(vec!["addons/gdUnit4".to_string()]),
                         exclude: None,
                         destination: None,
+                        preserve_godot_files: None,
+                        enable_plugin: None,
                         source: AddonSource::Git(GitAddonSource {
                             git: "https://github.com/godot-gdunit-labs/gdUnit4".to_string(),
                             rev: None,
+                            version: None,
                             subdir: None,
                         }),
                     },
@@ -520,8 +637,11 @@ path = "../local-project"
                         include: None,
                         exclude: None,
                         destination: None,
+                        preserve_godot_files: None,
+                        enable_plugin: None,
                         source: AddonSource::Local(LocalAddonSource {
                             path: PathBuf::from("../local-project"),
+                            link: None,
                         }),
                     },
                 ),
@@ -558,6 +678,86 @@ version = "4.6.0"
         Ok(())
     }
 
+    #[test]
+    fn test_gdenv_toml_project_spec_linked_addon_with_filters() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let version_file = tmp_dir.path().join("gdenv.toml");
+        let str_spec = r#"
+[godot]
+version = "4.6.0"
+
+[addon.my-addon]
+path = "../my-addon"
+link = true
+exclude = ["*.md"]
+        "#;
+        fs::write(&version_file, str_spec)?;
+        let error =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))
+                .unwrap_err();
+        assert!(matches!(error, ProjectSpecError::ParseError(..)), "{error}");
+        assert!(error.to_string().contains("my-addon"), "{error}");
+
+        fs::write(&version_file, str_spec.replace("exclude = [\"*.md\"]", ""))?;
+        let spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+        assert!(matches!(
+            &spec.addons["my-addon"].source,
+            AddonSource::Local(LocalAddonSource {
+                link: Some(true),
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_gdenv_toml_project_spec_git_addon_version() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let version_file = tmp_dir.path().join("gdenv.toml");
+        let str_spec = r#"
+[godot]
+version = "4.6.0"
+
+[addon.my-addon]
+git = "https://github.com/example/my-addon.git"
+version = "^0.10"
+rev = "v0.10.0"
+        "#;
+        fs::write(&version_file, str_spec)?;
+        let error =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))
+                .unwrap_err();
+        assert!(matches!(error, ProjectSpecError::ParseError(..)), "{error}");
+        assert!(
+            error.to_string().contains("both `rev` and `version`"),
+            "{error}"
+        );
+
+        fs::write(
+            &version_file,
+            str_spec
+                .replace("rev = \"v0.10.0\"", "")
+                .replace("^0.10", "latest"),
+        )?;
+        let error =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))
+                .unwrap_err();
+        assert!(
+            error.to_string().contains("invalid version requirement"),
+            "{error}"
+        );
+
+        fs::write(&version_file, str_spec.replace("rev = \"v0.10.0\"", ""))?;
+        let spec =
+            load_godot_project_spec(tmp_dir.path(), |_| bail!("Test lambda not implemented."))?;
+        assert!(matches!(
+            &spec.addons["my-addon"].source,
+            AddonSource::Git(GitAddonSource { version: Some(version), rev: None, .. }) if version == "^0.10"
+        ));
+        Ok(())
+    }
+
     #[test]
     fn test_godot_version_file_full() -> Result<()> {
         let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
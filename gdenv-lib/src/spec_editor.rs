@@ -0,0 +1,180 @@
+//! Edits the addons of a `gdenv.toml` file in place, keeping its comments and formatting.
+
+use crate::project_specification::AddonSpec;
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+/// Adds an `[addon.<addon_name>]` table after the other tables of `spec_file`.
+pub fn add_addon(spec_file: &Path, addon_name: &str, addon_spec: &AddonSpec) -> Result<()> {
+    let mut document = load(spec_file)?;
+    let addon_table: DocumentMut = toml::to_string(addon_spec)?.parse()?;
+    let mut addon_table = addon_table.as_table().clone();
+    addon_table.decor_mut().set_prefix("\n");
+
+    let addons = document
+        .entry("addon")
+        .or_insert_with(|| {
+            let mut addons = Table::new();
+            addons.set_implicit(true);
+            Item::Table(addons)
+        })
+        .as_table_like_mut()
+        .context(format!("`addon` in {} isn't a table", spec_file.display()))?;
+    if addons.contains_key(addon_name) {
+        bail!(
+            "Addon \"{}\" is already in {}",
+            addon_name,
+            spec_file.display()
+        );
+    }
+    addons.insert(addon_name, Item::Table(addon_table));
+    save(spec_file, &document)
+}
+
+/// Removes the `[addon.<addon_name>]` table of `spec_file`.
+pub fn remove_addon(spec_file: &Path, addon_name: &str) -> Result<()> {
+    let mut document = load(spec_file)?;
+    let removed = document
+        .get_mut("addon")
+        .and_then(Item::as_table_like_mut)
+        .and_then(|addons| addons.remove(addon_name));
+    if removed.is_none() {
+        bail!("Addon \"{}\" isn't in {}", addon_name, spec_file.display());
+    }
+    save(spec_file, &document)
+}
+
+/// Sets the `rev` of the `[addon.<addon_name>]` table of `spec_file`, keeping any comment
+/// next to the previous one.
+pub fn set_addon_rev(spec_file: &Path, addon_name: &str, rev: &str) -> Result<()> {
+    let mut document = load(spec_file)?;
+    let Some(addon_item) = document
+        .get_mut("addon")
+        .and_then(Item::as_table_like_mut)
+        .and_then(|addons| addons.get_mut(addon_name))
+    else {
+        bail!("Addon \"{}\" isn't in {}", addon_name, spec_file.display());
+    };
+    let addon = addon_item
+        .as_table_like_mut()
+        .context(format!("Addon \"{addon_name}\" isn't a table"))?;
+    let mut new_rev = Value::from(rev);
+    let old_rev = addon.get("rev").and_then(Item::as_value);
+    let is_new = old_rev.is_none();
+    if let Some(old_rev) = old_rev {
+        *new_rev.decor_mut() = old_rev.decor().clone();
+    }
+    addon.insert("rev", Item::Value(new_rev));
+    if is_new && let Some(inline_table) = addon_item.as_inline_table_mut() {
+        inline_table.fmt();
+    }
+    save(spec_file, &document)
+}
+
+fn load(spec_file: &Path) -> Result<DocumentMut> {
+    fs::read_to_string(spec_file)?
+        .parse()
+        .context(format!("Failed to parse {}", spec_file.display()))
+}
+
+fn save(spec_file: &Path, document: &DocumentMut) -> Result<()> {
+    fs::write(spec_file, document.to_string())
+        .context(format!("Failed to write {}", spec_file.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project_specification::{AddonSource, GitAddonSource};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_add_and_remove_addons() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let spec_file = tmp_dir.path().join("gdenv.toml");
+        let spec = r#"# My project
+[godot]
+version = "4.6.0-stable" # Keep in sync with CI
+
+# Dialogues
+[addon.dialogue]
+path = "../dialogue"
+"#;
+        fs::write(&spec_file, spec)?;
+        let addon_spec = AddonSpec {
+            include: None,
+            exclude: None,
+            destination: None,
+            preserve_godot_files: None,
+            enable_plugin: None,
+            source: AddonSource::Git(GitAddonSource {
+                git: "https://github.com/example/state-machine.git".to_string(),
+                rev: Some("v1.2.0".to_string()),
+                version: None,
+                subdir: Some(PathBuf::from("addons/state_machine")),
+            }),
+        };
+
+        add_addon(&spec_file, "state_machine", &addon_spec)?;
+        let with_addon = format!(
+            r#"{spec}
+[addon.state_machine]
+git = "https://github.com/example/state-machine.git"
+rev = "v1.2.0"
+subdir = "addons/state_machine"
+"#
+        );
+        assert_eq!(fs::read_to_string(&spec_file)?, with_addon);
+        assert!(add_addon(&spec_file, "dialogue", &addon_spec).is_err());
+
+        remove_addon(&spec_file, "state_machine")?;
+        assert_eq!(fs::read_to_string(&spec_file)?, spec);
+        remove_addon(&spec_file, "dialogue")?;
+        assert_eq!(
+            fs::read_to_string(&spec_file)?,
+            "# My project\n[godot]\nversion = \"4.6.0-stable\" # Keep in sync with CI\n"
+        );
+        assert!(remove_addon(&spec_file, "dialogue").is_err());
+        assert!(set_addon_rev(&spec_file, "dialogue", "v1.0.0").is_err());
+
+        // Projects without addons yet
+        add_addon(&spec_file, "state_machine", &addon_spec)?;
+        assert!(
+            fs::read_to_string(&spec_file)?.ends_with(
+                "CI\n\n[addon.state_machine]\ngit = \"https://github.com/example/state-machine.git\"\nrev = \"v1.2.0\"\nsubdir = \"addons/state_machine\"\n"
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_addon_rev() -> Result<()> {
+        let tmp_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let spec_file = tmp_dir.path().join("gdenv.toml");
+        let spec = r#"[godot]
+version = "4.6.0-stable"
+
+[addon.dialogue]
+git = "https://github.com/example/dialogue.git"
+rev = "v0.10.0" # Newer versions need Godot 4.7
+subdir = "addons/dialogue"
+
+[addon]
+state_machine = { git = "https://github.com/example/state-machine.git" }
+"#;
+        fs::write(&spec_file, spec)?;
+
+        set_addon_rev(&spec_file, "dialogue", "v0.10.2")?;
+        set_addon_rev(&spec_file, "state_machine", "v2.0.0")?;
+        assert_eq!(
+            fs::read_to_string(&spec_file)?,
+            spec.replace("v0.10.0", "v0.10.2").replace(
+                "state-machine.git\" }",
+                "state-machine.git\", rev = \"v2.0.0\" }"
+            )
+        );
+        Ok(())
+    }
+}
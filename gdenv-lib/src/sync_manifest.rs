@@ -0,0 +1,235 @@
+//! Records the files of each synced addon, so that later syncs can tell
+//! which files were changed by hand since then, and which destinations
+//! no addon uses anymore.
+
+use crate::file_sync::{HashCache, SyncChange, SyncChangeKind, SyncPlan, godot_sidecar_asset};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Location of the manifest, relative to the project root directory.
+pub const SYNC_MANIFEST_FILE: &str = ".gdenv/sync-manifest.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncManifest {
+    /// Synced addons by destination directory, relative to the project root directory.
+    #[serde(default)]
+    pub destinations: BTreeMap<PathBuf, AddonManifest>,
+}
+
+/// The files an addon had in its destination directory right after it was synced.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddonManifest {
+    pub addon_name: String,
+    /// Whether the destination is a link to the addon's source directory. Linked addons
+    /// have no files of their own.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub link: bool,
+    /// SHA-256 hashes of the files, by path relative to the destination directory.
+    pub files: BTreeMap<PathBuf, String>,
+}
+
+impl SyncManifest {
+    /// Loads the project's manifest. A project that was never synced has an empty manifest.
+    pub fn load(project_root_dir: &Path) -> Result<Self> {
+        let path = project_root_dir.join(SYNC_MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path)?;
+        serde_json::from_str(&contents).context(format!(
+            "Failed to parse sync manifest {}. Delete it to start over.",
+            path.display()
+        ))
+    }
+
+    pub fn save(&self, project_root_dir: &Path) -> Result<()> {
+        let path = project_root_dir.join(SYNC_MANIFEST_FILE);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let partial_path = path.with_extension(format!("json.part-{}", std::process::id()));
+        fs::write(&partial_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&partial_path, &path)
+            .context(format!("Failed to write sync manifest {}", path.display()))
+    }
+
+    /// The manifest of the addon last synced to `destination`.
+    pub fn addon(&self, destination: &Path) -> Option<&AddonManifest> {
+        self.destinations.get(destination)
+    }
+}
+
+impl AddonManifest {
+    /// Hashes the files currently in `dest_dir`.
+    pub fn record(addon_name: &str, dest_dir: &Path, hashes: &HashCache) -> Result<Self> {
+        let mut rel_paths = Vec::new();
+        for entry in WalkDir::new(dest_dir).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            rel_paths.push(entry.path().strip_prefix(dest_dir)?.to_path_buf());
+        }
+        let paths: Vec<PathBuf> = rel_paths.iter().map(|p| dest_dir.join(p)).collect();
+        let files = rel_paths
+            .into_iter()
+            .zip(hashes.sha256_all(&paths)?)
+            .collect();
+        Ok(Self {
+            addon_name: addon_name.to_string(),
+            link: false,
+            files,
+        })
+    }
+
+    /// The manifest of an addon whose destination is a link.
+    pub fn linked(addon_name: &str) -> Self {
+        Self {
+            addon_name: addon_name.to_string(),
+            link: true,
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Plans removing the files this addon owns from `dest_dir`, along with their Godot sidecar
+    /// files and the directories that end up empty. Returns the plan and the files that are
+    /// kept because the addon doesn't own them. `dest_dir` itself isn't part of the plan.
+    pub fn removal_plan(&self, dest_dir: &Path) -> Result<(SyncPlan, Vec<PathBuf>)> {
+        let mut entries = Vec::new();
+        for entry in WalkDir::new(dest_dir).into_iter().filter_map(|e| e.ok()) {
+            let rel_path = entry.path().strip_prefix(dest_dir)?.to_path_buf();
+            if !rel_path.as_os_str().is_empty() {
+                entries.push((rel_path, entry.file_type().is_dir()));
+            }
+        }
+
+        let is_owned = |rel_path: &Path| {
+            self.files.contains_key(rel_path)
+                || godot_sidecar_asset(rel_path)
+                    .is_some_and(|asset| self.files.contains_key(&asset))
+        };
+        let kept: Vec<PathBuf> = entries
+            .iter()
+            .filter(|(rel_path, is_dir)| !is_dir && !is_owned(rel_path))
+            .map(|(rel_path, _)| rel_path.clone())
+            .collect();
+
+        let mut plan = SyncPlan::default();
+        for (rel_path, is_dir) in entries {
+            let is_removed = if is_dir {
+                !kept.iter().any(|k| k.starts_with(&rel_path))
+            } else {
+                is_owned(&rel_path)
+            };
+            if is_removed {
+                plan.removed.push(SyncChange {
+                    kind: SyncChangeKind::Removed,
+                    rel_path,
+                    is_dir,
+                });
+            }
+        }
+        // Children are removed before their parents
+        plan.removed.sort_by(|a, b| b.rel_path.cmp(&a.rel_path));
+        Ok((plan, kept))
+    }
+
+    /// Files that `plan` would overwrite or remove, but that were changed or added in
+    /// `dest_dir` since the last sync.
+    pub fn local_changes(
+        &self,
+        dest_dir: &Path,
+        plan: &SyncPlan,
+        hashes: &HashCache,
+    ) -> Result<Vec<PathBuf>> {
+        let rel_paths: Vec<&Path> = plan
+            .modified
+            .iter()
+            .chain(&plan.removed)
+            // Sidecar files are rewritten by Godot whenever it imports the addon
+            .filter(|change| {
+                !change.is_dir
+                    && dest_dir.join(&change.rel_path).is_file()
+                    && godot_sidecar_asset(&change.rel_path).is_none()
+            })
+            .map(|change| change.rel_path.as_path())
+            .collect();
+        let paths: Vec<PathBuf> = rel_paths.iter().map(|p| dest_dir.join(p)).collect();
+        let mut changed: Vec<PathBuf> = rel_paths
+            .into_iter()
+            .zip(hashes.sha256_all(&paths)?)
+            .filter(|(rel_path, hash)| self.files.get(*rel_path) != Some(hash))
+            .map(|(rel_path, _)| rel_path.to_path_buf())
+            .collect();
+        changed.sort();
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_sync::{plan_sync, sync_recursive};
+
+    #[test]
+    fn test_local_changes() -> Result<()> {
+        let test_data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data");
+        let project_dir = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let dest_dir = project_dir.path().join("addons/test-addon1");
+        let source_v1 = test_data_dir.join("test-addon1-repo/addons/test-addon1");
+        let source_v2 = test_data_dir.join("test-addon1v2-repo/addons/test-addon1");
+
+        let hashes = HashCache::default();
+        fs::create_dir_all(&dest_dir)?;
+        sync_recursive(&source_v1, &dest_dir, None, None)?;
+        let mut manifest = SyncManifest::default();
+        manifest.destinations.insert(
+            PathBuf::from("addons/test-addon1"),
+            AddonManifest::record("test-addon1", &dest_dir, &hashes)?,
+        );
+        manifest.save(project_dir.path())?;
+        let manifest = SyncManifest::load(project_dir.path())?;
+        let addon_manifest = manifest
+            .addon(Path::new("addons/test-addon1"))
+            .context("Manifest not found")?;
+        assert!(manifest.addon(Path::new("elsewhere")).is_none());
+
+        // Untouched files aren't reported
+        let plan = plan_sync(&source_v2, &dest_dir, None, None, false, &hashes)?;
+        assert!(
+            addon_manifest
+                .local_changes(&dest_dir, &plan, &hashes)?
+                .is_empty()
+        );
+
+        // Edited files that would be overwritten, and files added by hand, are reported
+        fs::write(dest_dir.join("changed_file.txt"), "A local patch")?;
+        fs::write(dest_dir.join("notes.txt"), "A file added by hand")?;
+        let plan = plan_sync(&source_v2, &dest_dir, None, None, false, &hashes)?;
+        assert_eq!(
+            addon_manifest.local_changes(&dest_dir, &plan, &hashes)?,
+            vec![
+                PathBuf::from("changed_file.txt"),
+                PathBuf::from("notes.txt")
+            ]
+        );
+
+        // Removing the addon keeps the files it doesn't own, and their directories
+        fs::write(dest_dir.join("plugin.cfg.import"), "[remap]")?;
+        let (plan, kept) = addon_manifest.removal_plan(&dest_dir)?;
+        assert_eq!(kept, vec![PathBuf::from("notes.txt")]);
+        let removed: Vec<&Path> = plan.removed.iter().map(|c| c.rel_path.as_path()).collect();
+        assert!(removed.contains(&Path::new("plugin.cfg.import")));
+        assert!(removed.contains(&Path::new("directory_changed_to_file.txt/file.txt")));
+        assert!(removed.contains(&Path::new("directory_changed_to_file.txt")));
+        assert!(!removed.contains(&Path::new("notes.txt")));
+        assert_eq!(
+            addon_manifest.local_changes(&dest_dir, &plan, &hashes)?,
+            vec![PathBuf::from("changed_file.txt")]
+        );
+        Ok(())
+    }
+}
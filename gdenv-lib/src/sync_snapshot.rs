@@ -0,0 +1,444 @@
+//! Keeps copies of the files that syncing addons overwrites or removes, so that a sync can be
+//! undone, e.g. after a misconfigured `destination` replaced a folder of the project.
+//!
+//! Each sync gets a snapshot in the data directory, which is only created once the sync is
+//! about to change a file. Old snapshots of a project are pruned by count and total size.
+
+use crate::config::Config;
+use crate::file_sync::{SyncPlan, copy_file, copy_link, remove_file_or_link};
+use crate::git_cache::dir_size;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use walkdir::WalkDir;
+
+/// Number of snapshots that are kept for each project.
+pub const MAX_SNAPSHOTS: usize = 10;
+
+/// Total size of the snapshots that are kept for each project, in bytes. The most recent
+/// snapshot is kept regardless of its size.
+pub const MAX_SNAPSHOTS_SIZE: u64 = 1024 * 1024 * 1024;
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const FILES_DIR: &str = "files";
+
+/// What a snapshot knows about a sync.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub project_root_dir: PathBuf,
+    pub created_at: DateTime<Utc>,
+    /// The files, directories and links as they were before the sync changed them.
+    pub saved: Vec<SavedEntry>,
+    /// The paths that didn't exist before the sync.
+    pub added: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedEntry {
+    pub path: PathBuf,
+    pub kind: SavedEntryKind,
+    /// Path of the copy of a file or link, relative to the snapshot directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copy: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SavedEntryKind {
+    File,
+    Dir,
+    Link,
+}
+
+/// The snapshot of a sync in progress. Saving the same path twice keeps the first version.
+#[derive(Debug)]
+pub struct SyncSnapshot {
+    snapshots_dir: PathBuf,
+    dir: PathBuf,
+    state: Mutex<SnapshotState>,
+}
+
+#[derive(Debug)]
+struct SnapshotState {
+    info: SnapshotInfo,
+    saved: HashSet<PathBuf>,
+    added: HashSet<PathBuf>,
+}
+
+impl SyncSnapshot {
+    pub fn new(config: &Config, project_root_dir: &Path) -> Result<Self> {
+        let created_at = Utc::now();
+        let name = format!(
+            "{}-{}",
+            created_at.format("%Y%m%d-%H%M%S%.9f"),
+            std::process::id()
+        );
+        Ok(Self {
+            snapshots_dir: config.sync_snapshots_dir.clone(),
+            dir: config.sync_snapshots_dir.join(name),
+            state: Mutex::new(SnapshotState {
+                info: SnapshotInfo {
+                    project_root_dir: std::path::absolute(project_root_dir)?,
+                    created_at,
+                    saved: Vec::new(),
+                    added: Vec::new(),
+                },
+                saved: HashSet::new(),
+                added: HashSet::new(),
+            }),
+        })
+    }
+
+    /// Saves what applying `plan` to `dest_dir` would overwrite or remove, and records what it
+    /// would add.
+    pub fn save_plan(&self, dest_dir: &Path, plan: &SyncPlan) -> Result<()> {
+        if plan.is_empty() {
+            return Ok(());
+        }
+        self.update(|state| {
+            self.save_entry(state, dest_dir, false)?;
+            for change in plan.removed.iter().chain(&plan.modified).chain(&plan.added) {
+                self.save_entry(state, &dest_dir.join(&change.rel_path), false)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Saves the file, link or directory at `path` along with everything in it.
+    pub fn save_path(&self, path: &Path) -> Result<()> {
+        self.update(|state| self.save_entry(state, path, true))
+    }
+
+    fn update(&self, save: impl FnOnce(&mut SnapshotState) -> Result<()>) -> Result<()> {
+        let mut state = self.state.lock().expect("Snapshot lock poisoned");
+        let is_new = !self.dir.exists();
+        save(&mut state)?;
+        if state.saved.is_empty() && state.added.is_empty() {
+            return Ok(());
+        }
+        write_info(&self.dir, &state.info)?;
+        if is_new {
+            prune_snapshots(
+                &self.snapshots_dir,
+                &state.info.project_root_dir,
+                MAX_SNAPSHOTS,
+                MAX_SNAPSHOTS_SIZE,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn save_entry(&self, state: &mut SnapshotState, path: &Path, recursive: bool) -> Result<()> {
+        let path = std::path::absolute(path)?;
+        if state.saved.contains(&path) || state.added.contains(&path) {
+            return Ok(());
+        }
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            // So undoing the sync also removes the directories created for the path
+            if let Some(parent) = path.parent()
+                && parent != state.info.project_root_dir
+                && fs::symlink_metadata(parent).is_err()
+            {
+                self.save_entry(state, parent, false)?;
+            }
+            state.added.insert(path.clone());
+            state.info.added.push(path);
+            return Ok(());
+        };
+        let info = &mut state.info;
+
+        let kind = if metadata.is_symlink() {
+            SavedEntryKind::Link
+        } else if metadata.is_dir() {
+            SavedEntryKind::Dir
+        } else {
+            SavedEntryKind::File
+        };
+        let copy = match kind {
+            SavedEntryKind::Dir => None,
+            _ => {
+                let copy = Path::new(FILES_DIR).join(info.saved.len().to_string());
+                let copy_path = self.dir.join(&copy);
+                fs::create_dir_all(copy_path.parent().unwrap_or(&self.dir))?;
+                if kind == SavedEntryKind::Link {
+                    copy_link(&path, &copy_path)?;
+                } else {
+                    copy_file(&path, &copy_path)?;
+                }
+                Some(copy)
+            }
+        };
+        info.saved.push(SavedEntry {
+            path: path.clone(),
+            kind,
+            copy,
+        });
+        state.saved.insert(path.clone());
+
+        if recursive && kind == SavedEntryKind::Dir {
+            for entry in WalkDir::new(&path).min_depth(1) {
+                self.save_entry(state, entry?.path(), false)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A snapshot in the data directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredSnapshot {
+    pub dir: PathBuf,
+    pub info: SnapshotInfo,
+    pub size: u64,
+}
+
+impl StoredSnapshot {
+    /// Puts back what the sync overwrote or removed, removes what it added, and then removes
+    /// the snapshot, so the next undo restores the snapshot before this one.
+    pub fn restore(&self) -> Result<()> {
+        // Children before their parents
+        let mut added = self.info.added.clone();
+        added.sort_by(|a, b| b.cmp(a));
+        for path in &added {
+            let Ok(metadata) = fs::symlink_metadata(path) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                // Files added since the sync are kept, along with their directories
+                if let Err(e) = fs::remove_dir(path) {
+                    tracing::debug!("Keeping directory {:?}: {}", path, e);
+                }
+            } else {
+                remove_file_or_link(path)?;
+            }
+        }
+
+        // Parents before their children
+        let mut saved = self.info.saved.clone();
+        saved.sort_by(|a, b| a.path.cmp(&b.path));
+        for entry in &saved {
+            let path = &entry.path;
+            let existing = fs::symlink_metadata(path).ok();
+            if let Some(metadata) = &existing {
+                let replace = match entry.kind {
+                    SavedEntryKind::Dir => !metadata.is_dir(),
+                    _ => true,
+                };
+                if replace {
+                    if metadata.is_dir() {
+                        fs::remove_dir_all(path)
+                            .context(format!("Failed to remove {}", path.display()))?;
+                    } else {
+                        remove_file_or_link(path)?;
+                    }
+                }
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            match (&entry.kind, &entry.copy) {
+                (SavedEntryKind::Dir, _) => fs::create_dir_all(path)?,
+                (SavedEntryKind::Link, Some(copy)) => copy_link(&self.dir.join(copy), path)?,
+                (SavedEntryKind::File, Some(copy)) => copy_file(&self.dir.join(copy), path)?,
+                (_, None) => {
+                    anyhow::bail!(
+                        "Snapshot {} has no copy of {}",
+                        self.dir.display(),
+                        path.display()
+                    )
+                }
+            }
+            tracing::debug!("Restored {:?}", path);
+        }
+
+        fs::remove_dir_all(&self.dir)
+            .context(format!("Failed to remove snapshot {}", self.dir.display()))
+    }
+}
+
+/// The snapshots in the data directory, most recent first.
+pub fn list_snapshots(config: &Config) -> Result<Vec<StoredSnapshot>> {
+    read_snapshots(&config.sync_snapshots_dir)
+}
+
+/// The most recent snapshot of a sync of the project.
+pub fn latest_snapshot(config: &Config, project_root_dir: &Path) -> Result<Option<StoredSnapshot>> {
+    let project_root_dir = std::path::absolute(project_root_dir)?;
+    Ok(list_snapshots(config)?
+        .into_iter()
+        .find(|snapshot| snapshot.info.project_root_dir == project_root_dir))
+}
+
+fn read_snapshots(snapshots_dir: &Path) -> Result<Vec<StoredSnapshot>> {
+    if !snapshots_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(snapshots_dir)? {
+        let dir = entry?.path();
+        let Some(info) = fs::read_to_string(dir.join(SNAPSHOT_FILE))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+        else {
+            tracing::debug!("Skipping unreadable snapshot {:?}", dir);
+            continue;
+        };
+        snapshots.push(StoredSnapshot {
+            size: dir_size(&dir),
+            dir,
+            info,
+        });
+    }
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.info.created_at));
+    Ok(snapshots)
+}
+
+fn write_info(dir: &Path, info: &SnapshotInfo) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(SNAPSHOT_FILE);
+    let partial_path = path.with_extension(format!("json.part-{}", std::process::id()));
+    fs::write(&partial_path, serde_json::to_string_pretty(info)?)?;
+    fs::rename(&partial_path, &path).context(format!("Failed to write snapshot {}", path.display()))
+}
+
+/// Removes the oldest snapshots of the project, keeping at most `max_count` of them, of at most
+/// `max_size` bytes in total. Returns the number of snapshots removed.
+fn prune_snapshots(
+    snapshots_dir: &Path,
+    project_root_dir: &Path,
+    max_count: usize,
+    max_size: u64,
+) -> Result<usize> {
+    let mut total_size = 0;
+    let mut removed = 0;
+    let snapshots = read_snapshots(snapshots_dir)?
+        .into_iter()
+        .filter(|snapshot| snapshot.info.project_root_dir == project_root_dir);
+    for (i, snapshot) in snapshots.enumerate() {
+        total_size += snapshot.size;
+        if i == 0 || (i < max_count && total_size <= max_size) {
+            continue;
+        }
+        tracing::debug!("Removing snapshot {:?}", snapshot.dir);
+        fs::remove_dir_all(&snapshot.dir).context(format!(
+            "Failed to remove snapshot {}",
+            snapshot.dir.display()
+        ))?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_sync::{HashCache, apply_sync_plan, plan_sync};
+
+    /// Syncs `source` to `dest` like a sync of the project would, saving to `snapshot` first.
+    fn sync(snapshot: &SyncSnapshot, source: &Path, dest: &Path) -> Result<()> {
+        let plan = plan_sync(source, dest, None, None, false, &HashCache::default())?;
+        snapshot.save_plan(dest, &plan)?;
+        fs::create_dir_all(dest)?;
+        apply_sync_plan(source, dest, &plan)
+    }
+
+    #[test]
+    fn test_restore_snapshot() -> Result<()> {
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let project = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let project_dir = project.path();
+        let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data/test-addon1-repo/addons/test-addon1");
+
+        // A misconfigured destination replaces a folder of the project
+        let scenes_dir = project_dir.join("scenes");
+        fs::create_dir_all(scenes_dir.join("levels"))?;
+        fs::write(scenes_dir.join("main.tscn"), "[gd_scene]")?;
+        fs::write(scenes_dir.join("levels/level1.tscn"), "[gd_scene]")?;
+        fs::write(scenes_dir.join("plugin.cfg"), "[plugin]\nname=\"Mine\"")?;
+        let snapshot = SyncSnapshot::new(&config, project_dir)?;
+        sync(&snapshot, &source, &scenes_dir)?;
+        sync(&snapshot, &source, &project_dir.join("addons/new"))?;
+        assert!(!scenes_dir.join("main.tscn").exists());
+        assert_ne!(
+            fs::read_to_string(scenes_dir.join("plugin.cfg"))?,
+            "[plugin]\nname=\"Mine\""
+        );
+
+        let latest = latest_snapshot(&config, project_dir)?.context("No snapshot")?;
+        assert_eq!(latest.dir, snapshot.dir);
+        assert!(latest.size > 0);
+        latest.restore()?;
+        assert_eq!(
+            fs::read_to_string(scenes_dir.join("main.tscn"))?,
+            "[gd_scene]"
+        );
+        assert!(scenes_dir.join("levels/level1.tscn").exists());
+        assert_eq!(
+            fs::read_to_string(scenes_dir.join("plugin.cfg"))?,
+            "[plugin]\nname=\"Mine\""
+        );
+        assert!(!scenes_dir.join("old_file.txt").exists());
+        assert!(!project_dir.join("addons").exists());
+
+        // A restored snapshot is gone, and a sync that changes nothing has none
+        assert_eq!(latest_snapshot(&config, project_dir)?, None);
+        let snapshot = SyncSnapshot::new(&config, project_dir)?;
+        sync(&snapshot, &source, &project_dir.join("addons/new"))?;
+        let snapshot = SyncSnapshot::new(&config, project_dir)?;
+        sync(&snapshot, &source, &project_dir.join("addons/new"))?;
+        assert_eq!(list_snapshots(&config)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_snapshots() -> Result<()> {
+        let tmp_data_dir = tempfile::Builder::new()
+            .prefix("gdenv-test-data-dir")
+            .tempdir()?;
+        let config = Config::setup(Some(tmp_data_dir.path()))?;
+        let project = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let other_project = tempfile::Builder::new().prefix("gdenv-test").tempdir()?;
+        let file = project.path().join("file.txt");
+        fs::write(&file, vec![b'x'; 1000])?;
+        let other_file = other_project.path().join("file.txt");
+        fs::write(&other_file, vec![b'x'; 1000])?;
+        SyncSnapshot::new(&config, other_project.path())?.save_path(&other_file)?;
+        for _ in 0..4 {
+            SyncSnapshot::new(&config, project.path())?.save_path(&file)?;
+        }
+        let project_root_dir = std::path::absolute(project.path())?;
+        let project_snapshots = || -> Result<Vec<StoredSnapshot>> {
+            Ok(list_snapshots(&config)?
+                .into_iter()
+                .filter(|snapshot| snapshot.info.project_root_dir == project_root_dir)
+                .collect())
+        };
+        let snapshots = project_snapshots()?;
+        assert_eq!(snapshots.len(), 4);
+
+        let dir = &config.sync_snapshots_dir;
+        assert_eq!(prune_snapshots(dir, &project_root_dir, 3, u64::MAX)?, 1);
+        assert_eq!(project_snapshots()?, snapshots[..3]);
+        assert_eq!(
+            prune_snapshots(dir, &project_root_dir, 3, snapshots[0].size * 2)?,
+            1
+        );
+        assert_eq!(project_snapshots()?, snapshots[..2]);
+        // The most recent snapshot is kept, however large it is
+        assert_eq!(prune_snapshots(dir, &project_root_dir, 3, 0)?, 1);
+        assert_eq!(project_snapshots()?, snapshots[..1]);
+
+        // Snapshots of other projects are pruned separately, even though they're older
+        assert_eq!(list_snapshots(&config)?.len(), 2);
+        assert!(latest_snapshot(&config, other_project.path())?.is_some());
+        Ok(())
+    }
+}
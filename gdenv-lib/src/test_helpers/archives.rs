@@ -0,0 +1,25 @@
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::Path;
+use walkdir::WalkDir;
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+/// Zips the contents of `dir` into an in-memory archive, inside a `top_level_folder`.
+pub fn zip_directory(dir: &Path, top_level_folder: &str) -> anyhow::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let mut zip = ZipWriter::new(Cursor::new(&mut buffer));
+    for entry in WalkDir::new(dir).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let relative_path = entry.path().strip_prefix(dir)?.to_string_lossy();
+        let name = format!("{top_level_folder}/{relative_path}");
+        if entry.file_type().is_dir() {
+            zip.add_directory(name, SimpleFileOptions::default())?;
+        } else {
+            zip.start_file(name, SimpleFileOptions::default())?;
+            zip.write_all(&fs::read(entry.path())?)?;
+        }
+    }
+    zip.finish()?;
+    Ok(buffer)
+}
@@ -15,9 +15,9 @@ impl DownloadClient for MockDownloadClient {
         _force_refresh: bool,
         _partial_fetch: bool,
     ) -> anyhow::Result<Vec<GitHubRelease>> {
-        Ok(vec![GitHubRelease {
-            version: GodotVersion::new("4.2.1-stable", false)?,
-            assets: vec![
+        Ok(vec![GitHubRelease::new(
+            GodotVersion::new("4.2.1-stable", false)?,
+            vec![
                 GitHubAsset {
                     name: "Godot_v4.2.1-stable_linux.x86_64.zip".to_string(),
                     browser_download_url: "https://example.com/linux64".to_string(),
@@ -33,8 +33,13 @@ impl DownloadClient for MockDownloadClient {
                     browser_download_url: "https://example.com/macos".to_string(),
                     size: 1000,
                 },
+                GitHubAsset {
+                    name: "Godot_v4.2.1-stable_export_templates.tpz".to_string(),
+                    browser_download_url: "https://example.com/templates".to_string(),
+                    size: 1000,
+                },
             ],
-        }])
+        )])
     }
 
     async fn download_asset(&self, asset: &GitHubAsset, output_path: &Path) -> anyhow::Result<()> {
@@ -1,5 +1,6 @@
 use crate::config::Config;
-use crate::git::GitClient;
+use crate::file_lock::FileLock;
+use crate::git::{GitClient, GitTag};
 use anyhow::Context;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -7,11 +8,28 @@ use std::path::{Path, PathBuf};
 /// Mock implementation for testing purposes.
 pub struct MockGitClient {
     config: Config,
+    tags: Vec<GitTag>,
 }
 
 impl MockGitClient {
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self {
+            config,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Makes every repository have these tags.
+    pub fn with_tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags
+            .iter()
+            .enumerate()
+            .map(|(i, name)| GitTag {
+                name: name.to_string(),
+                commit: format!("{i:040x}"),
+            })
+            .collect();
+        self
     }
 }
 
@@ -20,7 +38,16 @@ impl GitClient for MockGitClient {
         todo!("Not yet implemented")
     }
 
-    async fn checkout(&self, repo_url: &str, _git_ref: &str) -> anyhow::Result<PathBuf> {
+    async fn lock_repo(&self, repo_url: &str) -> anyhow::Result<FileLock> {
+        crate::git::lock_repo_dir(&self.config, repo_url).await
+    }
+
+    async fn checkout(
+        &self,
+        repo_url: &str,
+        _git_ref: &str,
+        _sparse_dirs: &[PathBuf],
+    ) -> anyhow::Result<PathBuf> {
         let repo_dir = crate::git::get_repo_dir(&self.config, repo_url);
         fs::create_dir_all(&repo_dir).context("Failed to create mock repository directory")?;
 
@@ -45,4 +72,8 @@ impl GitClient for MockGitClient {
 
         Ok(repo_dir)
     }
+
+    async fn list_remote_tags(&self, _repo_url: &str) -> anyhow::Result<Vec<GitTag>> {
+        Ok(self.tags.clone())
+    }
 }
@@ -1,2 +1,4 @@
+pub mod archives;
 pub mod mock_download_client;
 pub mod mock_git_client;
+pub mod mock_http_server;
@@ -2,14 +2,21 @@ use anyhow::Result;
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
+use crate::commands::addon::add::AddCommand;
+use crate::commands::addon::outdated::OutdatedCommand;
+use crate::commands::addon::remove::RemoveCommand;
+use crate::commands::addon::tree::TreeCommand;
+use crate::commands::addon::update::UpdateCommand;
+use crate::commands::bundle::{create::CreateCommand, import::ImportCommand};
+use crate::commands::cache::git::GitCacheCommand;
 use crate::commands::editor::EditorCommand;
 use crate::commands::run::RunCommand;
 use crate::commands::schema::SchemaCommand;
 use crate::commands::sync::SyncCommand;
 use crate::commands::{
-    godot::cache::CacheCommand, godot::current::CurrentCommand, godot::fetch::FetchCommand,
-    godot::install::InstallCommand, godot::list::ListCommand, godot::uninstall::UninstallCommand,
-    godot::use_cmd::UseCommand,
+    godot::cache::CacheCommand, godot::current::CurrentCommand, godot::download::DownloadCommand,
+    godot::fetch::FetchCommand, godot::install::InstallCommand, godot::list::ListCommand,
+    godot::uninstall::UninstallCommand, godot::use_cmd::UseCommand,
 };
 
 #[derive(Parser)]
@@ -52,6 +59,18 @@ pub enum Commands {
     /// Manage Godot versions
     #[command(subcommand)]
     Godot(GodotCommands),
+
+    /// Package Godot downloads and addon repositories for offline machines
+    #[command(subcommand)]
+    Bundle(BundleCommands),
+
+    /// Add, remove and inspect the project's addons
+    #[command(subcommand)]
+    Addon(AddonCommands),
+
+    /// Manage gdenv's caches
+    #[command(subcommand)]
+    Cache(CacheCommands),
 }
 
 #[derive(Subcommand)]
@@ -67,6 +86,9 @@ pub enum GodotCommands {
     /// Download and install a specific version of Godot
     Install(InstallCommand),
 
+    /// Download Godot archives into the cache without installing them
+    Download(DownloadCommand),
+
     /// Switch to a specific Godot version
     Use(UseCommand),
 
@@ -81,6 +103,35 @@ pub enum GodotCommands {
     Cache(CacheCommand),
 }
 
+#[derive(Subcommand)]
+pub enum BundleCommands {
+    /// Create a bundle with everything a project needs to be used offline
+    Create(CreateCommand),
+
+    /// Import a bundle created with `gdenv bundle create`
+    Import(ImportCommand),
+}
+
+#[derive(Subcommand)]
+pub enum AddonCommands {
+    /// Add an addon to gdenv.toml, finding the directory of its source that contains plugin.cfg
+    Add(AddCommand),
+    /// Remove an addon from gdenv.toml, along with its synced files
+    Remove(RemoveCommand),
+    /// Show the newer versions of the project's git addons
+    Outdated(OutdatedCommand),
+    /// Update git addons to newer version tags and sync them
+    Update(UpdateCommand),
+    /// Show the project's addons and the addons they depend on
+    Tree(TreeCommand),
+}
+
+#[derive(Subcommand)]
+pub enum CacheCommands {
+    /// Manage the cached repositories of git addons
+    Git(GitCacheCommand),
+}
+
 impl Cli {
     pub async fn run(self) -> Result<()> {
         match self.command {
@@ -88,11 +139,26 @@ impl Cli {
                 GodotCommands::Fetch(cmd) => cmd.run(self.global_args).await,
                 GodotCommands::List(cmd) => cmd.run(self.global_args).await,
                 GodotCommands::Install(cmd) => cmd.run(self.global_args).await,
+                GodotCommands::Download(cmd) => cmd.run(self.global_args).await,
                 GodotCommands::Use(cmd) => cmd.run(self.global_args).await,
                 GodotCommands::Current(cmd) => cmd.run(self.global_args).await,
                 GodotCommands::Uninstall(cmd) => cmd.run(self.global_args).await,
                 GodotCommands::Cache(cmd) => cmd.run(self.global_args).await,
             },
+            Commands::Bundle(bundle_command) => match bundle_command {
+                BundleCommands::Create(cmd) => cmd.run(self.global_args).await,
+                BundleCommands::Import(cmd) => cmd.run(self.global_args).await,
+            },
+            Commands::Addon(addon_command) => match addon_command {
+                AddonCommands::Add(cmd) => cmd.run(self.global_args).await,
+                AddonCommands::Remove(cmd) => cmd.run(self.global_args).await,
+                AddonCommands::Outdated(cmd) => cmd.run(self.global_args).await,
+                AddonCommands::Update(cmd) => cmd.run(self.global_args).await,
+                AddonCommands::Tree(cmd) => cmd.run(self.global_args).await,
+            },
+            Commands::Cache(cache_command) => match cache_command {
+                CacheCommands::Git(cmd) => cmd.run(self.global_args).await,
+            },
             Commands::Run(cmd) => cmd.run(self.global_args).await,
             Commands::Editor(cmd) => cmd.run(self.global_args).await,
             Commands::Sync(cmd) => cmd.run(self.global_args).await,
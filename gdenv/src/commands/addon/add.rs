@@ -0,0 +1,212 @@
+use crate::cli::GlobalArgs;
+use crate::commands::sync::SyncCommand;
+use crate::ui;
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use gdenv_lib::addons::find_addon_plugin_dirs;
+use gdenv_lib::cargo::cargo_info_provider;
+use gdenv_lib::config::Config;
+use gdenv_lib::git::AutoGitClient;
+use gdenv_lib::project_specification::{
+    AddonSource, AddonSpec, AssetLibraryAddonSource, GitAddonSource, LocalAddonSource,
+    load_godot_project_spec,
+};
+use gdenv_lib::spec_editor::add_addon;
+use pathdiff::diff_paths;
+use std::path::{Path, PathBuf};
+
+#[derive(Args)]
+pub struct AddCommand {
+    /// Git repository URL, path to a local directory, or Godot Asset Library asset ID
+    pub source: String,
+
+    /// Git reference to check out (branch, tag, commit hash, etc), or the version of an
+    /// Asset Library asset
+    #[arg(long)]
+    pub rev: Option<String>,
+
+    /// Name of the addon, which is also the name of its directory in `addons`. Defaults to
+    /// the name of the directory containing `plugin.cfg`
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Directory of the source to copy the addon files from. Defaults to the directory
+    /// containing `plugin.cfg`
+    #[arg(long)]
+    pub subdir: Option<PathBuf>,
+
+    /// Sync the project's addons after adding this one
+    #[arg(long)]
+    pub sync: bool,
+}
+
+impl AddCommand {
+    pub async fn run(self, global_args: GlobalArgs) -> Result<()> {
+        let config = Config::setup(global_args.datadir.as_deref())?;
+        let git_client = AutoGitClient::new(config.clone());
+        let working_dir = global_args
+            .project
+            .clone()
+            .unwrap_or(std::env::current_dir()?);
+        let project_spec = load_godot_project_spec(&working_dir, cargo_info_provider())?;
+        let spec_file = project_spec
+            .spec_file_path
+            .clone()
+            .filter(|path| path.file_name().is_some_and(|name| name == "gdenv.toml"))
+            .context("Addons are configured in gdenv.toml, which this project doesn't have.")?;
+
+        let mut source = self.addon_source(
+            &project_spec
+                .project_root_dir
+                .join(&project_spec.godot_project_dir),
+        )?;
+        let subdir = match &self.subdir {
+            Some(subdir) => Some(subdir.clone()),
+            None => {
+                let addon_spec = new_addon_spec(source.clone());
+                let addon_name = self.name.as_deref().unwrap_or("new addon");
+                let plugin_dirs = find_addon_plugin_dirs(
+                    &config,
+                    &project_spec,
+                    &git_client,
+                    addon_name,
+                    &addon_spec,
+                )
+                .await?;
+                match plugin_dirs.as_slice() {
+                    [] => {
+                        ui::warning(&format!(
+                            "No plugin.cfg found in {}, so the addon will contain all of its files. Use --subdir to pick a directory.",
+                            self.source
+                        ));
+                        None
+                    }
+                    [plugin_dir] if plugin_dir.as_os_str().is_empty() => None,
+                    [plugin_dir] => {
+                        ui::info(&format!(
+                            "Found plugin.cfg in {}, using it as the addon's subdir",
+                            plugin_dir.display()
+                        ));
+                        Some(plugin_dir.clone())
+                    }
+                    plugin_dirs => bail!(
+                        "Found several plugins in {}, use --subdir to pick one:\n  {}",
+                        self.source,
+                        plugin_dirs
+                            .iter()
+                            .map(|dir| dir.display().to_string())
+                            .collect::<Vec<_>>()
+                            .join("\n  ")
+                    ),
+                }
+            }
+        };
+
+        let addon_name = match (&self.name, &subdir) {
+            (Some(name), _) => name.clone(),
+            (None, Some(subdir)) => dir_name(subdir)?,
+            (None, None) => default_name(&source)?,
+        };
+        match &mut source {
+            AddonSource::Git(git) => git.subdir = subdir,
+            AddonSource::AssetLibrary(asset) => asset.subdir = subdir,
+            AddonSource::Local(local) => {
+                if let Some(subdir) = subdir {
+                    local.path.push(subdir);
+                }
+            }
+            AddonSource::Archive(_) => unreachable!("Archives aren't added from the command line"),
+        }
+
+        add_addon(&spec_file, &addon_name, &new_addon_spec(source))?;
+        ui::success(&format!(
+            "Added addon \"{}\" to {}",
+            addon_name,
+            spec_file.display()
+        ));
+
+        if self.sync {
+            let sync = SyncCommand::new(true, 4);
+            sync.run(global_args).await?;
+        } else {
+            ui::tip("Run `gdenv sync` to copy its files into the project.");
+        }
+        Ok(())
+    }
+
+    fn addon_source(&self, godot_project_dir: &Path) -> Result<AddonSource> {
+        if let Ok(asset_id) = self.source.parse() {
+            return Ok(AddonSource::AssetLibrary(AssetLibraryAddonSource {
+                asset_id,
+                version: self.rev.clone(),
+                subdir: None,
+            }));
+        }
+        if self.source.contains("://")
+            || self.source.starts_with("git@")
+            || self.source.ends_with(".git")
+        {
+            return Ok(AddonSource::Git(GitAddonSource {
+                git: self.source.clone(),
+                rev: self.rev.clone(),
+                version: None,
+                subdir: None,
+            }));
+        }
+
+        if self.rev.is_some() {
+            bail!("--rev only applies to git repositories and Asset Library assets");
+        }
+        let path = std::env::current_dir()?.join(&self.source);
+        if !path.is_dir() {
+            bail!(
+                "{} isn't a git URL, Asset Library asset ID or directory",
+                self.source
+            );
+        }
+        // Local paths are relative to the Godot project directory
+        let path = path.canonicalize()?;
+        let godot_project_dir = godot_project_dir.canonicalize()?;
+        Ok(AddonSource::Local(LocalAddonSource {
+            path: diff_paths(&path, &godot_project_dir).unwrap_or(path),
+            link: None,
+        }))
+    }
+}
+
+fn new_addon_spec(source: AddonSource) -> AddonSpec {
+    AddonSpec {
+        include: None,
+        exclude: None,
+        destination: None,
+        preserve_godot_files: None,
+        enable_plugin: None,
+        source,
+    }
+}
+
+/// Names the addon after its repository or directory, when the addon has no `plugin.cfg`
+/// to name it after.
+fn default_name(source: &AddonSource) -> Result<String> {
+    match source {
+        AddonSource::Git(git) => {
+            let name = git
+                .git
+                .trim_end_matches('/')
+                .rsplit(['/', ':'])
+                .next()
+                .unwrap_or_default();
+            Ok(name.trim_end_matches(".git").to_string())
+        }
+        AddonSource::Local(local) => dir_name(&local.path),
+        AddonSource::AssetLibrary(_) | AddonSource::Archive(_) => {
+            bail!("Couldn't tell the addon's name, use --name to set it.")
+        }
+    }
+}
+
+fn dir_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .context("Couldn't tell the addon's name, use --name to set it.")
+}
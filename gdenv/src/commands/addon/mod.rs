@@ -0,0 +1,5 @@
+pub mod add;
+pub mod outdated;
+pub mod remove;
+pub mod tree;
+pub mod update;
@@ -0,0 +1,87 @@
+use crate::cli::GlobalArgs;
+use crate::ui;
+use anyhow::Result;
+use clap::Args;
+use colored::Colorize;
+use gdenv_lib::addon_updates::check_addon_versions;
+use gdenv_lib::cargo::cargo_info_provider;
+use gdenv_lib::config::Config;
+use gdenv_lib::git::AutoGitClient;
+use gdenv_lib::project_specification::load_godot_project_spec;
+
+#[derive(Args)]
+pub struct OutdatedCommand {
+    /// Maximum number of repositories to query at the same time
+    #[arg(long, short, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: u16,
+}
+
+impl OutdatedCommand {
+    pub async fn run(self, global_args: GlobalArgs) -> Result<()> {
+        let config = Config::setup(global_args.datadir.as_deref())?;
+        let git_client = AutoGitClient::new(config);
+        let working_dir = global_args.project.unwrap_or(std::env::current_dir()?);
+        let project_spec = load_godot_project_spec(&working_dir, cargo_info_provider())?;
+
+        let versions = check_addon_versions(&project_spec, &git_client, self.jobs as usize).await?;
+        if versions.is_empty() {
+            ui::info("The project has no git addons.");
+            return Ok(());
+        }
+
+        let rows: Vec<[String; 4]> = versions
+            .iter()
+            .map(|addon| {
+                [
+                    addon.addon_name.clone(),
+                    addon.current.clone().unwrap_or_else(|| "-".to_string()),
+                    addon
+                        .latest_compatible
+                        .clone()
+                        .unwrap_or_else(|| "-".to_string()),
+                    addon.latest.clone().unwrap_or_else(|| "-".to_string()),
+                ]
+            })
+            .collect();
+        let header = ["Addon", "Current", "Compatible", "Latest"].map(str::to_string);
+        let widths: Vec<usize> = (0..4)
+            .map(|column| {
+                rows.iter()
+                    .chain([&header])
+                    .map(|row| row[column].len())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let format_row = |row: &[String; 4]| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+
+        ui::info(&format_row(&header).bold().to_string());
+        for (addon, row) in versions.iter().zip(&rows) {
+            let line = format_row(row);
+            if addon.update_target(false).is_some() {
+                ui::info(&line.yellow().to_string());
+            } else if addon.update_target(true).is_some() {
+                ui::info(&line.red().to_string());
+            } else {
+                ui::info(&line);
+            }
+        }
+        if versions
+            .iter()
+            .any(|addon| addon.update_target(true).is_some())
+        {
+            ui::tip(
+                "Run `gdenv addon update` to update to the latest compatible versions, or `gdenv addon update --major` to the latest ones.",
+            );
+        }
+        Ok(())
+    }
+}
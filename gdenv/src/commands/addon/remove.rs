@@ -0,0 +1,56 @@
+use crate::cli::GlobalArgs;
+use crate::commands::sync::update_plugins;
+use crate::ui;
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use gdenv_lib::addons::{SyncOptions, remove_orphaned_addons};
+use gdenv_lib::cargo::cargo_info_provider;
+use gdenv_lib::config::Config;
+use gdenv_lib::project_specification::load_godot_project_spec;
+use gdenv_lib::spec_editor::remove_addon;
+
+#[derive(Args)]
+pub struct RemoveCommand {
+    /// Name of the addon to remove
+    pub name: String,
+
+    /// Remove the addon's files even if they were changed by hand since the last sync
+    #[arg(long)]
+    pub force: bool,
+}
+
+impl RemoveCommand {
+    pub async fn run(self, global_args: GlobalArgs) -> Result<()> {
+        let config = Config::setup(global_args.datadir.as_deref())?;
+        let working_dir = global_args.project.unwrap_or(std::env::current_dir()?);
+        let mut project_spec = load_godot_project_spec(&working_dir, cargo_info_provider())?;
+        let spec_file = project_spec
+            .spec_file_path
+            .clone()
+            .context("The project has no gdenv.toml")?;
+        if project_spec.addons.remove(&self.name).is_none() {
+            bail!("Addon \"{}\" isn't in {}", self.name, spec_file.display());
+        }
+
+        // Remove the files first, so the addon stays configured if they were changed by hand
+        let options = SyncOptions {
+            force: self.force,
+            addons: Some(vec![self.name.clone()]),
+            ..Default::default()
+        };
+        let removed = remove_orphaned_addons(&config, &project_spec, &options)?;
+        remove_addon(&spec_file, &self.name)?;
+        let removed_destinations: Vec<_> = removed
+            .into_iter()
+            .map(|orphan| orphan.destination)
+            .collect();
+        update_plugins(&project_spec, &removed_destinations)?;
+
+        ui::success(&format!(
+            "Removed addon \"{}\" from {}",
+            self.name,
+            spec_file.display()
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use crate::cli::GlobalArgs;
+use crate::ui;
+use anyhow::Result;
+use clap::Args;
+use colored::Colorize;
+use gdenv_lib::addon_dependencies::resolve_addon_tree;
+use gdenv_lib::cargo::cargo_info_provider;
+use gdenv_lib::config::Config;
+use gdenv_lib::git::AutoGitClient;
+use gdenv_lib::project_specification::{AddonSource, AddonSpec, load_godot_project_spec};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Args)]
+pub struct TreeCommand {
+    /// Maximum number of addons to fetch at the same time
+    #[arg(long, short, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: u16,
+}
+
+impl TreeCommand {
+    pub async fn run(self, global_args: GlobalArgs) -> Result<()> {
+        let config = Config::setup(global_args.datadir.as_deref())?;
+        let git_client = AutoGitClient::new(config.clone());
+        let working_dir = global_args.project.unwrap_or(std::env::current_dir()?);
+        let project_spec = load_godot_project_spec(&working_dir, cargo_info_provider())?;
+
+        let tree =
+            resolve_addon_tree(&config, &project_spec, &git_client, self.jobs as usize).await?;
+        if tree.roots.is_empty() {
+            ui::info("The project has no addons.");
+            return Ok(());
+        }
+
+        // Whether the ancestors of the current entry were the last of their siblings
+        let mut last_ancestors: Vec<bool> = Vec::new();
+        for entry in tree.walk() {
+            last_ancestors.truncate(entry.depth);
+            let mut prefix = String::new();
+            if entry.depth > 0 {
+                for &is_last in &last_ancestors[1..] {
+                    prefix.push_str(if is_last { "    " } else { "│   " });
+                }
+                prefix.push_str(if entry.is_last {
+                    "└── "
+                } else {
+                    "├── "
+                });
+            }
+            last_ancestors.push(entry.is_last);
+
+            let addon_spec = &tree.project_spec.addons[&entry.addon_name];
+            let repeated = if entry.is_repeated { " (*)" } else { "" };
+            ui::info(&format!(
+                "{prefix}{} {}{repeated}",
+                entry.addon_name.bold(),
+                describe_source(addon_spec, &working_dir).dimmed()
+            ));
+        }
+        if tree.walk().iter().any(|entry| entry.is_repeated) {
+            ui::info(&"(*) dependencies shown above".dimmed().to_string());
+        }
+        Ok(())
+    }
+}
+
+fn describe_source(addon_spec: &AddonSpec, working_dir: &Path) -> String {
+    match &addon_spec.source {
+        AddonSource::Git(git) => match (&git.rev, &git.version) {
+            (Some(rev), _) => format!("({} @ {rev})", git.git),
+            (None, Some(version)) => format!("({} {version})", git.git),
+            (None, None) => format!("({})", git.git),
+        },
+        AddonSource::AssetLibrary(asset) => match &asset.version {
+            Some(version) => format!("(Asset Library #{} {version})", asset.asset_id),
+            None => format!("(Asset Library #{})", asset.asset_id),
+        },
+        AddonSource::Archive(archive) => format!("({})", archive.url),
+        AddonSource::Local(local) if local.link == Some(true) => {
+            format!(
+                "(linked to {})",
+                display_local_path(&local.path, working_dir)
+            )
+        }
+        AddonSource::Local(local) => format!("({})", display_local_path(&local.path, working_dir)),
+    }
+}
+
+/// Paths of dependencies are joined onto the directories of the addons declaring them, so
+/// drop their `.` and `..` components and show them relative to the project when possible.
+fn display_local_path(path: &Path, working_dir: &Path) -> String {
+    if path.is_relative() {
+        return path.display().to_string();
+    }
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    match normalized.strip_prefix(working_dir) {
+        Ok(relative) => relative.display().to_string(),
+        Err(_) => normalized.display().to_string(),
+    }
+}
@@ -0,0 +1,107 @@
+use crate::cli::GlobalArgs;
+use crate::commands::sync::SyncCommand;
+use crate::ui;
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use gdenv_lib::addon_updates::{AddonVersions, check_addon_versions};
+use gdenv_lib::cargo::cargo_info_provider;
+use gdenv_lib::config::Config;
+use gdenv_lib::git::AutoGitClient;
+use gdenv_lib::project_specification::{AddonSource, load_godot_project_spec};
+use gdenv_lib::spec_editor::set_addon_rev;
+
+#[derive(Args)]
+pub struct UpdateCommand {
+    /// Name of the addon to update. Defaults to every git addon
+    pub name: Option<String>,
+
+    /// Update to the latest version, even if it isn't semver compatible with the current one
+    #[arg(long)]
+    pub major: bool,
+
+    /// Do not ask for confirmation before syncing
+    #[arg(long, short)]
+    pub yes: bool,
+
+    /// Maximum number of repositories to query and addons to fetch at the same time
+    #[arg(long, short, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: u16,
+}
+
+impl UpdateCommand {
+    pub async fn run(self, global_args: GlobalArgs) -> Result<()> {
+        let config = Config::setup(global_args.datadir.as_deref())?;
+        let git_client = AutoGitClient::new(config);
+        let working_dir = global_args
+            .project
+            .clone()
+            .unwrap_or(std::env::current_dir()?);
+        let mut project_spec = load_godot_project_spec(&working_dir, cargo_info_provider())?;
+        let spec_file = project_spec
+            .spec_file_path
+            .clone()
+            .context("The project has no gdenv.toml")?;
+        if let Some(name) = &self.name {
+            match project_spec.addons.get(name).map(|addon| &addon.source) {
+                Some(AddonSource::Git(_)) => project_spec.addons.retain(|n, _| n == name),
+                Some(_) => bail!("Addon \"{name}\" isn't a git addon"),
+                None => bail!("Addon \"{}\" isn't in {}", name, spec_file.display()),
+            }
+        }
+
+        let versions = check_addon_versions(&project_spec, &git_client, self.jobs as usize).await?;
+        let mut updated = 0;
+        for addon in &versions {
+            let Some(target) = addon.update_target(self.major) else {
+                if self.name.is_some() {
+                    ui::info(&update_skipped_reason(addon, self.major));
+                }
+                continue;
+            };
+            set_addon_rev(&spec_file, &addon.addon_name, target)?;
+            ui::success(&format!(
+                "Updated addon \"{}\" from {} to {}",
+                addon.addon_name,
+                addon.current.as_deref().unwrap_or_default(),
+                target
+            ));
+            updated += 1;
+        }
+        if updated == 0 {
+            if self.name.is_none() {
+                ui::success("All addons are up to date.");
+            }
+            return Ok(());
+        }
+
+        SyncCommand::new(self.yes, self.jobs).run(global_args).await
+    }
+}
+
+fn update_skipped_reason(addon: &AddonVersions, major: bool) -> String {
+    let name = &addon.addon_name;
+    if addon.is_requirement {
+        return format!(
+            "Addon \"{}\" follows version \"{}\", which `gdenv sync` resolves to the newest matching tag{}. Edit the version in gdenv.toml to change it.",
+            name,
+            addon.current.as_deref().unwrap_or_default(),
+            addon
+                .latest_compatible
+                .as_ref()
+                .map(|tag| format!(" ({tag})"))
+                .unwrap_or_default()
+        );
+    }
+    match (&addon.current, &addon.latest_compatible) {
+        (Some(current), Some(_)) => match addon.update_target(true) {
+            Some(latest) if !major => format!(
+                "Addon \"{name}\" is on the latest compatible version ({current}). Use --major to update it to {latest}."
+            ),
+            _ => format!("Addon \"{name}\" is on the latest version ({current})"),
+        },
+        _ if addon.latest.is_none() => {
+            format!("Addon \"{name}\" has no version tags to update to")
+        }
+        _ => format!("Addon \"{name}\" isn't pinned to a version tag, so it isn't updated"),
+    }
+}
@@ -0,0 +1,115 @@
+use crate::cli::GlobalArgs;
+use crate::ui;
+use anyhow::Result;
+use clap::Args;
+use gdenv_lib::addon_dependencies::resolve_addon_tree;
+use gdenv_lib::bundle::{BundleContents, create_bundle};
+use gdenv_lib::cargo::cargo_info_provider;
+use gdenv_lib::config::Config;
+use gdenv_lib::git::AutoGitClient;
+use gdenv_lib::github::GitHubClient;
+use gdenv_lib::godot_version::GodotVersion;
+use gdenv_lib::project_specification::{AddonSource, load_godot_project_spec};
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct CreateCommand {
+    /// Path of the bundle to create (e.g., gdenv-bundle.tar)
+    pub output: PathBuf,
+
+    /// Godot versions to include. If not provided, the project's Godot version is used
+    #[arg(long = "version")]
+    pub versions: Vec<String>,
+
+    /// Include the .NET versions of Godot
+    #[arg(long, alias = "mono")]
+    pub dotnet: bool,
+
+    /// Operating system of the machine the bundle is for (e.g., linux, windows, macos)
+    #[arg(long, default_value = std::env::consts::OS)]
+    pub os: String,
+
+    /// Architecture of the machine the bundle is for (e.g., x86_64, aarch64)
+    #[arg(long, default_value = std::env::consts::ARCH)]
+    pub arch: String,
+
+    /// Include the export templates
+    #[arg(long)]
+    pub templates: bool,
+}
+
+impl CreateCommand {
+    pub async fn run(self, global_args: GlobalArgs) -> Result<()> {
+        let config = Config::setup(global_args.datadir.as_deref())?;
+        let github_client = GitHubClient::new(config.clone());
+        let git_client = AutoGitClient::new(config.clone());
+        ui::info(&github_client.cache_status_message());
+
+        let working_dir = global_args.project.unwrap_or(std::env::current_dir()?);
+        let project_spec = load_godot_project_spec(&working_dir, cargo_info_provider()).ok();
+
+        let godot_versions = if self.versions.is_empty() {
+            match &project_spec {
+                Some(spec) => vec![spec.godot_version.clone()],
+                None => anyhow::bail!(
+                    "No Godot version given and no project found. Use --version to choose the Godot versions to bundle."
+                ),
+            }
+        } else {
+            self.versions
+                .iter()
+                .map(|v| GodotVersion::new(v, self.dotnet))
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        // Include the repositories of the addons that other addons depend on
+        let project_spec = match project_spec {
+            Some(spec) => Some(
+                resolve_addon_tree(&config, &spec, &git_client, 4)
+                    .await?
+                    .project_spec,
+            ),
+            None => None,
+        };
+        let mut git_addons: Vec<_> = project_spec
+            .iter()
+            .flat_map(|spec| spec.addons.values())
+            .filter_map(|addon| match &addon.source {
+                AddonSource::Git(git) => Some(git.clone()),
+                AddonSource::AssetLibrary(_) | AddonSource::Archive(_) | AddonSource::Local(_) => {
+                    None
+                }
+            })
+            .collect();
+        git_addons.sort_by(|a, b| a.git.cmp(&b.git));
+
+        let contents = BundleContents {
+            godot_versions,
+            os: self.os,
+            arch: self.arch,
+            export_templates: self.templates,
+            git_addons,
+        };
+        let manifest = create_bundle(
+            &config,
+            &github_client,
+            &git_client,
+            &contents,
+            &self.output,
+        )
+        .await?;
+
+        ui::success(&format!("Created bundle: {}", self.output.display()));
+        for version in &manifest.godot_versions {
+            ui::info(&format!(
+                "Godot {version} ({}, {})",
+                manifest.os, manifest.arch
+            ));
+        }
+        for repo_url in &manifest.git_repositories {
+            ui::info(&format!("Git repository {repo_url}"));
+        }
+        ui::tip("Use `gdenv bundle import` to import the bundle on another machine.");
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+use crate::cli::GlobalArgs;
+use crate::ui;
+use anyhow::Result;
+use clap::Args;
+use gdenv_lib::bundle::import_bundle;
+use gdenv_lib::config::Config;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct ImportCommand {
+    /// Path of the bundle to import
+    pub file: PathBuf,
+}
+
+impl ImportCommand {
+    pub async fn run(self, global_args: GlobalArgs) -> Result<()> {
+        let config = Config::setup(global_args.datadir.as_deref())?;
+
+        let manifest = import_bundle(&config, &self.file).await?;
+
+        ui::success(&format!("Imported bundle: {}", self.file.display()));
+        for version in &manifest.godot_versions {
+            ui::info(&format!(
+                "Godot {version} ({}, {})",
+                manifest.os, manifest.arch
+            ));
+        }
+        for repo_url in &manifest.git_repositories {
+            ui::info(&format!("Git repository {repo_url}"));
+        }
+        ui::tip("Use `gdenv godot install` to install the bundled Godot versions offline.");
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+use crate::cli::GlobalArgs;
+use crate::commands::godot::cache::format_size;
+use crate::ui;
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use colored::Colorize;
+use gdenv_lib::config::Config;
+use gdenv_lib::git_cache::{list_git_cache, prune_git_cache};
+use std::time::{Duration, SystemTime};
+
+#[derive(Args)]
+pub struct GitCacheCommand {
+    #[command(subcommand)]
+    pub action: GitCacheAction,
+}
+
+#[derive(Subcommand)]
+pub enum GitCacheAction {
+    /// List the cached repositories and the commits checked out from them
+    #[command(alias = "ls")]
+    List,
+    /// Remove checkouts that haven't been used for a while
+    Prune {
+        /// Remove checkouts that haven't been used for this many days
+        #[arg(long, default_value_t = 30)]
+        days: u64,
+
+        /// Remove every cached repository and checkout, including the mirrors needed to sync
+        /// offline
+        #[arg(long, conflicts_with = "days")]
+        all: bool,
+    },
+}
+
+impl GitCacheCommand {
+    pub async fn run(self, global_args: GlobalArgs) -> Result<()> {
+        let config = Config::setup(global_args.datadir.as_deref())?;
+
+        match self.action {
+            GitCacheAction::List => list(&config).await,
+            GitCacheAction::Prune { days, all } => {
+                let unused_for = (!all).then(|| Duration::from_secs(days * 24 * 60 * 60));
+                prune(&config, unused_for).await
+            }
+        }
+    }
+}
+
+async fn list(config: &Config) -> Result<()> {
+    let repositories = list_git_cache(config).await?;
+    if repositories.is_empty() {
+        ui::info("The git cache is empty");
+        return Ok(());
+    }
+
+    let mut total_size = 0;
+    let now = SystemTime::now();
+    for repository in &repositories {
+        let url = repository.url.as_deref().unwrap_or("(broken mirror)");
+        ui::info(&format!(
+            "{} {}",
+            url.bold(),
+            format!("({})", format_size(repository.size)).dimmed()
+        ));
+        total_size += repository.size;
+        if repository.worktrees.is_empty() {
+            ui::info("  No checkouts");
+        }
+        for worktree in &repository.worktrees {
+            let commit = worktree
+                .commit
+                .as_deref()
+                .map_or("?", |commit| &commit[..commit.len().min(12)]);
+            let last_used = match worktree.last_used {
+                Some(last_used) => {
+                    let days = now.duration_since(last_used).unwrap_or_default().as_secs()
+                        / (24 * 60 * 60);
+                    match days {
+                        0 => "used today".to_string(),
+                        1 => "used yesterday".to_string(),
+                        days => format!("used {days} days ago"),
+                    }
+                }
+                None => "never used".to_string(),
+            };
+            ui::info(&format!(
+                "  {}  {:>9}  {}",
+                commit,
+                format_size(worktree.size),
+                last_used.dimmed()
+            ));
+            total_size += worktree.size;
+        }
+    }
+    ui::info(&format!(
+        "Location: {}, total size: {}",
+        config.git_cache_dir.display(),
+        format_size(total_size)
+    ));
+    ui::tip("Run `gdenv cache git prune` to remove checkouts that haven't been used for a while");
+    Ok(())
+}
+
+async fn prune(config: &Config, unused_for: Option<Duration>) -> Result<()> {
+    let pruned = prune_git_cache(config, unused_for).await?;
+    if pruned.worktrees == 0 && pruned.repositories == 0 && pruned.old_checkouts == 0 {
+        ui::success("Nothing to prune");
+        return Ok(());
+    }
+
+    let count = |count: usize, one: &str, many: &str| {
+        format!("{count} {}", if count == 1 { one } else { many })
+    };
+    let mut removed = vec![count(pruned.worktrees, "checkout", "checkouts")];
+    if pruned.repositories > 0 {
+        removed.push(count(pruned.repositories, "repository", "repositories"));
+    }
+    if pruned.old_checkouts > 0 {
+        removed.push(count(
+            pruned.old_checkouts,
+            "checkout from an older version of gdenv",
+            "checkouts from older versions of gdenv",
+        ));
+    }
+    ui::success(&format!(
+        "Removed {}, freeing {}",
+        removed.join(", "),
+        format_size(pruned.size)
+    ));
+    Ok(())
+}
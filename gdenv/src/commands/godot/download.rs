@@ -0,0 +1,61 @@
+use crate::cli::GlobalArgs;
+use crate::ui;
+use anyhow::Result;
+use clap::Args;
+use gdenv_lib::config::Config;
+use gdenv_lib::github::GitHubClient;
+use gdenv_lib::godot_version::GodotVersion;
+use gdenv_lib::installer;
+
+#[derive(Args)]
+pub struct DownloadCommand {
+    /// The Godot version to download (e.g., 4.2.1, 4.1.0-stable)
+    pub version: String,
+
+    /// Download the .NET version of Godot
+    #[arg(long, alias = "mono")]
+    pub dotnet: bool,
+
+    /// Operating system to download Godot for (e.g., linux, windows, macos)
+    #[arg(long, default_value = std::env::consts::OS)]
+    pub os: String,
+
+    /// Architecture to download Godot for (e.g., x86_64, aarch64)
+    #[arg(long, default_value = std::env::consts::ARCH)]
+    pub arch: String,
+
+    /// Also download the export templates
+    #[arg(long)]
+    pub templates: bool,
+}
+
+impl DownloadCommand {
+    pub async fn run(self, global_args: GlobalArgs) -> Result<()> {
+        let config = Config::setup(global_args.datadir.as_deref())?;
+        let github_client = GitHubClient::new(config.clone());
+        ui::info(&github_client.cache_status_message());
+
+        let version = GodotVersion::new(&self.version, self.dotnet)?;
+        let archive_path = installer::download_godot_archive(
+            &config,
+            &version,
+            &github_client,
+            &self.os,
+            &self.arch,
+        )
+        .await?;
+        ui::success(&format!("Downloaded to: {}", archive_path.display()));
+
+        if self.templates {
+            let templates_path =
+                installer::download_export_templates(&config, &version, &github_client).await?;
+            ui::success(&format!(
+                "Downloaded export templates to: {}",
+                templates_path.display()
+            ));
+        }
+
+        ui::tip("Use `gdenv bundle create` to package downloads for offline machines.");
+        Ok(())
+    }
+}
@@ -1,36 +1,45 @@
 use crate::cli::GlobalArgs;
 use crate::ui;
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 use clap::Args;
 use gdenv_lib::cargo::cargo_info_provider;
 use gdenv_lib::config::Config;
 use gdenv_lib::download_client::DownloadClient;
-use gdenv_lib::github::GitHubClient;
+use gdenv_lib::github::{GitHubClient, GitHubRelease};
 use gdenv_lib::godot_version::GodotVersion;
 use gdenv_lib::installer;
-use gdenv_lib::project_specification::{ProjectSpecification, load_godot_project_spec};
+use gdenv_lib::installer::InstallOutcome;
+use gdenv_lib::project_specification::load_godot_project_spec;
 
 #[derive(Args)]
 pub struct InstallCommand {
-    /// The Godot version to install (e.g., 4.2.1, 4.1.0-stable)
-    /// If not provided, reads from .godot-version file
-    pub version: Option<String>,
+    /// The Godot versions to install (e.g., 4.2.1, 4.1.0-stable)
+    /// If not provided, reads from gdenv.toml or .godot-version file
+    pub versions: Vec<String>,
 
     /// Install the .NET version of Godot
     #[arg(long, alias = "mono")]
     pub dotnet: bool,
 
+    /// Together with --dotnet, also install the standard (non-.NET) version of each release
+    #[arg(long, requires = "dotnet")]
+    pub also_standard: bool,
+
     /// Force reinstall even if version is already installed
     #[arg(long, short)]
     pub force: bool,
 
     /// Install the latest stable release
-    #[arg(long, conflicts_with_all = ["version", "latest_prerelease"])]
+    #[arg(long, conflicts_with_all = ["versions", "latest_prerelease"])]
     pub latest: bool,
 
     /// Install the latest prerelease (beta, rc, etc.)
-    #[arg(long, conflicts_with_all = ["version", "latest"])]
+    #[arg(long, conflicts_with_all = ["versions", "latest"])]
     pub latest_prerelease: bool,
+
+    /// Maximum number of archives to download at the same time
+    #[arg(long, short, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: u16,
 }
 
 impl InstallCommand {
@@ -39,95 +48,179 @@ impl InstallCommand {
         let github_client = GitHubClient::new(config.clone());
         ui::info(&github_client.cache_status_message());
 
-        let project_spec = self.project_spec(global_args, &github_client).await?;
+        // Fetch available releases from GitHub first (needed for --latest flags)
+        let releases = github_client.godot_releases(false, true).await?;
+        let versions = self.target_versions(global_args, &config, &releases)?;
+
+        // Versions without a build for this platform fail without downloading anything,
+        // and don't prevent the other versions from being installed
+        let mut available_versions = Vec::new();
+        let mut unavailable = Vec::new();
+        for version in &versions {
+            match installer::check_available(&releases, version, &config.os, &config.arch) {
+                Ok(()) => available_versions.push(version.clone()),
+                Err(e) => unavailable.push(InstallOutcome {
+                    version: version.clone(),
+                    result: Err(e),
+                }),
+            }
+        }
 
-        let install_path = installer::ensure_installed(
+        let mut installed = installer::ensure_installed_all(
             &config,
-            &project_spec.godot_version,
+            &available_versions,
             &github_client,
             self.force,
+            self.jobs as usize,
         )
-        .await
-        .context(format!(
-            "Failed to install Godot version {}",
-            project_spec.godot_version
-        ))?;
+        .await?
+        .into_iter();
+        // Report the outcomes in the requested order
+        let mut outcomes: Vec<InstallOutcome> = Vec::new();
+        for version in versions {
+            if outcomes.iter().any(|outcome| outcome.version == version) {
+                continue;
+            }
+            if let Some(index) = unavailable
+                .iter()
+                .position(|outcome| outcome.version == version)
+            {
+                outcomes.push(unavailable.remove(index));
+            } else if let Some(outcome) = installed.next() {
+                outcomes.push(outcome);
+            }
+        }
+        let requested_count = outcomes.len();
 
-        ui::success(&format!("Installed to: {}", install_path.display()));
+        let installed_versions = if requested_count == 1 {
+            let InstallOutcome { version, result } = outcomes.remove(0);
+            let install_path =
+                result.context(format!("Failed to install Godot version {version}"))?;
+            ui::success(&format!("Installed to: {}", install_path.display()));
+            vec![version]
+        } else {
+            Self::print_summary(&outcomes);
+            outcomes
+                .iter()
+                .filter(|outcome| outcome.result.is_ok())
+                .map(|outcome| outcome.version.clone())
+                .collect()
+        };
 
         tracing::info!("");
-        // Only set as active version if no version is currently active
-        if installer::get_active_version(&config)?.is_none() {
-            installer::set_active_version(&config, &project_spec.godot_version)?;
-            ui::info(&format!(
-                "Using Godot {} as active version (first installation).",
-                project_spec.godot_version
-            ));
-        } else {
-            ui::tip(&format!(
-                "Run `gdenv godot use {}{}` to switch to this version.",
-                project_spec.godot_version.as_godot_version_str(),
-                if project_spec.godot_version.is_dotnet {
-                    " --dotnet"
-                } else {
-                    ""
-                }
-            ));
+        if let Some(first_installed) = installed_versions.first() {
+            // Only set as active version if no version is currently active
+            if installer::get_active_version(&config)?.is_none() {
+                installer::set_active_version(&config, first_installed)?;
+                ui::info(&format!(
+                    "Using Godot {first_installed} as active version (first installation)."
+                ));
+            } else {
+                ui::tip(&format!(
+                    "Run `gdenv godot use {}{}` to switch to this version.",
+                    first_installed.as_godot_version_str(),
+                    if first_installed.is_dotnet {
+                        " --dotnet"
+                    } else {
+                        ""
+                    }
+                ));
+            }
+            ui::tip("Run `gdenv godot current` for PATH setup instructions.");
+        }
+
+        let failure_count = requested_count - installed_versions.len();
+        if failure_count > 0 {
+            bail!(
+                "Failed to install {} of {} Godot versions",
+                failure_count,
+                requested_count
+            );
         }
-        ui::tip("Run `gdenv godot current` for PATH setup instructions.");
 
         Ok(())
     }
 
-    async fn project_spec(
+    fn print_summary(outcomes: &[InstallOutcome]) {
+        tracing::info!("");
+        ui::info("Installation summary:");
+        for outcome in outcomes {
+            match &outcome.result {
+                Ok(install_path) => ui::success(&format!(
+                    "{}: installed to {}",
+                    outcome.version,
+                    install_path.display()
+                )),
+                Err(e) => ui::error(&format!("{}: {e:#}", outcome.version)),
+            }
+        }
+    }
+
+    /// Determine which versions to install from the command line arguments,
+    /// falling back to the project specification when no version was given.
+    fn target_versions(
         &self,
         global_args: GlobalArgs,
-        github_client: &GitHubClient,
-    ) -> Result<ProjectSpecification> {
-        // Fetch available releases from GitHub first (needed for --latest flags)
-        let release_versions = github_client
-            .godot_releases(false, true)
-            .await?
-            .iter()
-            .map(|release| release.version.clone())
-            .collect::<Vec<_>>();
-        let version_override = self.override_version(release_versions)?;
-        let working_dir = global_args.project.unwrap_or(std::env::current_dir()?);
-        let spec_from_file = load_godot_project_spec(&working_dir, cargo_info_provider())?;
-        Ok(ProjectSpecification {
-            godot_version: version_override.unwrap_or(spec_from_file.godot_version),
-            ..spec_from_file
-        })
+        config: &Config,
+        releases: &[GitHubRelease],
+    ) -> Result<Vec<GodotVersion>> {
+        let version_overrides = self.override_versions(config, releases)?;
+        let versions = if version_overrides.is_empty() {
+            let working_dir = global_args.project.unwrap_or(std::env::current_dir()?);
+            let spec_from_file = load_godot_project_spec(&working_dir, cargo_info_provider())?;
+            vec![spec_from_file.godot_version]
+        } else {
+            version_overrides
+        };
+
+        if !self.also_standard {
+            return Ok(versions);
+        }
+        Ok(versions
+            .into_iter()
+            .flat_map(|version| {
+                let standard = GodotVersion {
+                    is_dotnet: false,
+                    ..version.clone()
+                };
+                [version, standard]
+            })
+            .collect())
     }
 
-    fn override_version(
+    fn override_versions(
         &self,
-        release_versions: Vec<GodotVersion>,
-    ) -> Result<Option<GodotVersion>> {
-        let version_override = if self.latest {
+        config: &Config,
+        releases: &[GitHubRelease],
+    ) -> Result<Vec<GodotVersion>> {
+        // Only consider releases that ship the requested variant for this platform
+        let mut available_versions = releases
+            .iter()
+            .filter(|release| release.provides(self.dotnet, &config.os, &config.arch))
+            .map(|release| GodotVersion {
+                is_dotnet: self.dotnet,
+                ..release.version.clone()
+            });
+        let version_overrides = if self.latest {
             // Find latest stable release (last one since it's sorted ascending)
-            Some(
-                release_versions
-                    .iter()
-                    .rfind(|v| !v.is_prerelease() && v.is_dotnet == self.dotnet)
-                    .cloned()
+            vec![
+                available_versions
+                    .rfind(|v| !v.is_prerelease())
                     .ok_or_else(|| anyhow!("No stable releases found"))?,
-            )
+            ]
         } else if self.latest_prerelease {
             // Find latest release (including prereleases)
-            Some(
-                release_versions
-                    .iter()
-                    .rfind(|v| v.is_dotnet == self.dotnet)
-                    .cloned()
+            vec![
+                available_versions
+                    .next_back()
                     .ok_or_else(|| anyhow!("No releases found"))?,
-            )
+            ]
         } else {
-            self.version
-                .clone()
-                .map(|v| GodotVersion::new(&v, self.dotnet))
-                .transpose()?
+            self.versions
+                .iter()
+                .map(|v| GodotVersion::new(v, self.dotnet))
+                .collect::<Result<Vec<_>>>()?
         };
-        Ok(version_override)
+        Ok(version_overrides)
     }
 }
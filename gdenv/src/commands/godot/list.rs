@@ -10,6 +10,14 @@ use gdenv_lib::godot_version::{GodotVersion, version_buffet};
 use gdenv_lib::installer;
 use std::io::{self, Write};
 
+/// What to show next to each listed version.
+pub struct VersionListing<'a> {
+    installed: &'a [GodotVersion],
+    active_version: Option<&'a GodotVersion>,
+    /// Releases that ship a .NET build for the current platform.
+    dotnet_available: &'a [GodotVersion],
+}
+
 #[derive(Args)]
 pub struct ListCommand {
     /// Filter versions by query (e.g., "4" "4.2", "4.2-rc", "4.2-beta")
@@ -47,11 +55,21 @@ impl ListCommand {
             .iter()
             .map(|release| release.version.clone())
             .collect();
+        let dotnet_available: Vec<GodotVersion> = all_releases
+            .iter()
+            .filter(|release| release.provides(true, &config.os, &config.arch))
+            .map(|release| release.version.clone())
+            .collect();
+        let listing = VersionListing {
+            installed: &installed,
+            active_version: active_version.as_ref(),
+            dotnet_available: &dotnet_available,
+        };
 
         if let Some(q) = &self.query {
-            Self::print_version_matches(&all_versions, &installed, &active_version, q, self.pre);
+            Self::print_version_matches(&all_versions, &listing, q, self.pre);
         } else {
-            Self::print_version_buffet(&all_versions, &installed, &active_version);
+            Self::print_version_buffet(&all_versions, &listing);
         }
 
         tracing::info!("");
@@ -62,6 +80,7 @@ impl ListCommand {
         ui::tip("Use `gdenv godot fetch` to refresh the cache.");
         ui::tip("Use `gdenv godot list <string_pattern>` to filter available versions");
         ui::tip("Use `gdenv godot install <version>` to install a new version from github");
+        ui::tip("Use `gdenv godot install <version> --dotnet` for versions marked .NET available");
         ui::tip("Use `gdenv godot use <version>` to set the active version");
 
         Ok(())
@@ -69,8 +88,7 @@ impl ListCommand {
 
     pub fn print_version_matches(
         all_releases: &[GodotVersion],
-        installed: &[GodotVersion],
-        active_version: &Option<GodotVersion>,
+        listing: &VersionListing,
         query: &str,
         all: bool,
     ) {
@@ -92,7 +110,7 @@ impl ListCommand {
         };
 
         // Print version matches
-        Self::print_versions(&smart_filtered, installed, active_version.as_ref());
+        Self::print_versions(&smart_filtered, listing);
 
         // Print statistics
         if count_all == 0 {
@@ -113,11 +131,7 @@ impl ListCommand {
         tracing::info!("");
     }
 
-    pub fn print_version_buffet(
-        all_versions: &[GodotVersion],
-        installed: &[GodotVersion],
-        active_version: &Option<GodotVersion>,
-    ) {
+    pub fn print_version_buffet(all_versions: &[GodotVersion], listing: &VersionListing) {
         ui::info(&format!(
             "{}",
             &"Recent Godot releases available from GitHub:"
@@ -125,17 +139,13 @@ impl ListCommand {
                 .green()
         ));
         let mut buffet = version_buffet(all_versions);
-        buffet.extend(installed);
+        buffet.extend(listing.installed);
         buffet.sort();
         buffet.dedup();
-        Self::print_versions(&buffet, installed, active_version.as_ref());
+        Self::print_versions(&buffet, listing);
     }
 
-    fn print_versions(
-        versions: &[&GodotVersion],
-        installed: &[GodotVersion],
-        active_version: Option<&GodotVersion>,
-    ) {
+    fn print_versions(versions: &[&GodotVersion], listing: &VersionListing) {
         if versions.is_empty() {
             ui::warning("No releases found");
             return;
@@ -152,21 +162,27 @@ impl ListCommand {
             } else {
                 "".to_string().normal()
             };
-            let is_installed_str = if installed.contains(release) {
+            let dotnet_str = if !release.is_dotnet && listing.dotnet_available.contains(release) {
+                " (.NET available)".cyan()
+            } else {
+                "".to_string().normal()
+            };
+            let is_installed_str = if listing.installed.contains(release) {
                 " (installed)".green()
             } else {
                 "".to_string().normal()
             };
-            let is_active_str = if active_version == Some(release) {
+            let is_active_str = if listing.active_version == Some(release) {
                 " (active)".bright_blue()
             } else {
                 "".to_string().normal()
             };
             ui::info(
                 format!(
-                    "{:width$}{}{}{}",
+                    "{:width$}{}{}{}{}",
                     version_str,
                     pre_release_str,
+                    dotnet_str,
                     is_installed_str,
                     is_active_str,
                     width = width,
@@ -1,5 +1,6 @@
 pub mod cache;
 pub mod current;
+pub mod download;
 pub mod fetch;
 pub mod install;
 pub mod list;
@@ -1,3 +1,6 @@
+pub mod addon;
+pub mod bundle;
+pub mod cache;
 pub mod editor;
 pub mod godot;
 pub mod run;
@@ -1,40 +1,196 @@
 use crate::cli::GlobalArgs;
 use crate::ui;
-use anyhow::Result;
+use anyhow::{Result, bail};
 use clap::Args;
-use gdenv_lib::addons::sync_addons;
+use colored::Colorize;
+use gdenv_lib::addon_dependencies::resolve_addon_tree;
+use gdenv_lib::addon_watcher::{AddonWatcher, WatchEvent};
+use gdenv_lib::addons::{
+    AddonSyncOutcome, AddonSyncPlan, OrphanedAddon, SyncOptions, apply_addon_plans,
+    find_orphaned_addons, plan_addons, remove_orphaned_addons, sync_addons,
+};
 use gdenv_lib::cargo::cargo_info_provider;
 use gdenv_lib::config::Config;
-use gdenv_lib::git::SystemGitClient;
+use gdenv_lib::editor_plugins::update_editor_plugins;
+use gdenv_lib::file_sync::{SyncChange, SyncChangeKind};
+use gdenv_lib::git::AutoGitClient;
 use gdenv_lib::project_specification::{ProjectSpecification, load_godot_project_spec};
+use gdenv_lib::sync_snapshot::{SavedEntryKind, SyncSnapshot, latest_snapshot};
 use std::io;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Args)]
 pub struct SyncCommand {
     /// Do not ask for confirmation before syncing
     #[arg(long, short)]
     pub yes: bool,
+
+    /// Show the changes a sync would make without changing any files
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Show text diffs of the files that would be overwritten
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Overwrite addon files that were changed by hand since the last sync
+    #[arg(long)]
+    pub force: bool,
+
+    /// Save addon files that were changed by hand as a patch, then overwrite them
+    #[arg(long, value_name = "FILE")]
+    pub save_patch: Option<PathBuf>,
+
+    /// Keep running, and re-sync local addons whenever their files or the project
+    /// configuration change
+    #[arg(long, conflicts_with = "dry_run")]
+    pub watch: bool,
+
+    /// Maximum number of addons to fetch at the same time
+    #[arg(long, short, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: u16,
+
+    /// Stop at the first addon that fails, instead of carrying on with the others
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Restore the files that the last sync of the project overwrote or removed, and remove
+    /// the files it added
+    #[arg(long, conflicts_with_all = ["dry_run", "diff", "force", "save_patch", "watch"])]
+    pub undo: bool,
 }
 
 impl SyncCommand {
+    /// A plain sync of every addon, for commands that sync after editing gdenv.toml.
+    pub(crate) fn new(yes: bool, jobs: u16) -> Self {
+        Self {
+            yes,
+            dry_run: false,
+            diff: false,
+            force: false,
+            save_patch: None,
+            watch: false,
+            jobs,
+            fail_fast: false,
+            undo: false,
+        }
+    }
+
     pub async fn run(self, global_args: GlobalArgs) -> Result<()> {
         let config = Config::setup(global_args.datadir.as_deref())?;
-        let git_client = SystemGitClient::new(config);
+        let git_client = AutoGitClient::new(config.clone());
         let working_dir = global_args.project.unwrap_or(std::env::current_dir()?);
         let spec_from_file = load_godot_project_spec(&working_dir, cargo_info_provider())?;
-        let project_spec = ProjectSpecification { ..spec_from_file };
+        if self.undo {
+            return self.undo(&config, &spec_from_file.project_root_dir);
+        }
+        let project_spec = self
+            .with_dependencies(&config, &git_client, &spec_from_file)
+            .await?;
 
-        if !self.yes {
-            ui::warning("Warning! Synchronizing addons is a potentially destructive operation.");
-            ui::warning(
-                "In order to sync, files that do not exist in the addon must be removed, which could result in the loss of data.",
+        if self.sync(&config, &git_client, &project_spec).await? && self.watch {
+            self.watch(&config, &git_client, &working_dir).await?;
+        }
+        Ok(())
+    }
+
+    /// Plans and, once confirmed, applies the sync. Returns whether the addons were synced.
+    async fn sync(
+        &self,
+        config: &Config,
+        git_client: &AutoGitClient,
+        project_spec: &ProjectSpecification,
+    ) -> Result<bool> {
+        let planned = plan_addons(
+            config,
+            project_spec,
+            git_client,
+            self.diff,
+            &self.sync_options(),
+        )
+        .await?;
+        let outcomes = &planned.outcomes;
+        let plans: Vec<&AddonSyncPlan> = outcomes
+            .iter()
+            .filter_map(|outcome| outcome.result.as_ref().ok())
+            .collect();
+        let failures: Vec<&AddonSyncOutcome> = outcomes
+            .iter()
+            .filter(|outcome| outcome.result.is_err())
+            .collect();
+        print_plans(&plans, self.diff);
+        for failure in &failures {
+            if let Err(e) = &failure.result {
+                ui::error(&format!("Addon \"{}\": {e:#}", failure.addon_name));
+            }
+        }
+        if self.fail_fast && !failures.is_empty() {
+            bail!("Stopped at the first addon that failed.");
+        }
+        let orphans = find_orphaned_addons(project_spec)?;
+        print_orphans(&orphans);
+
+        if self.dry_run {
+            ui::info("Dry run: no files were changed.");
+            return Ok(false);
+        }
+
+        let has_local_changes = plans.iter().any(|plan| !plan.local_changes.is_empty());
+        if has_local_changes {
+            if let Some(patch_path) = &self.save_patch {
+                let patch: String = plans
+                    .iter()
+                    .map(|plan| plan.local_changes_patch.as_str())
+                    .collect();
+                std::fs::write(patch_path, patch)?;
+                ui::success(&format!(
+                    "Saved the local changes to {}. Re-apply them after syncing with `git apply {}` from the project root.",
+                    patch_path.display(),
+                    patch_path.display()
+                ));
+            } else if !self.force {
+                bail!(
+                    "Refusing to overwrite addon files that were changed since the last sync (marked with !). \
+                     Use --force to overwrite them, or --save-patch <FILE> to save the changes first."
+                );
+            }
+        }
+        if !self.force
+            && let Some(orphan) = orphans.iter().find(|o| !o.local_changes.is_empty())
+        {
+            bail!(
+                "Refusing to remove the files of orphaned addon \"{}\" that were changed since the last sync (marked with !). \
+                 Use --force to remove them.",
+                orphan.addon_name
             );
-            ui::warning(
-                "Please make sure you have commited your changes and/or created a backup of your project.",
+        }
+        if plans.iter().all(|plan| plan.is_up_to_date()) && orphans.is_empty() {
+            update_plugins(project_spec, &[])?;
+            if failures.is_empty() {
+                ui::success("All addons are up to date.");
+                return Ok(true);
+            }
+            bail!(
+                "Failed to sync {} of {} addons",
+                failures.len(),
+                outcomes.len()
             );
-            ui::warning("Any manual changes to addons will be lost.");
-            ui::question("Are you ready to synchronize? [y/N]: ");
+        }
+
+        if !self.yes {
+            let destructive = !orphans.is_empty()
+                || plans
+                    .iter()
+                    .any(|plan| !plan.plan.modified.is_empty() || !plan.plan.removed.is_empty());
+            if destructive {
+                ui::warning(
+                    "Files marked as modified (~) will be overwritten and files marked as removed (-) will be deleted. \
+                     Use `gdenv sync --undo` to restore them.",
+                );
+            }
+            ui::question("Apply these changes? [y/N]: ");
             io::stdout().flush()?;
 
             let mut input = String::new();
@@ -43,12 +199,390 @@ impl SyncCommand {
             let confirmed = input.trim().to_lowercase();
             if confirmed != "y" && confirmed != "yes" {
                 ui::warning("Synchronization cancelled.");
-                return Ok(());
+                return Ok(false);
             }
         }
 
-        sync_addons(project_spec, &git_client).await?;
+        // A single snapshot, so `--undo` restores everything this sync changed
+        let snapshot = Arc::new(SyncSnapshot::new(config, &project_spec.project_root_dir)?);
+        // Apply the plans that were confirmed, instead of fetching the addons again
+        let options = SyncOptions {
+            force: self.force || self.save_patch.is_some(),
+            snapshot: Some(snapshot.clone()),
+            ..self.sync_options()
+        };
+        let synced =
+            apply_addon_plans(config, project_spec, git_client, &planned, &options).await?;
+        let mut removed_destinations = Vec::new();
+        if !orphans.is_empty() {
+            let options = SyncOptions {
+                force: self.force,
+                snapshot: Some(snapshot),
+                ..Default::default()
+            };
+            let removed = remove_orphaned_addons(config, project_spec, &options)?;
+            removed_destinations.extend(removed.into_iter().map(|orphan| orphan.destination));
+        }
+        update_plugins(project_spec, &removed_destinations)?;
 
+        let mut summary: Vec<&AddonSyncOutcome> = synced.iter().chain(failures).collect();
+        summary.sort_by(|a, b| a.addon_name.cmp(&b.addon_name));
+        print_summary(&summary);
+        let failure_count = summary.iter().filter(|o| o.result.is_err()).count();
+        if failure_count > 0 {
+            bail!(
+                "Failed to sync {} of {} addons",
+                failure_count,
+                outcomes.len()
+            );
+        }
+        ui::success("Addons synchronized.");
+
+        Ok(true)
+    }
+
+    /// Restores the most recent snapshot of the project, once confirmed.
+    fn undo(&self, config: &Config, project_root_dir: &Path) -> Result<()> {
+        let Some(snapshot) = latest_snapshot(config, project_root_dir)? else {
+            ui::info("There is no sync of this project to undo.");
+            return Ok(());
+        };
+        let display = |path: &Path| {
+            path.strip_prefix(&snapshot.info.project_root_dir)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        ui::info(&format!(
+            "Undoing the sync of {}:",
+            snapshot
+                .info
+                .created_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+        ));
+        let mut added: Vec<&PathBuf> = snapshot
+            .info
+            .added
+            .iter()
+            .filter(|path| path.exists())
+            .collect();
+        added.sort();
+        for path in &added {
+            ui::info(&format!("  - {}", display(path)).red().to_string());
+        }
+        let mut restored: Vec<&Path> = snapshot
+            .info
+            .saved
+            .iter()
+            .filter(|entry| entry.kind != SavedEntryKind::Dir)
+            .map(|entry| entry.path.as_path())
+            .collect();
+        restored.sort();
+        for path in &restored {
+            let line = if path.exists() {
+                format!("  ~ {}", display(path)).yellow()
+            } else {
+                format!("  + {}", display(path)).green()
+            };
+            ui::info(&line.to_string());
+        }
+
+        if !self.yes {
+            ui::question("Restore these files? [y/N]: ");
+            io::stdout().flush()?;
+
+            let mut input = String::new();
+            io::stdin().read_line(&mut input)?;
+
+            let confirmed = input.trim().to_lowercase();
+            if confirmed != "y" && confirmed != "yes" {
+                ui::warning("Undo cancelled.");
+                return Ok(());
+            }
+        }
+
+        snapshot.restore()?;
+        ui::success(&format!(
+            "Restored {} {} and removed {} added {}.",
+            restored.len(),
+            if restored.len() == 1 { "file" } else { "files" },
+            added.len(),
+            if added.len() == 1 { "path" } else { "paths" }
+        ));
         Ok(())
     }
+
+    /// Adds the addons that the project's addons depend on.
+    async fn with_dependencies(
+        &self,
+        config: &Config,
+        git_client: &AutoGitClient,
+        project_spec: &ProjectSpecification,
+    ) -> Result<ProjectSpecification> {
+        let tree = resolve_addon_tree(config, project_spec, git_client, self.jobs as usize).await?;
+        Ok(tree.project_spec)
+    }
+
+    fn sync_options(&self) -> SyncOptions {
+        SyncOptions {
+            jobs: self.jobs as usize,
+            fail_fast: self.fail_fast,
+            ..Default::default()
+        }
+    }
+
+    /// Re-syncs the local addons that change, and every addon when the project
+    /// configuration changes, until interrupted.
+    async fn watch(
+        &self,
+        config: &Config,
+        git_client: &AutoGitClient,
+        working_dir: &Path,
+    ) -> Result<()> {
+        let spec_from_file = load_godot_project_spec(working_dir, cargo_info_provider())?;
+        let mut project_spec = self
+            .with_dependencies(config, git_client, &spec_from_file)
+            .await?;
+        let mut watcher = AddonWatcher::new(&project_spec)?;
+        // One snapshot for the whole session, so `--undo` restores the files as they were
+        // before it, rather than piling up a snapshot per change
+        let snapshot = Arc::new(SyncSnapshot::new(config, &project_spec.project_root_dir)?);
+        ui::info("Watching local addons for changes. Press Ctrl+C to stop.");
+
+        loop {
+            let event = tokio::select! {
+                event = watcher.next_event() => event?,
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+            };
+            let addons = match event {
+                WatchEvent::SpecChanged => {
+                    let spec = match load_godot_project_spec(working_dir, cargo_info_provider()) {
+                        Ok(spec) => self.with_dependencies(config, git_client, &spec).await,
+                        Err(e) => Err(e.into()),
+                    };
+                    match spec {
+                        Ok(spec) => project_spec = spec,
+                        Err(e) => {
+                            ui::error(&format!(
+                                "Failed to reload the project configuration: {e:#}"
+                            ));
+                            continue;
+                        }
+                    }
+                    watcher.watch(&project_spec)?;
+                    ui::info("Project configuration changed, syncing all addons");
+                    None
+                }
+                WatchEvent::AddonsChanged(addons) if addons.is_empty() => continue,
+                WatchEvent::AddonsChanged(addons) => Some(addons),
+            };
+
+            let spec_changed = addons.is_none();
+            let options = SyncOptions {
+                force: self.force,
+                addons,
+                snapshot: Some(snapshot.clone()),
+                ..self.sync_options()
+            };
+            match sync_addons(config, project_spec.clone(), git_client, &options).await {
+                Ok(outcomes) => {
+                    for outcome in outcomes {
+                        match &outcome.result {
+                            Ok(plan) if plan.is_up_to_date() => {}
+                            Ok(plan) => print_plan(plan, false),
+                            Err(e) => {
+                                ui::error(&format!("Addon \"{}\": {e:#}", outcome.addon_name))
+                            }
+                        }
+                    }
+                }
+                Err(e) => ui::error(&format!("{e:#}")),
+            }
+            let mut removed_destinations = Vec::new();
+            if spec_changed {
+                match remove_orphaned_addons(config, &project_spec, &options) {
+                    Ok(orphans) => {
+                        print_orphans(&orphans);
+                        removed_destinations
+                            .extend(orphans.into_iter().map(|orphan| orphan.destination));
+                    }
+                    Err(e) => ui::error(&format!("{e:#}")),
+                }
+            }
+            if let Err(e) = update_plugins(&project_spec, &removed_destinations) {
+                ui::error(&format!("{e:#}"));
+            }
+        }
+    }
+}
+
+/// Enables the editor plugins of the synced addons in `project.godot`.
+pub(crate) fn update_plugins(
+    project_spec: &ProjectSpecification,
+    removed_destinations: &[PathBuf],
+) -> Result<()> {
+    let changes = update_editor_plugins(project_spec, removed_destinations)?;
+    for plugin in &changes.enabled {
+        ui::info(&format!("Enabled editor plugin {plugin}"));
+    }
+    for plugin in &changes.disabled {
+        ui::info(&format!("Disabled editor plugin {plugin}"));
+    }
+    Ok(())
+}
+
+fn print_plans(plans: &[&AddonSyncPlan], show_diffs: bool) {
+    for addon in plans {
+        print_plan(addon, show_diffs);
+    }
+}
+
+fn print_plan(addon: &AddonSyncPlan, show_diffs: bool) {
+    let plan = &addon.plan;
+    if addon.is_up_to_date() {
+        let linked = if addon.link {
+            format!(", linked to {}", addon.source_dir.display())
+        } else {
+            String::new()
+        };
+        ui::info(&format!(
+            "Addon \"{}\" is up to date ({}{linked}{})",
+            addon.addon_name,
+            addon.dest_dir.display(),
+            describe_resolved_tag(addon)
+        ));
+        return;
+    }
+    if addon.relink {
+        ui::info(&format!(
+            "Addon \"{}\" ({}): linked to {}",
+            addon.addon_name,
+            addon.dest_dir.display(),
+            addon.source_dir.display()
+        ));
+        if plan.is_empty() {
+            return;
+        }
+    }
+    ui::info(&format!(
+        "Addon \"{}\" ({}{}): {} added, {} modified, {} removed",
+        addon.addon_name,
+        addon.dest_dir.display(),
+        describe_resolved_tag(addon),
+        plan.added.len(),
+        plan.modified.len(),
+        plan.removed.len()
+    ));
+    for change in plan.changes() {
+        let path = display_path(change);
+        let line = match change.kind {
+            SyncChangeKind::Added => format!("  + {path}").green(),
+            SyncChangeKind::Modified => format!("  ~ {path}").yellow(),
+            SyncChangeKind::Removed => format!("  - {path}").red(),
+        };
+        if addon.local_changes.contains(&change.rel_path) {
+            ui::info(&format!(
+                "{line} {}",
+                "(! changed locally)".bright_red().bold()
+            ));
+        } else {
+            ui::info(&line.to_string());
+        }
+    }
+    if show_diffs {
+        for (_, diff) in &addon.diffs {
+            for line in diff.lines() {
+                let line = if line.starts_with("+++") || line.starts_with("---") {
+                    line.bold()
+                } else if line.starts_with('+') {
+                    line.green()
+                } else if line.starts_with('-') {
+                    line.red()
+                } else if line.starts_with("@@") {
+                    line.cyan()
+                } else {
+                    line.normal()
+                };
+                ui::info(&line.to_string());
+            }
+        }
+    }
+}
+
+/// The tag and commit a `version` requirement resolved to, e.g. ", tag v1.2.0 at 1a2b3c4d".
+fn describe_resolved_tag(addon: &AddonSyncPlan) -> String {
+    match &addon.resolved_tag {
+        Some(tag) => format!(
+            ", tag {} at {}",
+            tag.name,
+            &tag.commit[..tag.commit.len().min(8)]
+        ),
+        None => String::new(),
+    }
+}
+
+fn print_summary(outcomes: &[&AddonSyncOutcome]) {
+    tracing::info!("");
+    ui::info("Sync summary:");
+    for outcome in outcomes {
+        let elapsed = outcome.elapsed.as_secs_f64();
+        match &outcome.result {
+            Ok(plan) => ui::success(&format!(
+                "{}: synced in {elapsed:.1}s{}",
+                outcome.addon_name,
+                describe_resolved_tag(plan)
+            )),
+            Err(e) => ui::error(&format!(
+                "{}: failed after {elapsed:.1}s: {e:#}",
+                outcome.addon_name
+            )),
+        }
+    }
+}
+
+fn print_orphans(orphans: &[OrphanedAddon]) {
+    for orphan in orphans {
+        if orphan.link {
+            ui::info(&format!(
+                "Addon \"{}\" ({}) is no longer in the project configuration: its link will be removed",
+                orphan.addon_name,
+                orphan.dest_dir.display()
+            ));
+            continue;
+        }
+        ui::info(&format!(
+            "Addon \"{}\" ({}) is no longer in the project configuration: {} removed",
+            orphan.addon_name,
+            orphan.dest_dir.display(),
+            orphan.plan.removed.len()
+        ));
+        for change in orphan.plan.changes() {
+            let path = display_path(change);
+            let line = format!("  - {path}").red();
+            if orphan.local_changes.contains(&change.rel_path) {
+                ui::info(&format!(
+                    "{line} {}",
+                    "(! changed locally)".bright_red().bold()
+                ));
+            } else {
+                ui::info(&line.to_string());
+            }
+        }
+        for path in &orphan.kept {
+            ui::info(
+                &format!("  = {} (not synced by gdenv, kept)", path.display())
+                    .dimmed()
+                    .to_string(),
+            );
+        }
+    }
+}
+
+fn display_path(change: &SyncChange) -> String {
+    if change.is_dir {
+        format!("{}/", change.rel_path.display())
+    } else {
+        change.rel_path.display().to_string()
+    }
 }